@@ -0,0 +1,750 @@
+//! Aircraft definition files, describing an airframe's wings, mass and engine in TOML
+//! so that new airframes can be tuned without recompiling.
+
+use std::{error::Error, path::Path};
+
+use rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    airfoil::Airfoil,
+    autopilot::PidGains,
+    control_input::{Actuator, Actuators, Centering},
+    mqo::load_mqo_scale,
+    vehicle::Control,
+};
+
+pub(crate) const DEFAULT_AIRCRAFT: &str = "assets/F15.toml";
+
+/// Validated aircraft definition.
+pub(crate) struct AircraftDef {
+    pub name: String,
+    /// Path to the Metasequoia model of the airframe
+    pub model: String,
+    /// Scale factor applied to the model's vertices
+    pub model_scale: f32,
+    /// Mass of the airframe without fuel and payload
+    pub mass: f32,
+    /// Center of gravity of the airframe without fuel and payload, in the body frame
+    pub center_of_mass: Vector<f32>,
+    /// Principal moments of inertia of the airframe about the center of gravity, along
+    /// the body axes [kg m^2]
+    pub inertia: Vector<f32>,
+    /// Full extents of the airframe's box collider
+    pub collider_size: Vector<f32>,
+    /// Triangles of the model's airframe, whose convex decomposition replaces the box
+    /// collider
+    pub hull: Option<Hull>,
+    /// Eye point of the pilot for the cockpit camera, in the body frame
+    pub pilot_eye: Vector<f32>,
+    pub engine: EngineDef,
+    pub wings: Vec<WingDef>,
+    pub gears: Vec<GearDef>,
+    pub stations: Vec<StationDef>,
+    pub autopilot: AutopilotDef,
+    pub actuators: Actuators,
+}
+
+/// Triangle mesh of the airframe in the body frame
+pub(crate) struct Hull {
+    pub vertices: Vec<Point<f32>>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+/// Validated engine definition.
+pub(crate) struct EngineDef {
+    /// Maximum thrust without the afterburner [N]
+    pub thrust: f32,
+    /// Maximum thrust with the afterburner [N], same as `thrust` without an afterburner
+    pub afterburner_thrust: f32,
+    /// N1 at idle, relative to the maximum
+    pub idle: f32,
+    /// Time constant of the N1 following the throttle [s]
+    pub spool_time: f32,
+    /// Initial fuel [kg]
+    pub fuel: f32,
+    /// Fuel flow at the maximum thrust without the afterburner [kg/s]
+    pub fuel_flow: f32,
+    /// Additional fuel flow of the full afterburner [kg/s]
+    pub afterburner_fuel_flow: f32,
+}
+
+/// Validated definition of a landing gear strut and its wheel.
+pub(crate) struct GearDef {
+    pub name: String,
+    /// Attachment point of the strut in the body frame
+    pub pos: Vector<f32>,
+    /// Length of the strut including the wheel radius, fully extended [m]
+    pub length: f32,
+    /// Spring constant of the suspension [N/m]
+    pub stiffness: f32,
+    /// Damping coefficient of the suspension [N s/m]
+    pub damping: f32,
+    /// Steering angle at full rudder in radians, zero for a fixed wheel
+    pub steering: f32,
+    /// Friction coefficient at full brake, zero for a wheel without brakes
+    pub brake: f32,
+    /// Drag area while extended [m^2]
+    pub drag: f32,
+}
+
+/// Validated gains and limits of the autopilot loops. Angles are in degrees.
+#[derive(Clone, Debug)]
+pub(crate) struct AutopilotDef {
+    /// Bank angle error to aileron
+    pub roll: PidGains,
+    /// Pitch attitude error to elevator
+    pub pitch: PidGains,
+    /// Heading error to bank angle, proportional only
+    pub heading: PidGains,
+    /// Altitude error to vertical speed, proportional only
+    pub altitude: PidGains,
+    /// Vertical speed error to pitch attitude
+    pub vertical_speed: PidGains,
+    /// Sideslip angle to rudder
+    pub sideslip: PidGains,
+    /// Airspeed error to thrust
+    pub airspeed: PidGains,
+    /// Maximum bank angle commanded by the heading hold
+    pub max_bank: f32,
+    /// Maximum pitch attitude commanded by the vertical modes
+    pub max_pitch: f32,
+    /// Maximum vertical speed commanded by the altitude hold [m/s]
+    pub max_vertical_speed: f32,
+}
+
+/// Validated definition of a payload or fuel station.
+pub(crate) struct StationDef {
+    pub name: String,
+    /// Position of the station's center of gravity in the body frame
+    pub pos: Vector<f32>,
+    /// Payload mass [kg]
+    pub mass: f32,
+    /// Initial fuel in the station's tank [kg]
+    pub fuel: f32,
+}
+
+/// Validated definition of a single wing or control surface.
+pub(crate) struct WingDef {
+    pub name: String,
+    /// Position of the wing's center in the body frame
+    pub pos: Vector<f32>,
+    /// The aerodynamic tensor, defines how force is applied to the wing.
+    pub aero: Matrix<f32>,
+    pub control: Control,
+    /// The aerodynamic tensor is rotated around this axis if this control surface is manipulated.
+    pub axis: Vector<f32>,
+    /// Deflection of this control surface in radians at full control input.
+    pub sensitivity: f32,
+    /// Nonlinear lift and drag replacing the aerodynamic tensor in the plane of the airfoil
+    pub airfoil: Option<Airfoil>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AircraftFile {
+    name: String,
+    model: String,
+    #[serde(default = "default_model_scale")]
+    model_scale: f32,
+    mass: f32,
+    #[serde(default)]
+    center_of_mass: [f32; 3],
+    /// Principal moments of inertia, defaults to those of the collider box
+    inertia: Option<[f32; 3]>,
+    collider_size: [f32; 3],
+    /// Builds the collider from the model instead of the box
+    #[serde(default)]
+    hull: bool,
+    #[serde(default)]
+    pilot_eye: [f32; 3],
+    engine: EngineFile,
+    #[serde(default)]
+    wings: Vec<WingFile>,
+    #[serde(default)]
+    gears: Vec<GearFile>,
+    #[serde(default)]
+    stations: Vec<StationFile>,
+    #[serde(default)]
+    autopilot: AutopilotFile,
+    #[serde(default)]
+    actuators: ActuatorsFile,
+}
+
+/// Actuators of the control channels, each defaulting to the fields left out
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ActuatorsFile {
+    thrust: ActuatorFile,
+    aileron: ActuatorFile,
+    elevator: ActuatorFile,
+    rudder: ActuatorFile,
+    brake: ActuatorFile,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ActuatorFile {
+    rate: Option<f32>,
+    lag: Option<f32>,
+    centering: Option<Centering>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AutopilotFile {
+    roll: PidGains,
+    pitch: PidGains,
+    heading: PidGains,
+    altitude: PidGains,
+    vertical_speed: PidGains,
+    sideslip: PidGains,
+    airspeed: PidGains,
+    max_bank: f32,
+    max_pitch: f32,
+    max_vertical_speed: f32,
+}
+
+impl Default for AutopilotFile {
+    fn default() -> Self {
+        let gains = |kp, ki, kd| PidGains { kp, ki, kd };
+        Self {
+            roll: gains(0.01, 0.002, 0.005),
+            pitch: gains(0.1, 0.05, 0.02),
+            heading: gains(3., 0., 0.),
+            altitude: gains(0.2, 0., 0.),
+            vertical_speed: gains(0.5, 0.2, 0.),
+            sideslip: gains(0.1, 0., 0.),
+            airspeed: gains(0.05, 0.02, 0.),
+            max_bank: 30.,
+            max_pitch: 15.,
+            max_vertical_speed: 10.,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StationFile {
+    name: String,
+    pos: [f32; 3],
+    #[serde(default)]
+    mass: f32,
+    #[serde(default)]
+    fuel: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EngineFile {
+    thrust: f32,
+    afterburner_thrust: Option<f32>,
+    #[serde(default = "default_idle")]
+    idle: f32,
+    #[serde(default = "default_spool_time")]
+    spool_time: f32,
+    #[serde(default)]
+    fuel: f32,
+    #[serde(default)]
+    fuel_flow: f32,
+    #[serde(default)]
+    afterburner_fuel_flow: f32,
+}
+
+fn default_idle() -> f32 {
+    0.6
+}
+
+fn default_spool_time() -> f32 {
+    1.
+}
+
+fn default_model_scale() -> f32 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WingFile {
+    name: String,
+    pos: [f32; 3],
+    /// Rows of the 3x3 aerodynamic tensor
+    aero: Vec<Vec<f32>>,
+    #[serde(default)]
+    control: Option<String>,
+    #[serde(default = "default_axis")]
+    axis: [f32; 3],
+    /// Deflection in degrees at full control input
+    #[serde(default)]
+    sensitivity: f32,
+    #[serde(default)]
+    airfoil: Option<AirfoilFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AirfoilFile {
+    area: f32,
+    #[serde(default = "default_normal")]
+    normal: [f32; 3],
+    /// Rows of angle of attack in degrees and lift coefficient
+    lift: Vec<[f32; 2]>,
+    /// Rows of angle of attack in degrees and drag coefficient
+    drag: Vec<[f32; 2]>,
+    /// Angle of attack in degrees at which the wing stalls
+    stall_angle: f32,
+    /// Angle of attack in degrees below which the stalled wing recovers, defaults to
+    /// `stall_angle`
+    reattach_angle: Option<f32>,
+}
+
+fn default_normal() -> [f32; 3] {
+    [0., 1., 0.]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GearFile {
+    name: String,
+    pos: [f32; 3],
+    length: f32,
+    stiffness: f32,
+    damping: f32,
+    /// Steering angle in degrees at full rudder
+    #[serde(default)]
+    steering: f32,
+    #[serde(default)]
+    brake: f32,
+    #[serde(default)]
+    drag: f32,
+}
+
+fn default_axis() -> [f32; 3] {
+    [1., 0., 0.]
+}
+
+impl AircraftDef {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read aircraft file {}: {e}", path.display()))?;
+        Self::parse(&src).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let file: AircraftFile = toml::from_str(src)?;
+
+        if !(file.mass.is_finite() && 0. < file.mass) {
+            return Err(format!("mass must be a positive number, got {}", file.mass).into());
+        }
+        if file
+            .collider_size
+            .iter()
+            .any(|v| !(v.is_finite() && 0. < *v))
+        {
+            return Err(format!(
+                "collider_size must have 3 positive numbers, got {:?}",
+                file.collider_size
+            )
+            .into());
+        }
+        if file.center_of_mass.iter().any(|v| !v.is_finite()) {
+            return Err(format!(
+                "center_of_mass must be finite, got {:?}",
+                file.center_of_mass
+            )
+            .into());
+        }
+        let collider_size = Vector::from(file.collider_size);
+        let inertia = match file.inertia {
+            Some(inertia) => {
+                if inertia.iter().any(|v| !(v.is_finite() && 0. < *v)) {
+                    return Err(
+                        format!("inertia must have 3 positive numbers, got {inertia:?}").into(),
+                    );
+                }
+                inertia.into()
+            }
+            None => box_inertia(file.mass, &collider_size),
+        };
+        let hull = if file.hull {
+            Some(
+                load_hull(&file.model, file.model_scale)
+                    .map_err(|e| format!("hull: {}: {e}", file.model))?,
+            )
+        } else {
+            None
+        };
+        let engine = EngineDef::from_file(file.engine).map_err(|e| format!("engine: {e}"))?;
+        let autopilot =
+            AutopilotDef::from_file(file.autopilot).map_err(|e| format!("autopilot: {e}"))?;
+        let actuators =
+            actuators_from_file(file.actuators).map_err(|e| format!("actuators: {e}"))?;
+
+        let wings = file
+            .wings
+            .into_iter()
+            .map(WingDef::from_file)
+            .collect::<Result<_, _>>()?;
+        let gears = file
+            .gears
+            .into_iter()
+            .map(GearDef::from_file)
+            .collect::<Result<_, _>>()?;
+        let stations = file
+            .stations
+            .into_iter()
+            .map(StationDef::from_file)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name: file.name,
+            model: file.model,
+            model_scale: file.model_scale,
+            mass: file.mass,
+            center_of_mass: file.center_of_mass.into(),
+            inertia,
+            collider_size,
+            hull,
+            pilot_eye: file.pilot_eye.into(),
+            engine,
+            wings,
+            gears,
+            stations,
+            autopilot,
+            actuators,
+        })
+    }
+}
+
+/// Principal moments of inertia of a uniform box with the mass and the full extents
+fn box_inertia(mass: f32, size: &Vector<f32>) -> Vector<f32> {
+    let sq = size.component_mul(size);
+    vector![sq.y + sq.z, sq.x + sq.z, sq.x + sq.y] * mass / 12.
+}
+
+/// Loads the triangles of the airframe, the first object of the model, in the body frame.
+fn load_hull(model: &str, scale: f32) -> Result<Hull, String> {
+    let file = std::fs::File::open(model).map_err(|e| e.to_string())?;
+    let meshes = load_mqo_scale(&mut std::io::BufReader::new(file), None, scale, &|| ())
+        .map_err(|e| e.to_string())?;
+    let Some(mesh) = meshes.first() else {
+        return Err("the model has no objects".to_string());
+    };
+    // The model faces backwards, so it is rendered turned around the y axis.
+    let vertices = mesh
+        .positions
+        .to_f32()
+        .iter()
+        .map(|v| point![-v.x, v.y, -v.z])
+        .collect();
+    let indices = mesh
+        .indices
+        .to_u32()
+        .ok_or("the model has no indices")?
+        .chunks_exact(3)
+        .map(|face| [face[0], face[1], face[2]])
+        .collect();
+    Ok(Hull { vertices, indices })
+}
+
+impl EngineDef {
+    fn from_file(engine: EngineFile) -> Result<Self, String> {
+        let non_negative = |name: &str, v: f32| {
+            if v.is_finite() && 0. <= v {
+                Ok(v)
+            } else {
+                Err(format!("{name} must be a non-negative number, got {v}"))
+            }
+        };
+        let thrust = non_negative("thrust", engine.thrust)?;
+        let afterburner_thrust = engine.afterburner_thrust.unwrap_or(thrust);
+        if !(afterburner_thrust.is_finite() && thrust <= afterburner_thrust) {
+            return Err(format!(
+                "afterburner_thrust must not be less than thrust, got {afterburner_thrust}"
+            ));
+        }
+        if !(0. <= engine.idle && engine.idle < 1.) {
+            return Err(format!(
+                "idle must be in the range [0, 1), got {}",
+                engine.idle
+            ));
+        }
+        if !(engine.spool_time.is_finite() && 0. < engine.spool_time) {
+            return Err(format!(
+                "spool_time must be positive, got {}",
+                engine.spool_time
+            ));
+        }
+        Ok(Self {
+            thrust,
+            afterburner_thrust,
+            idle: engine.idle,
+            spool_time: engine.spool_time,
+            fuel: non_negative("fuel", engine.fuel)?,
+            fuel_flow: non_negative("fuel_flow", engine.fuel_flow)?,
+            afterburner_fuel_flow: non_negative(
+                "afterburner_fuel_flow",
+                engine.afterburner_fuel_flow,
+            )?,
+        })
+    }
+}
+
+impl GearDef {
+    fn from_file(gear: GearFile) -> Result<Self, String> {
+        let name = gear.name;
+        let check = |field: &str, v: f32, min: f32| {
+            if v.is_finite() && min <= v {
+                Ok(v)
+            } else {
+                Err(format!(
+                    "gear \"{name}\": {field} must be a number not less than {min}, got {v}"
+                ))
+            }
+        };
+        if gear.pos.iter().any(|v| !v.is_finite()) || !gear.steering.is_finite() {
+            return Err(format!("gear \"{name}\": pos and steering must be finite"));
+        }
+        Ok(Self {
+            pos: gear.pos.into(),
+            length: check("length", gear.length, f32::EPSILON)?,
+            stiffness: check("stiffness", gear.stiffness, 0.)?,
+            damping: check("damping", gear.damping, 0.)?,
+            steering: gear.steering.to_radians(),
+            brake: check("brake", gear.brake, 0.)?,
+            drag: check("drag", gear.drag, 0.)?,
+            name,
+        })
+    }
+}
+
+impl AutopilotDef {
+    fn from_file(autopilot: AutopilotFile) -> Result<Self, String> {
+        let gains = |name: &str, gains: PidGains| {
+            if [gains.kp, gains.ki, gains.kd].iter().all(|v| v.is_finite()) {
+                Ok(gains)
+            } else {
+                Err(format!("{name} gains must be finite, got {gains:?}"))
+            }
+        };
+        let limit = |name: &str, v: f32| {
+            if v.is_finite() && 0. < v {
+                Ok(v)
+            } else {
+                Err(format!("{name} must be a positive number, got {v}"))
+            }
+        };
+        Ok(Self {
+            roll: gains("roll", autopilot.roll)?,
+            pitch: gains("pitch", autopilot.pitch)?,
+            heading: gains("heading", autopilot.heading)?,
+            altitude: gains("altitude", autopilot.altitude)?,
+            vertical_speed: gains("vertical_speed", autopilot.vertical_speed)?,
+            sideslip: gains("sideslip", autopilot.sideslip)?,
+            airspeed: gains("airspeed", autopilot.airspeed)?,
+            max_bank: limit("max_bank", autopilot.max_bank)?,
+            max_pitch: limit("max_pitch", autopilot.max_pitch)?,
+            max_vertical_speed: limit("max_vertical_speed", autopilot.max_vertical_speed)?,
+        })
+    }
+}
+
+fn actuators_from_file(file: ActuatorsFile) -> Result<Actuators, String> {
+    let actuator = |name: &str, default: Actuator, file: ActuatorFile| {
+        let rate = file.rate.unwrap_or(default.rate);
+        if !(rate.is_finite() && 0. < rate) {
+            return Err(format!("{name} rate must be a positive number, got {rate}"));
+        }
+        let lag = file.lag.unwrap_or(default.lag);
+        if !(lag.is_finite() && 0. <= lag) {
+            return Err(format!(
+                "{name} lag must be a non-negative number, got {lag}"
+            ));
+        }
+        Ok(Actuator {
+            rate,
+            lag,
+            centering: file.centering.unwrap_or(default.centering),
+        })
+    };
+    let default = Actuators::default();
+    Ok(Actuators {
+        thrust: actuator("thrust", default.thrust, file.thrust)?,
+        aileron: actuator("aileron", default.aileron, file.aileron)?,
+        elevator: actuator("elevator", default.elevator, file.elevator)?,
+        rudder: actuator("rudder", default.rudder, file.rudder)?,
+        brake: actuator("brake", default.brake, file.brake)?,
+    })
+}
+
+impl StationDef {
+    fn from_file(station: StationFile) -> Result<Self, String> {
+        let name = station.name;
+        if station.pos.iter().any(|v| !v.is_finite()) {
+            return Err(format!("station \"{name}\": pos must be finite"));
+        }
+        let check = |field: &str, v: f32| {
+            if v.is_finite() && 0. <= v {
+                Ok(v)
+            } else {
+                Err(format!(
+                    "station \"{name}\": {field} must be a non-negative number, got {v}"
+                ))
+            }
+        };
+        Ok(Self {
+            pos: station.pos.into(),
+            mass: check("mass", station.mass)?,
+            fuel: check("fuel", station.fuel)?,
+            name,
+        })
+    }
+}
+
+impl WingDef {
+    fn from_file(wing: WingFile) -> Result<Self, String> {
+        let name = wing.name;
+        let aero = parse_tensor(&wing.aero).map_err(|e| format!("wing \"{name}\": {e}"))?;
+        let control = match wing.control {
+            Some(s) => s.parse().map_err(|e| format!("wing \"{name}\": {e}"))?,
+            None => Control::None,
+        };
+        let axis = Vector::from(wing.axis);
+        if axis.iter().any(|v| !v.is_finite()) || axis.norm() == 0. {
+            return Err(format!(
+                "wing \"{name}\": axis must be a non-zero vector, got {:?}",
+                wing.axis
+            ));
+        }
+        let airfoil = wing
+            .airfoil
+            .map(|airfoil| {
+                Airfoil::new(
+                    airfoil.area,
+                    airfoil.normal.into(),
+                    &airfoil.lift,
+                    &airfoil.drag,
+                    airfoil.stall_angle,
+                    airfoil.reattach_angle.unwrap_or(airfoil.stall_angle),
+                )
+            })
+            .transpose()
+            .map_err(|e| format!("wing \"{name}\": {e}"))?;
+        Ok(Self {
+            pos: wing.pos.into(),
+            aero,
+            control,
+            axis: axis.normalize(),
+            sensitivity: wing.sensitivity.to_radians(),
+            airfoil,
+            name,
+        })
+    }
+}
+
+fn parse_tensor(rows: &[Vec<f32>]) -> Result<Matrix<f32>, String> {
+    if rows.len() != 3 {
+        return Err(format!("aero tensor must have 3 rows, got {}", rows.len()));
+    }
+    for (i, row) in rows.iter().enumerate() {
+        if row.len() != 3 {
+            return Err(format!(
+                "aero tensor row {i} must have 3 elements, got {}",
+                row.len()
+            ));
+        }
+        if let Some(v) = row.iter().find(|v| !v.is_finite()) {
+            return Err(format!("aero tensor row {i} has a non-finite element {v}"));
+        }
+    }
+    Ok(Matrix::from_fn(|r, c| rows[r][c]))
+}
+
+#[test]
+fn test_default_aircraft() {
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    assert_eq!(aircraft.wings.len(), 6);
+    assert_eq!(aircraft.wings[0].control, Control::Aileron);
+    assert_eq!(aircraft.wings[0].aero[(1, 1)], -390.);
+    assert_eq!(aircraft.wings[0].aero[(2, 1)], -36.);
+    assert!(aircraft.wings[0].airfoil.is_some());
+    assert!(aircraft.wings[4].airfoil.is_none());
+    assert_eq!(aircraft.gears.len(), 3);
+    assert!(0. < aircraft.gears[0].steering);
+    assert!(aircraft.hull.is_none());
+    let box_inertia = box_inertia(aircraft.mass, &aircraft.collider_size);
+    assert!((aircraft.inertia - box_inertia).norm() < 10.);
+    assert_eq!(aircraft.autopilot.heading.kp, 3.);
+    assert_eq!(aircraft.actuators.aileron.centering, Centering::Spring);
+    assert_eq!(aircraft.actuators.brake.rate, 4.);
+
+    // The hull is loaded from the model.
+    let src = std::fs::read_to_string(DEFAULT_AIRCRAFT).unwrap();
+    let aircraft = AircraftDef::parse(&format!("hull = true\n{src}")).unwrap();
+    let hull = aircraft.hull.unwrap();
+    assert!(!hull.indices.is_empty());
+    assert!(hull
+        .indices
+        .iter()
+        .flatten()
+        .all(|i| (*i as usize) < hull.vertices.len()));
+}
+
+#[test]
+fn test_aircraft_errors() {
+    const HEADER: &str = r#"
+name = "Test"
+model = "test.mqo"
+mass = 1.0
+collider_size = [1.0, 1.0, 1.0]
+
+[engine]
+thrust = 1.0
+"#;
+    let parse_err = |wing: &str| {
+        AircraftDef::parse(&format!("{HEADER}[[wings]]\n{wing}"))
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        parse_err("name = \"W\"\npos = [0.0, 0.0, 0.0]\naero = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]"),
+        "wing \"W\": aero tensor must have 3 rows, got 2"
+    );
+    assert_eq!(
+        parse_err("name = \"W\"\npos = [0.0, 0.0, 0.0]\naero = [[1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]"),
+        "wing \"W\": aero tensor row 0 must have 3 elements, got 2"
+    );
+    assert_eq!(
+        parse_err("name = \"W\"\npos = [0.0, 0.0, 0.0]\naero = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]\ncontrol = \"flap\""),
+        "wing \"W\": unknown control \"flap\", expected one of none, aileron, elevator or rudder"
+    );
+    assert_eq!(
+        parse_err("name = \"W\"\npos = [0.0, 0.0, 0.0]\naero = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]\nairfoil = { area = 1.0, lift = [[0.0, 0.0], [0.0, 1.0]], drag = [[0.0, 0.0], [1.0, 1.0]], stall_angle = 15.0 }"),
+        "wing \"W\": airfoil lift table must have strictly increasing angles"
+    );
+    assert_eq!(
+        AircraftDef::parse(&format!(
+            "{HEADER}[[stations]]\nname = \"S\"\npos = [0.0, 0.0, 0.0]\nfuel = -1.0"
+        ))
+        .err()
+        .unwrap()
+        .to_string(),
+        "station \"S\": fuel must be a non-negative number, got -1"
+    );
+    assert_eq!(
+        AircraftDef::parse(&format!("{HEADER}[autopilot]\nmax_bank = 0.0"))
+            .err()
+            .unwrap()
+            .to_string(),
+        "autopilot: max_bank must be a positive number, got 0"
+    );
+    assert_eq!(
+        AircraftDef::parse(&format!("{HEADER}[actuators]\nrudder = {{ lag = -0.1 }}"))
+            .err()
+            .unwrap()
+            .to_string(),
+        "actuators: rudder lag must be a non-negative number, got -0.1"
+    );
+}
@@ -0,0 +1,205 @@
+//! Nonlinear lift and drag of a wing section, tabulated over the angle of attack.
+//!
+//! Above the stall angle the flow separates and the wing behaves like a flat plate,
+//! losing lift and gaining drag, until the angle of attack falls below the reattach angle.
+//! The gap between the two angles gives the stall hysteresis.
+
+use rapier3d::prelude::*;
+
+/// Lift and drag coefficient tables of a wing.
+#[derive(Clone, Debug)]
+pub(crate) struct Airfoil {
+    /// Reference area [m^2]
+    pub area: f32,
+    /// Unit normal of the surface in the wing frame, the direction of the lift at zero
+    /// angle of attack. The chord is along -z, so a vertical surface has a normal along x
+    /// and its angle of attack is the sideslip.
+    pub normal: Vector<f32>,
+    /// Pairs of angle of attack [rad] and lift coefficient, sorted by the angle
+    lift: Vec<(f32, f32)>,
+    /// Pairs of angle of attack [rad] and drag coefficient, sorted by the angle
+    drag: Vec<(f32, f32)>,
+    /// Angle of attack at which the flow separates [rad]
+    stall_angle: f32,
+    /// Angle of attack below which the separated flow reattaches [rad]
+    reattach_angle: f32,
+}
+
+impl Airfoil {
+    /// Creates an airfoil from tables of angles of attack in degrees and coefficients.
+    pub fn new(
+        area: f32,
+        normal: Vector<f32>,
+        lift: &[[f32; 2]],
+        drag: &[[f32; 2]],
+        stall_angle: f32,
+        reattach_angle: f32,
+    ) -> Result<Self, String> {
+        if !(area.is_finite() && 0. < area) {
+            return Err(format!("airfoil area must be positive, got {area}"));
+        }
+        if normal.iter().any(|v| !v.is_finite()) || normal.norm() == 0. {
+            return Err(format!(
+                "airfoil normal must be a non-zero vector, got {normal:?}"
+            ));
+        }
+        let normal = normal.normalize();
+        if 1e-3 < normal.z.abs() {
+            return Err("airfoil normal must be perpendicular to the chord (z axis)".to_string());
+        }
+        let valid = stall_angle.is_finite()
+            && 0. < reattach_angle
+            && reattach_angle <= stall_angle
+            && stall_angle <= 180.;
+        if !valid {
+            return Err(format!(
+                "airfoil angles must satisfy 0 < reattach_angle <= stall_angle <= 180, got {reattach_angle} and {stall_angle}"
+            ));
+        }
+        Ok(Self {
+            area,
+            normal,
+            lift: parse_table("lift", lift)?,
+            drag: parse_table("drag", drag)?,
+            stall_angle: stall_angle.to_radians(),
+            reattach_angle: reattach_angle.to_radians(),
+        })
+    }
+
+    /// Returns the lift and drag coefficients at the angle of attack `alpha` in radians.
+    /// `stalled` is the state of the flow separation, updated by this angle of attack.
+    pub fn coefficients(&self, alpha: f32, stalled: &mut bool) -> (f32, f32) {
+        if *stalled {
+            *stalled = self.reattach_angle <= alpha.abs();
+        } else {
+            *stalled = self.stall_angle < alpha.abs();
+        }
+        let lift = interpolate(&self.lift, alpha);
+        let drag = interpolate(&self.drag, alpha);
+        if !*stalled {
+            return (lift, drag);
+        }
+        // Separated flow can't make more lift or less drag than a flat plate.
+        let plate_lift = (2. * alpha).sin();
+        let plate_drag = 2. * alpha.sin().powi(2);
+        let lift = if plate_lift.abs() < lift.abs() {
+            plate_lift
+        } else {
+            lift
+        };
+        (lift, drag.max(plate_drag))
+    }
+
+    /// Returns the aerodynamic force in the wing frame, given the velocity of the wing
+    /// relative to the air in the wing frame and the air density [kg/m^3].
+    ///
+    /// Only the velocity in the plane of the chord and the normal contributes, the spanwise
+    /// component is left to the linear aerodynamic tensor.
+    pub fn force(&self, velocity: &Vector<f32>, density: f32, stalled: &mut bool) -> Vector<f32> {
+        let chord = -Vector::z();
+        let forward = velocity.dot(&chord);
+        let up = velocity.dot(&self.normal);
+        let speed2 = forward * forward + up * up;
+        if speed2 == 0. {
+            return Vector::zeros();
+        }
+        // Wing moving downwards through the air sees a positive angle of attack.
+        let alpha = (-up).atan2(forward);
+        let (lift, drag) = self.coefficients(alpha, stalled);
+        let dynamic_pressure = 0.5 * density * speed2 * self.area;
+        let (sin, cos) = alpha.sin_cos();
+        let lift_dir = self.normal * cos + chord * sin;
+        let flow_dir = chord * cos - self.normal * sin;
+        (lift_dir * lift - flow_dir * drag) * dynamic_pressure
+    }
+
+    /// Returns the component of the velocity along the span, which the airfoil doesn't
+    /// act on.
+    pub fn spanwise(&self, velocity: &Vector<f32>) -> Vector<f32> {
+        let span = self.normal.cross(&Vector::z());
+        span * span.dot(velocity)
+    }
+}
+
+fn parse_table(name: &str, table: &[[f32; 2]]) -> Result<Vec<(f32, f32)>, String> {
+    if table.len() < 2 {
+        return Err(format!(
+            "airfoil {name} table must have at least 2 rows, got {}",
+            table.len()
+        ));
+    }
+    if let Some(row) = table.iter().find(|row| row.iter().any(|v| !v.is_finite())) {
+        return Err(format!("airfoil {name} table has a non-finite row {row:?}"));
+    }
+    if table.windows(2).any(|rows| rows[1][0] <= rows[0][0]) {
+        return Err(format!(
+            "airfoil {name} table must have strictly increasing angles"
+        ));
+    }
+    Ok(table
+        .iter()
+        .map(|[angle, coef]| (angle.to_radians(), *coef))
+        .collect())
+}
+
+/// Linearly interpolates the table, clamping at its ends.
+fn interpolate(table: &[(f32, f32)], x: f32) -> f32 {
+    let i = table.partition_point(|(angle, _)| *angle <= x);
+    if i == 0 {
+        return table[0].1;
+    }
+    let Some(&(x1, y1)) = table.get(i) else {
+        return table[table.len() - 1].1;
+    };
+    let (x0, y0) = table[i - 1];
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+#[test]
+fn test_stall_hysteresis() {
+    let airfoil = Airfoil::new(
+        1.,
+        Vector::y(),
+        &[[-20., -0.8], [-15., -1.2], [0., 0.], [15., 1.2], [20., 0.8]],
+        &[
+            [-90., 1.2],
+            [-15., 0.05],
+            [0., 0.02],
+            [15., 0.05],
+            [90., 1.2],
+        ],
+        15.,
+        10.,
+    )
+    .unwrap();
+    let mut stalled = false;
+    let coefficients =
+        |alpha: f32, stalled: &mut bool| airfoil.coefficients(alpha.to_radians(), stalled);
+
+    let (lift, _) = coefficients(7.5, &mut stalled);
+    assert!((lift - 0.6).abs() < 1e-5);
+    assert!(!stalled);
+    let (attached_lift, attached_drag) = coefficients(12., &mut stalled);
+    assert!(!stalled);
+    coefficients(16., &mut stalled);
+    assert!(stalled);
+
+    // Stays stalled on the way back until the reattach angle.
+    let (lift, drag) = coefficients(12., &mut stalled);
+    assert!(stalled);
+    assert!(lift < attached_lift && attached_drag < drag);
+    let (lift, _) = coefficients(9., &mut stalled);
+    assert!(!stalled);
+    assert!((lift - 0.72).abs() < 1e-5);
+
+    // Beyond the table, the ends are held.
+    assert_eq!(interpolate(&airfoil.lift, -1.), -0.8);
+
+    // A wing moving forward and down at 10 degrees gets lift upwards and drag against
+    // the airflow.
+    let velocity = vector![0., -10f32.to_radians().sin(), -10f32.to_radians().cos()] * 100.;
+    let force = airfoil.force(&velocity, 1., &mut stalled);
+    let lift = 0.5 * 100. * 100. * 0.8;
+    assert!(lift * 0.95 < force.y && force.y < lift);
+    assert!(force.dot(&velocity) < 0.);
+}
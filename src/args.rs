@@ -0,0 +1,141 @@
+//! Command line arguments.
+
+use crate::{
+    aircraft::DEFAULT_AIRCRAFT,
+    bindings::DEFAULT_BINDINGS,
+    timestep::{DEFAULT_MAX_SUBSTEPS, DEFAULT_STEP_RATE},
+    ui::DEFAULT_LAYOUT,
+};
+
+const USAGE: &str = "Usage: rusflight [options] [aircraft.toml]
+
+Options:
+    --headless         Run the simulation without a window
+    --script FILE      Control script for headless mode
+    --output FILE      Trajectory output of headless mode (default: trajectory.csv)
+    --duration SECS    Simulated time in headless mode (default: 60)
+    --rate HZ          Physics step rate (default: 60)
+    --substeps N       Maximum physics steps per rendered frame (default: 8)
+    --weather FILE     Wind, gusts and turbulence definition
+    --record FILE      Record every simulation tick to a flight data file
+    --replay FILE      Play back a flight data file instead of simulating
+    --trim SPEED,ALT   Start in trimmed level flight at the airspeed (m/s) and altitude (m)
+    --plan FILE        Flight plan of waypoints for the navigation modes of the autopilot
+    --gamepad FILE     Axis mapping of gamepads and joysticks (requires the gamepad feature)
+    --bindings FILE    Key and mouse bindings (default: assets/bindings.toml)
+    --profile NAME     Profile of the bindings (default: the default_profile of the file)
+    --print-controls   Print the controls of the bindings profile and exit
+    --layout FILE      Layout of the UI widgets (default: assets/ui.toml)
+    -h, --help         Print this message";
+
+pub(crate) struct Args {
+    pub aircraft: String,
+    pub headless: bool,
+    pub script: Option<String>,
+    pub output: String,
+    pub duration: f64,
+    pub step_rate: f64,
+    pub max_substeps: u32,
+    pub weather: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    /// Airspeed and altitude of the trimmed level flight to start in
+    pub trim: Option<(f32, f32)>,
+    pub plan: Option<String>,
+    pub gamepad: Option<String>,
+    pub bindings: String,
+    pub profile: Option<String>,
+    pub print_controls: bool,
+    pub layout: String,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut ret = Self {
+            aircraft: DEFAULT_AIRCRAFT.to_string(),
+            headless: false,
+            script: None,
+            output: "trajectory.csv".to_string(),
+            duration: 60.,
+            step_rate: DEFAULT_STEP_RATE,
+            max_substeps: DEFAULT_MAX_SUBSTEPS,
+            weather: None,
+            record: None,
+            replay: None,
+            trim: None,
+            plan: None,
+            gamepad: None,
+            bindings: DEFAULT_BINDINGS.to_string(),
+            profile: None,
+            print_controls: false,
+            layout: DEFAULT_LAYOUT.to_string(),
+        };
+
+        fn value(name: &str, args: &mut impl Iterator<Item = String>) -> Result<String, String> {
+            args.next()
+                .ok_or_else(|| format!("Option {name} requires a value\n\n{USAGE}"))
+        }
+
+        fn number(name: &str, args: &mut impl Iterator<Item = String>) -> Result<f64, String> {
+            let s = value(name, args)?;
+            match s.parse::<f64>() {
+                Ok(v) if v.is_finite() && 0. < v => Ok(v),
+                _ => Err(format!("Option {name} requires a positive number, got {s}")),
+            }
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => ret.headless = true,
+                "--script" => ret.script = Some(value(&arg, &mut args)?),
+                "--output" => ret.output = value(&arg, &mut args)?,
+                "--duration" => ret.duration = number(&arg, &mut args)?,
+                "--rate" => ret.step_rate = number(&arg, &mut args)?,
+                "--substeps" => ret.max_substeps = number(&arg, &mut args)?.round() as u32,
+                "--weather" => ret.weather = Some(value(&arg, &mut args)?),
+                "--record" => ret.record = Some(value(&arg, &mut args)?),
+                "--replay" => ret.replay = Some(value(&arg, &mut args)?),
+                "--trim" => {
+                    let s = value(&arg, &mut args)?;
+                    let values = s
+                        .split(',')
+                        .map(|v| v.trim().parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>();
+                    match values.as_deref() {
+                        Ok(&[speed, altitude])
+                            if speed.is_finite() && 0. < speed && altitude.is_finite() => {
+                            ret.trim = Some((speed, altitude))
+                        }
+                        _ => {
+                            return Err(format!(
+                                "Option {arg} requires a positive airspeed and an altitude separated by a comma, got {s}"
+                            ))
+                        }
+                    }
+                }
+                "--plan" => ret.plan = Some(value(&arg, &mut args)?),
+                "--gamepad" => ret.gamepad = Some(value(&arg, &mut args)?),
+                "--bindings" => ret.bindings = value(&arg, &mut args)?,
+                "--profile" => ret.profile = Some(value(&arg, &mut args)?),
+                "--print-controls" => ret.print_controls = true,
+                "--layout" => ret.layout = value(&arg, &mut args)?,
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ if arg.starts_with('-') => {
+                    return Err(format!("Unknown option {arg}\n\n{USAGE}"));
+                }
+                _ => ret.aircraft = arg,
+            }
+        }
+
+        // The replay doesn't simulate, so there would be nothing to record.
+        if ret.record.is_some() && ret.replay.is_some() {
+            return Err("Options --record and --replay can't be used together".to_string());
+        }
+
+        Ok(ret)
+    }
+}
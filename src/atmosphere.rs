@@ -0,0 +1,118 @@
+//! International Standard Atmosphere (ISA) model.
+
+/// Specific gas constant of dry air [J/(kg K)]
+const GAS_CONSTANT: f64 = 287.05287;
+/// Standard gravity [m/s^2]
+const GRAVITY: f64 = 9.80665;
+/// Ratio of specific heats of air
+const HEAT_CAPACITY_RATIO: f64 = 1.4;
+
+pub(crate) const SEA_LEVEL_TEMPERATURE: f32 = 288.15;
+pub(crate) const SEA_LEVEL_PRESSURE: f32 = 101325.;
+pub(crate) const SEA_LEVEL_DENSITY: f32 = 1.225;
+
+/// Upper limit of the model [m]
+const MAX_ALTITUDE: f64 = 84852.;
+
+/// Base geopotential altitude [m] and temperature lapse rate [K/m] of each layer.
+const LAYERS: [(f64, f64); 7] = [
+    (0., -0.0065),
+    (11000., 0.),
+    (20000., 0.001),
+    (32000., 0.0028),
+    (47000., 0.),
+    (51000., -0.0028),
+    (71000., -0.002),
+];
+
+/// State of the air at an altitude.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Atmosphere {
+    /// Temperature [K]
+    pub temperature: f32,
+    /// Pressure [Pa]
+    pub pressure: f32,
+    /// Density [kg/m^3]
+    pub density: f32,
+    /// Speed of sound [m/s]
+    pub speed_of_sound: f32,
+}
+
+impl Atmosphere {
+    /// Returns the standard atmosphere at the given geopotential altitude in meters.
+    /// Altitudes above the upper limit of the model are clamped.
+    pub fn at_altitude(altitude: f32) -> Self {
+        let altitude = (altitude as f64).min(MAX_ALTITUDE);
+
+        let mut base_temperature = SEA_LEVEL_TEMPERATURE as f64;
+        let mut base_pressure = SEA_LEVEL_PRESSURE as f64;
+        for (i, &(base, lapse)) in LAYERS.iter().enumerate() {
+            let top = LAYERS.get(i + 1).map_or(MAX_ALTITUDE, |layer| layer.0);
+            // Only the first layer can have a negative height, below sea level.
+            let height = altitude.min(top) - base;
+            let temperature = base_temperature + lapse * height;
+            let pressure = if lapse == 0. {
+                base_pressure * (-GRAVITY * height / (GAS_CONSTANT * base_temperature)).exp()
+            } else {
+                base_pressure
+                    * (temperature / base_temperature).powf(-GRAVITY / (lapse * GAS_CONSTANT))
+            };
+            if altitude <= top {
+                return Self::new(temperature, pressure);
+            }
+            base_temperature = temperature;
+            base_pressure = pressure;
+        }
+        Self::new(base_temperature, base_pressure)
+    }
+
+    fn new(temperature: f64, pressure: f64) -> Self {
+        Self {
+            temperature: temperature as f32,
+            pressure: pressure as f32,
+            density: (pressure / (GAS_CONSTANT * temperature)) as f32,
+            speed_of_sound: (HEAT_CAPACITY_RATIO * GAS_CONSTANT * temperature).sqrt() as f32,
+        }
+    }
+
+    /// Ratio of the density to the sea level density
+    pub fn density_ratio(&self) -> f32 {
+        self.density / SEA_LEVEL_DENSITY
+    }
+}
+
+#[test]
+fn test_isa_table() {
+    // Geopotential altitude [m], temperature [K], pressure [Pa], density [kg/m^3] and
+    // speed of sound [m/s] from the published ISA tables.
+    let table = [
+        (-1000., 294.65, 113929., 1.3470, 344.11),
+        (0., 288.15, 101325., 1.2250, 340.29),
+        (1000., 281.65, 89874.6, 1.1116, 336.43),
+        (5000., 255.65, 54019.9, 0.73612, 320.53),
+        (11000., 216.65, 22632.1, 0.36392, 295.07),
+        (15000., 216.65, 12044.6, 0.19367, 295.07),
+        (20000., 216.65, 5474.89, 0.088035, 295.07),
+        (32000., 228.65, 868.019, 0.013225, 303.13),
+        (47000., 270.65, 110.906, 0.0014275, 329.80),
+        (71000., 214.65, 3.95642, 0.000064211, 293.70),
+    ];
+    let assert_close = |actual: f32, expected: f32, what: &str, altitude: f32| {
+        assert!(
+            ((actual - expected) / expected).abs() < 1e-3,
+            "{what} at {altitude} m: {actual} != {expected}"
+        );
+    };
+    for (altitude, temperature, pressure, density, speed_of_sound) in table {
+        let atmosphere = Atmosphere::at_altitude(altitude);
+        assert_close(atmosphere.temperature, temperature, "temperature", altitude);
+        assert_close(atmosphere.pressure, pressure, "pressure", altitude);
+        assert_close(atmosphere.density, density, "density", altitude);
+        assert_close(
+            atmosphere.speed_of_sound,
+            speed_of_sound,
+            "speed of sound",
+            altitude,
+        );
+    }
+}
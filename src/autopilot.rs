@@ -0,0 +1,349 @@
+//! Autopilot of cascaded PID loops, which drives the aileron, elevator, rudder and thrust
+//! in place of the pilot on the engaged axes.
+//!
+//! The lateral modes level the wings or hold a heading by banking, and the rudder damps the
+//! sideslip. The vertical modes hold an altitude or a vertical speed through the pitch
+//! attitude, and the autothrottle holds the airspeed. The navigation modes follow the
+//! course, altitude and airspeed of the flight plan.
+
+use rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    aircraft::AutopilotDef,
+    control_input::ControlInput,
+    flight_plan::{FlightPlan, Guidance},
+};
+
+/// Proportional, integral and derivative gains of a loop
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PidGains {
+    pub kp: f32,
+    #[serde(default)]
+    pub ki: f32,
+    #[serde(default)]
+    pub kd: f32,
+}
+
+/// A PID controller with its output limited to a range. The integral stops winding up
+/// while the output is saturated.
+#[derive(Clone, Copy, Debug)]
+struct Pid {
+    gains: PidGains,
+    min: f32,
+    max: f32,
+    integral: f32,
+    prev_error: Option<f32>,
+}
+
+impl Pid {
+    fn new(gains: PidGains, min: f32, max: f32) -> Self {
+        Self {
+            gains,
+            min,
+            max,
+            integral: 0.,
+            prev_error: None,
+        }
+    }
+
+    /// Resets the state so that the output continues from `output` without a bump.
+    fn reset(&mut self, output: f32) {
+        self.integral = if self.gains.ki != 0. {
+            output.clamp(self.min, self.max) / self.gains.ki
+        } else {
+            0.
+        };
+        self.prev_error = None;
+    }
+
+    fn update(&mut self, error: f32, delta_time: f32) -> f32 {
+        let derivative = self
+            .prev_error
+            .map_or(0., |prev| (error - prev) / delta_time);
+        self.prev_error = Some(error);
+        let integral = self.integral + error * delta_time;
+        let output = self.gains.kp * error + self.gains.ki * integral + self.gains.kd * derivative;
+        let clamped = output.clamp(self.min, self.max);
+        if clamped == output || (output - clamped).signum() != error.signum() {
+            self.integral = integral;
+        }
+        clamped
+    }
+}
+
+/// State of the aircraft that the autopilot flies by
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct FlightState {
+    /// Position of the center of gravity in world coordinates
+    pub position: Vector<f32>,
+    /// Altitude [m]
+    pub altitude: f32,
+    /// Vertical speed, positive upwards [m/s]
+    pub vertical_speed: f32,
+    /// True airspeed [m/s]
+    pub airspeed: f32,
+    /// Horizontal ground speed [m/s]
+    pub ground_speed: f32,
+    /// Heading clockwise from north (-z) in the range [0, 360) [deg]
+    pub heading: f32,
+    /// Pitch attitude, nose up [deg]
+    pub pitch: f32,
+    /// Bank angle, right wing down [deg]
+    pub roll: f32,
+    /// Rate of turn, clockwise seen from above [deg/s]
+    pub turn_rate: f32,
+    /// Sideslip angle, positive with the airflow from the right [deg]
+    pub sideslip: f32,
+    /// Angle of attack, positive with the airflow from below [deg]
+    pub angle_of_attack: f32,
+    /// Direction of the velocity relative to the nose, to the right and up [deg]
+    pub flight_path: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LateralMode {
+    WingsLeveler,
+    /// Heading to hold [deg]
+    Heading(f32),
+    /// Lateral navigation along the legs of the flight plan
+    Nav,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum VerticalMode {
+    /// Altitude to hold [m]
+    Altitude(f32),
+    /// Vertical speed to hold [m/s]
+    VerticalSpeed(f32),
+    /// Vertical navigation along the altitudes of the flight plan
+    Nav,
+}
+
+pub(crate) struct Autopilot {
+    def: AutopilotDef,
+    pub lateral: Option<LateralMode>,
+    pub vertical: Option<VerticalMode>,
+    /// Airspeed held by the autothrottle [m/s]
+    pub autothrottle: Option<f32>,
+    pub flight_plan: Option<FlightPlan>,
+    /// Commands of the navigation modes in the last update
+    pub guidance: Option<Guidance>,
+    roll: Pid,
+    pitch: Pid,
+    vertical_speed: Pid,
+    sideslip: Pid,
+    airspeed: Pid,
+}
+
+impl Autopilot {
+    /// Creates a disengaged autopilot.
+    pub fn new(def: &AutopilotDef) -> Self {
+        let max_pitch = def.max_pitch;
+        Self {
+            def: def.clone(),
+            lateral: None,
+            vertical: None,
+            autothrottle: None,
+            flight_plan: None,
+            guidance: None,
+            roll: Pid::new(def.roll, -1., 1.),
+            pitch: Pid::new(def.pitch, -1., 1.),
+            vertical_speed: Pid::new(def.vertical_speed, -max_pitch, max_pitch),
+            sideslip: Pid::new(def.sideslip, -1., 1.),
+            airspeed: Pid::new(def.airspeed, 0., 1.),
+        }
+    }
+
+    pub fn engaged(&self) -> bool {
+        self.lateral.is_some() || self.vertical.is_some() || self.autothrottle.is_some()
+    }
+
+    pub fn disengage(&mut self) {
+        self.lateral = None;
+        self.vertical = None;
+        self.autothrottle = None;
+    }
+
+    /// Engages the lateral mode, taking over from the current controls.
+    pub fn engage_lateral(&mut self, mode: LateralMode, controls: &ControlInput) {
+        if self.lateral.is_none() {
+            self.roll.reset(controls.aileron);
+            self.sideslip.reset(controls.rudder);
+        }
+        self.lateral = Some(mode);
+    }
+
+    /// Engages the vertical mode, taking over from the current controls and attitude.
+    pub fn engage_vertical(
+        &mut self,
+        mode: VerticalMode,
+        controls: &ControlInput,
+        state: &FlightState,
+    ) {
+        if self.vertical.is_none() {
+            self.pitch.reset(-controls.elevator);
+            self.vertical_speed.reset(state.pitch);
+        }
+        self.vertical = Some(mode);
+    }
+
+    /// Engages the autothrottle to hold the airspeed, taking over from the current thrust.
+    pub fn engage_autothrottle(&mut self, airspeed: f32, controls: &ControlInput) {
+        if self.autothrottle.is_none() {
+            self.airspeed.reset(controls.thrust);
+        }
+        self.autothrottle = Some(airspeed);
+    }
+
+    /// Engages the lateral and vertical navigation and the autothrottle to fly the flight
+    /// plan. The autothrottle holds the current airspeed until a waypoint sets the speed.
+    pub fn engage_nav(&mut self, controls: &ControlInput, state: &FlightState) {
+        self.engage_lateral(LateralMode::Nav, controls);
+        self.engage_vertical(VerticalMode::Nav, controls, state);
+        if self.autothrottle.is_none() {
+            self.engage_autothrottle(state.airspeed, controls);
+        }
+    }
+
+    /// Replaces the commanded controls of the engaged axes with the autopilot's.
+    pub fn command(
+        &mut self,
+        delta_time: f32,
+        state: &FlightState,
+        mut command: ControlInput,
+    ) -> ControlInput {
+        if delta_time == 0. {
+            return command;
+        }
+        self.guidance = None;
+        if self.lateral == Some(LateralMode::Nav) || self.vertical == Some(VerticalMode::Nav) {
+            let max_bank = self.def.max_bank;
+            self.guidance = self
+                .flight_plan
+                .as_mut()
+                .and_then(|plan| plan.guide(state, max_bank));
+            match self.guidance {
+                Some(guidance) => {
+                    if let (Some(_), Some(VerticalMode::Nav), Some(speed)) =
+                        (self.autothrottle, self.vertical, guidance.speed)
+                    {
+                        self.autothrottle = Some(speed);
+                    }
+                }
+                None => {
+                    // At the end of the plan, hold the heading and altitude.
+                    if self.lateral == Some(LateralMode::Nav) {
+                        self.lateral = Some(LateralMode::Heading(state.heading));
+                    }
+                    if self.vertical == Some(VerticalMode::Nav) {
+                        self.vertical = Some(VerticalMode::Altitude(state.altitude));
+                    }
+                }
+            }
+        }
+        if let Some(mode) = self.lateral {
+            let target_heading = match mode {
+                LateralMode::WingsLeveler => None,
+                LateralMode::Heading(heading) => Some(heading),
+                LateralMode::Nav => self.guidance.map(|guidance| guidance.course),
+            };
+            let target_roll = target_heading.map_or(0., |heading| {
+                let error = (heading - state.heading + 540.).rem_euclid(360.) - 180.;
+                (self.def.heading.kp * error).clamp(-self.def.max_bank, self.def.max_bank)
+            });
+            // Positive aileron rolls to the left.
+            command.aileron = -self.roll.update(target_roll - state.roll, delta_time);
+            // Positive rudder yaws to the right, into the airflow from the right.
+            command.rudder = self.sideslip.update(state.sideslip, delta_time);
+        }
+        if let Some(mode) = self.vertical {
+            let target_altitude = match mode {
+                VerticalMode::Altitude(altitude) => Some(altitude),
+                VerticalMode::VerticalSpeed(_) => None,
+                VerticalMode::Nav => self.guidance.map(|guidance| guidance.altitude),
+            };
+            let target_vertical_speed = match (mode, target_altitude) {
+                (VerticalMode::VerticalSpeed(vertical_speed), _) => vertical_speed,
+                (_, Some(altitude)) => (self.def.altitude.kp * (altitude - state.altitude))
+                    .clamp(-self.def.max_vertical_speed, self.def.max_vertical_speed),
+                (_, None) => 0.,
+            };
+            let target_pitch = self
+                .vertical_speed
+                .update(target_vertical_speed - state.vertical_speed, delta_time);
+            // Positive elevator pitches the nose down.
+            command.elevator = -self.pitch.update(target_pitch - state.pitch, delta_time);
+        }
+        if let Some(airspeed) = self.autothrottle {
+            command.thrust = self.airspeed.update(airspeed - state.airspeed, delta_time);
+        }
+        command
+    }
+}
+
+#[test]
+fn test_autopilot() {
+    use crate::{
+        aircraft::{AircraftDef, DEFAULT_AIRCRAFT},
+        physics::PhysicsSet,
+        trim::start_trimmed,
+        vehicle::Vehicle,
+        wind::WindField,
+    };
+    use rapier3d::prelude::*;
+
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let mut physics = PhysicsSet::new(1000.);
+    let handles = physics.new_body(vector![0., 1000., 0.], &aircraft);
+    let mut vehicle = Vehicle::new(handles, &aircraft);
+    start_trimmed(&mut vehicle, &mut physics, 150., 1000.).unwrap();
+    let wind = WindField::default();
+
+    // Engage all the modes from the trimmed state, and ask for a turn to the east.
+    let state = vehicle.flight_state(&physics.rigid_body_set, &wind);
+    let controls = vehicle.controls;
+    vehicle
+        .autopilot
+        .engage_lateral(LateralMode::Heading(90.), &controls);
+    vehicle
+        .autopilot
+        .engage_vertical(VerticalMode::Altitude(1000.), &controls, &state);
+    vehicle.autopilot.engage_autothrottle(150., &controls);
+    let delta_time = physics.integration_parameters.dt as f64;
+    for _ in 0..4800 {
+        vehicle.update(delta_time, &mut physics, &controls, &wind);
+        physics.step();
+    }
+    let state = vehicle.flight_state(&physics.rigid_body_set, &wind);
+    assert!((state.heading - 90.).abs() < 2., "{state:?}");
+    assert!((state.altitude - 1000.).abs() < 20., "{state:?}");
+    assert!((state.airspeed - 150.).abs() < 5., "{state:?}");
+    assert!(state.roll.abs() < 2., "{state:?}");
+
+    // Climb to a new altitude, limited by the maximum vertical speed.
+    vehicle
+        .autopilot
+        .engage_vertical(VerticalMode::Altitude(1200.), &controls, &state);
+    for _ in 0..600 {
+        vehicle.update(delta_time, &mut physics, &controls, &wind);
+        physics.step();
+    }
+    let state = vehicle.flight_state(&physics.rigid_body_set, &wind);
+    assert!(
+        (state.vertical_speed - aircraft.autopilot.max_vertical_speed).abs() < 2.,
+        "{state:?}"
+    );
+    for _ in 0..3000 {
+        vehicle.update(delta_time, &mut physics, &controls, &wind);
+        physics.step();
+    }
+    let state = vehicle.flight_state(&physics.rigid_body_set, &wind);
+    assert!((state.altitude - 1200.).abs() < 20., "{state:?}");
+    assert!((state.airspeed - 150.).abs() < 5., "{state:?}");
+
+    // Disengaging gives the controls back to the pilot.
+    vehicle.autopilot.disengage();
+    assert!(!vehicle.autopilot.engaged());
+}
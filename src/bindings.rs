@@ -0,0 +1,673 @@
+//! Bindings of keys and mouse buttons to the actions of the simulator, in named profiles
+//! loaded from a TOML file.
+//!
+//! An axis action moves its control while the positive or negative input is held. A button
+//! action is active while its input is held, or toggles on each press. A command action fires
+//! once on each press.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    path::Path,
+};
+
+use serde::Deserialize;
+use three_d::{Event, Key, MouseButton};
+
+pub(crate) const DEFAULT_BINDINGS: &str = "assets/bindings.toml";
+
+/// Every key, to parse the key names
+const KEYS: &[Key] = &[
+    Key::ArrowDown,
+    Key::ArrowLeft,
+    Key::ArrowRight,
+    Key::ArrowUp,
+    Key::Escape,
+    Key::Tab,
+    Key::Backspace,
+    Key::Enter,
+    Key::Space,
+    Key::Insert,
+    Key::Delete,
+    Key::Home,
+    Key::End,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+/// A key or a mouse button
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl std::str::FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mouse = match s {
+            "MouseLeft" => Some(MouseButton::Left),
+            "MouseRight" => Some(MouseButton::Right),
+            "MouseMiddle" => Some(MouseButton::Middle),
+            _ => None,
+        };
+        if let Some(button) = mouse {
+            return Ok(Self::Mouse(button));
+        }
+        KEYS.iter()
+            .find(|key| format!("{key:?}") == s)
+            .map(|key| Self::Key(*key))
+            .ok_or_else(|| {
+                format!(
+                    "unknown input \"{s}\", expected a key such as A, Num1, Space or ArrowUp, or MouseLeft, MouseRight or MouseMiddle"
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{key:?}"),
+            Self::Mouse(button) => write!(f, "Mouse{button:?}"),
+        }
+    }
+}
+
+impl Input {
+    /// Name of the input in the control list
+    fn label(&self) -> String {
+        match self {
+            Self::Key(key) => {
+                let name = format!("{key:?}");
+                name.strip_prefix("Num")
+                    .or_else(|| name.strip_prefix("Arrow"))
+                    .unwrap_or(&name)
+                    .to_string()
+            }
+            Self::Mouse(button) => format!("{button:?} mouse button"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ActionKind {
+    Axis,
+    Button,
+    Command,
+}
+
+/// An action of the simulator that an input is bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Thrust,
+    Aileron,
+    Elevator,
+    Rudder,
+    AileronTrim,
+    ElevatorTrim,
+    RudderTrim,
+    TrimReset,
+    BrakeLeft,
+    BrakeRight,
+    BrakeBoth,
+    Gear,
+    Engine,
+    Autopilot,
+    WingsLeveler,
+    HeadingHold,
+    AltitudeHold,
+    VerticalSpeedHold,
+    Autothrottle,
+    LateralNav,
+    VerticalNav,
+    OrbitCamera,
+    CockpitCamera,
+    ChaseCamera,
+    TowerCamera,
+    FlyByCamera,
+    FreeCamera,
+    Panel,
+    Debug,
+    ShorterArrows,
+    LongerArrows,
+    Reset,
+    Pause,
+    ReplayBackward,
+    ReplayForward,
+    ReplayFaster,
+    ReplaySlower,
+    ReplayReverse,
+    ReplayRestart,
+}
+
+impl Action {
+    fn kind(&self) -> ActionKind {
+        match self {
+            Self::Thrust
+            | Self::Aileron
+            | Self::Elevator
+            | Self::Rudder
+            | Self::AileronTrim
+            | Self::ElevatorTrim
+            | Self::RudderTrim => ActionKind::Axis,
+            Self::TrimReset | Self::BrakeLeft | Self::BrakeRight | Self::BrakeBoth => {
+                ActionKind::Button
+            }
+            _ => ActionKind::Command,
+        }
+    }
+
+    /// Description in the control list, positive before negative for an axis
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Thrust => "increase/decrease thrust",
+            Self::Aileron => "left/right aileron",
+            Self::Elevator => "up/down elevator",
+            Self::Rudder => "left/right rudder",
+            Self::AileronTrim => "left/right aileron trim",
+            Self::ElevatorTrim => "up/down elevator trim",
+            Self::RudderTrim => "left/right rudder trim",
+            Self::TrimReset => "Center the trim tabs",
+            Self::BrakeLeft => "left wheel brake",
+            Self::BrakeRight => "right wheel brake",
+            Self::BrakeBoth => "both wheel brakes",
+            Self::Gear => "Gear up/down",
+            Self::Engine => "Start/stop the engine",
+            Self::Autopilot => "Engage the autopilot holding the current heading, altitude and airspeed, or disengage it",
+            Self::WingsLeveler => "Toggle the wings leveler",
+            Self::HeadingHold => "Toggle the heading hold",
+            Self::AltitudeHold => "Toggle the altitude hold",
+            Self::VerticalSpeedHold => "Toggle the vertical speed hold",
+            Self::Autothrottle => "Toggle the autothrottle",
+            Self::LateralNav => "Toggle the lateral navigation along the flight plan",
+            Self::VerticalNav => "Toggle the vertical navigation along the flight plan",
+            Self::OrbitCamera => "Camera: orbit around the airplane",
+            Self::CockpitCamera => "Camera: cockpit view",
+            Self::ChaseCamera => "Camera: chase behind the flight path",
+            Self::TowerCamera => "Camera: tower tracking the airplane",
+            Self::FlyByCamera => "Camera: fly-by ahead on the flight path",
+            Self::FreeCamera => "Camera: free, detached from the airplane",
+            Self::Panel => "Toggle the instrument panel",
+            Self::Debug => "Toggle the aerodynamics debug overlay",
+            Self::ShorterArrows => "Halve the length of the force arrows",
+            Self::LongerArrows => "Double the length of the force arrows",
+            Self::Reset => "Reset the airplane state to initial state",
+            Self::Pause => "Toggle pause",
+            Self::ReplayBackward => "Replay: scrub backward by a second",
+            Self::ReplayForward => "Replay: scrub forward by a second",
+            Self::ReplayFaster => "Replay: double the playback speed",
+            Self::ReplaySlower => "Replay: halve the playback speed",
+            Self::ReplayReverse => "Replay: reverse the playback direction",
+            Self::ReplayRestart => "Replay: restart the playback",
+        }
+    }
+}
+
+/// Whether a button is active while held or toggles on each press
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Mode {
+    #[default]
+    Hold,
+    Toggle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Binding {
+    Axis { positive: Input, negative: Input },
+    Button { input: Input, mode: Mode },
+    Command(Input),
+}
+
+impl Binding {
+    fn inputs(&self) -> Vec<Input> {
+        match self {
+            Self::Axis { positive, negative } => vec![*positive, *negative],
+            Self::Button { input, .. } | Self::Command(input) => vec![*input],
+        }
+    }
+}
+
+/// The bindings of a profile, at most one for each action
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Profile {
+    pub name: String,
+    bindings: Vec<(Action, Binding)>,
+}
+
+impl Profile {
+    fn binding(&self, action: Action) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, binding)| binding)
+    }
+
+    /// Returns the names of the inputs bound to the action, as in the control list.
+    pub fn label(&self, action: Action) -> Option<String> {
+        Some(match self.binding(action)? {
+            Binding::Axis { positive, negative } => {
+                format!("{}, {}", positive.label(), negative.label())
+            }
+            Binding::Button { input, .. } | Binding::Command(input) => input.label(),
+        })
+    }
+
+    /// Returns the control list of the profile in Markdown, as in the README.
+    pub fn controls_markdown(&self) -> String {
+        self.bindings
+            .iter()
+            .map(|(action, binding)| {
+                let suffix = match binding {
+                    Binding::Button {
+                        mode: Mode::Toggle, ..
+                    } => " (toggle)",
+                    _ => "",
+                };
+                format!(
+                    "* {} - {}{suffix}\n",
+                    self.label(*action).unwrap_or_default(),
+                    action.description()
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingsFile {
+    default_profile: String,
+    profiles: HashMap<String, ProfileFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    /// Profile whose bindings this one starts from
+    base: Option<String>,
+    #[serde(default)]
+    bindings: HashMap<Action, BindingFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingFile {
+    input: Option<String>,
+    mode: Option<Mode>,
+    positive: Option<String>,
+    negative: Option<String>,
+}
+
+impl Binding {
+    fn from_file(action: Action, binding: &BindingFile) -> Result<Self, String> {
+        let parse = |s: &String| s.parse::<Input>();
+        let name = format!("{action:?}");
+        match (action.kind(), binding) {
+            (
+                ActionKind::Axis,
+                BindingFile {
+                    input: None,
+                    mode: None,
+                    positive: Some(positive),
+                    negative: Some(negative),
+                },
+            ) => Ok(Self::Axis {
+                positive: parse(positive)?,
+                negative: parse(negative)?,
+            }),
+            (ActionKind::Axis, _) => Err(format!(
+                "{name} is an axis, which needs positive and negative inputs"
+            )),
+            (
+                ActionKind::Button,
+                BindingFile {
+                    input: Some(input),
+                    mode,
+                    positive: None,
+                    negative: None,
+                },
+            ) => Ok(Self::Button {
+                input: parse(input)?,
+                mode: mode.unwrap_or_default(),
+            }),
+            (ActionKind::Button, _) => Err(format!(
+                "{name} is a button, which needs an input and optionally a mode"
+            )),
+            (
+                ActionKind::Command,
+                BindingFile {
+                    input: Some(input),
+                    mode: None,
+                    positive: None,
+                    negative: None,
+                },
+            ) => Ok(Self::Command(parse(input)?)),
+            (ActionKind::Command, _) => {
+                Err(format!("{name} is a command, which needs an input only"))
+            }
+        }
+    }
+}
+
+/// The profiles of a bindings file
+pub(crate) struct Bindings {
+    default_profile: String,
+    profiles: HashMap<String, Profile>,
+}
+
+impl Bindings {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read bindings {}: {e}", path.display()))?;
+        Self::parse(&src).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let file: BindingsFile = toml::from_str(src)?;
+        // Resolve in order of the names, for the same error each time.
+        let mut names: Vec<_> = file.profiles.keys().collect();
+        names.sort();
+        let profiles = names
+            .into_iter()
+            .map(|name| Ok((name.clone(), Self::resolve(&file, name)?)))
+            .collect::<Result<HashMap<_, _>, String>>()?;
+        if !profiles.contains_key(&file.default_profile) {
+            return Err(format!("unknown default_profile \"{}\"", file.default_profile).into());
+        }
+        Ok(Self {
+            default_profile: file.default_profile,
+            profiles,
+        })
+    }
+
+    /// Merges the bindings of the profile over its bases, and checks that no input is bound
+    /// to two actions.
+    fn resolve(file: &BindingsFile, name: &str) -> Result<Profile, String> {
+        let mut chain = vec![];
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if chain.contains(&name) {
+                return Err(format!("profile \"{name}\" is its own base"));
+            }
+            let profile = file
+                .profiles
+                .get(name)
+                .ok_or_else(|| format!("unknown base profile \"{name}\""))?;
+            chain.push(name);
+            next = profile.base.as_deref();
+        }
+
+        let mut merged = HashMap::new();
+        for base in chain.iter().rev() {
+            for (action, binding) in &file.profiles[*base].bindings {
+                let binding = Binding::from_file(*action, binding)
+                    .map_err(|e| format!("profile \"{base}\": {e}"))?;
+                merged.insert(*action, binding);
+            }
+        }
+        let mut bindings: Vec<_> = merged.into_iter().collect();
+        bindings.sort_by_key(|(action, _)| *action);
+
+        let mut bound = HashMap::new();
+        for (action, binding) in &bindings {
+            for input in binding.inputs() {
+                if let Some(other) = bound.insert(input, *action) {
+                    return Err(format!(
+                        "profile \"{name}\": {input} is bound to both {other:?} and {action:?}"
+                    ));
+                }
+            }
+        }
+        Ok(Profile {
+            name: name.to_string(),
+            bindings,
+        })
+    }
+
+    /// Returns the named profile, or the default profile.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, String> {
+        let name = name.unwrap_or(&self.default_profile);
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            let mut names: Vec<_> = self.profiles.keys().map(|s| s.as_str()).collect();
+            names.sort();
+            format!(
+                "unknown profile \"{name}\", expected one of {}",
+                names.join(", ")
+            )
+        })
+    }
+}
+
+/// The state of the bound inputs
+pub(crate) struct InputState {
+    profile: Profile,
+    held: HashSet<Input>,
+    toggled: HashMap<Action, bool>,
+    /// Commands fired since the last take
+    commands: Vec<Action>,
+}
+
+impl InputState {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            held: HashSet::new(),
+            toggled: HashMap::new(),
+            commands: vec![],
+        }
+    }
+
+    pub fn handle_events(&mut self, events: &[Event]) {
+        for e in events {
+            match e {
+                Event::KeyPress { kind, .. } => self.press(Input::Key(*kind)),
+                Event::KeyRelease { kind, .. } => self.release(Input::Key(*kind)),
+                Event::MousePress { button, .. } => self.press(Input::Mouse(*button)),
+                Event::MouseRelease { button, .. } => self.release(Input::Mouse(*button)),
+                _ => {}
+            }
+        }
+    }
+
+    fn press(&mut self, input: Input) {
+        // Ignore the repeats of a held key.
+        if !self.held.insert(input) {
+            return;
+        }
+        for (action, binding) in &self.profile.bindings {
+            match binding {
+                Binding::Button {
+                    input: bound,
+                    mode: Mode::Toggle,
+                } if *bound == input => *self.toggled.entry(*action).or_default() ^= true,
+                Binding::Command(bound) if *bound == input => self.commands.push(*action),
+                _ => {}
+            }
+        }
+    }
+
+    fn release(&mut self, input: Input) {
+        self.held.remove(&input);
+    }
+
+    /// Returns 1 while the positive input of the axis is held, -1 for the negative input and
+    /// 0 for both or neither.
+    pub fn axis(&self, action: Action) -> f32 {
+        match self.profile.binding(action) {
+            Some(Binding::Axis { positive, negative }) => {
+                let held = |input| if self.held.contains(input) { 1. } else { 0. };
+                held(positive) - held(negative)
+            }
+            _ => 0.,
+        }
+    }
+
+    /// Whether the button is held, or toggled on
+    pub fn active(&self, action: Action) -> bool {
+        match self.profile.binding(action) {
+            Some(Binding::Button {
+                input,
+                mode: Mode::Hold,
+            }) => self.held.contains(input),
+            Some(Binding::Button {
+                mode: Mode::Toggle, ..
+            }) => self.toggled.get(&action).copied().unwrap_or_default(),
+            _ => false,
+        }
+    }
+
+    /// Returns the commands fired since the last call.
+    pub fn take_commands(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.commands)
+    }
+}
+
+#[test]
+fn test_bindings() {
+    let bindings = Bindings::load(DEFAULT_BINDINGS).unwrap();
+    let profile = bindings.profile(None).unwrap();
+
+    // The README lists the controls of the default profile.
+    let readme = std::fs::read_to_string("README.md").unwrap();
+    assert!(
+        readme.contains(&profile.controls_markdown()),
+        "README.md is out of date, update it with the output of --print-controls:\n{}",
+        profile.controls_markdown()
+    );
+
+    let bindings = Bindings::parse(
+        r#"
+default_profile = "base"
+
+[profiles.base.bindings]
+thrust = { positive = "Q", negative = "Z" }
+brake_left = { input = "J" }
+pause = { input = "P" }
+
+[profiles.parking]
+base = "base"
+bindings.brake_left = { input = "MouseRight", mode = "toggle" }
+"#,
+    )
+    .unwrap();
+    let mut state = InputState::new(bindings.profile(Some("parking")).unwrap());
+    let key = |kind, pressed| {
+        let modifiers = three_d::Modifiers::default();
+        if pressed {
+            Event::KeyPress {
+                kind,
+                modifiers,
+                handled: false,
+            }
+        } else {
+            Event::KeyRelease {
+                kind,
+                modifiers,
+                handled: false,
+            }
+        }
+    };
+    state.handle_events(&[key(Key::Z, true), key(Key::P, true), key(Key::P, true)]);
+    assert_eq!(state.axis(Action::Thrust), -1.);
+    // The repeat of the held key doesn't fire again.
+    assert_eq!(state.take_commands(), [Action::Pause]);
+    assert!(state.take_commands().is_empty());
+    state.handle_events(&[key(Key::Z, false), key(Key::J, true)]);
+    assert_eq!(state.axis(Action::Thrust), 0.);
+    // J is no longer bound in the derived profile.
+    assert!(!state.active(Action::BrakeLeft));
+    let mouse = Input::Mouse(MouseButton::Right);
+    state.press(mouse);
+    state.release(mouse);
+    assert!(state.active(Action::BrakeLeft));
+    state.press(mouse);
+    assert!(!state.active(Action::BrakeLeft));
+
+    let parse_err = |src: &str| Bindings::parse(src).err().unwrap().to_string();
+    assert_eq!(
+        parse_err(
+            r#"
+default_profile = "a"
+[profiles.a.bindings]
+thrust = { positive = "Q", negative = "Z" }
+reset = { input = "Q" }
+"#
+        ),
+        "profile \"a\": Q is bound to both Thrust and Reset"
+    );
+    assert_eq!(
+        parse_err(
+            r#"
+default_profile = "a"
+[profiles.a.bindings]
+thrust = { input = "Q" }
+"#
+        ),
+        "profile \"a\": Thrust is an axis, which needs positive and negative inputs"
+    );
+    assert!(parse_err(
+        r#"
+default_profile = "a"
+[profiles.a.bindings]
+reset = { input = "F13" }
+"#
+    )
+    .starts_with("profile \"a\": unknown input \"F13\""));
+    assert_eq!(
+        parse_err("default_profile = \"b\"\n[profiles.a]"),
+        "unknown default_profile \"b\""
+    );
+    assert_eq!(
+        parse_err(
+            r#"
+default_profile = "a"
+[profiles.a]
+base = "b"
+[profiles.b]
+base = "a"
+"#
+        ),
+        "profile \"a\" is its own base"
+    );
+}
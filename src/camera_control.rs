@@ -0,0 +1,397 @@
+//! Camera modes of the viewer. The orbit camera circles the airplane with the mouse, the
+//! cockpit camera looks out from the pilot's eye point, the chase camera lags behind the
+//! flight path, the tower camera tracks the airplane from a fixed point on the ground, the
+//! fly-by camera waits ahead on the flight path for the airplane to pass, and the free camera
+//! is detached from the airplane.
+
+use three_d::*;
+
+use crate::orbit_control_ex::OrbitControlEx;
+
+/// Vertical field of view of the cameras but the tower's and the zoomed cockpit's
+const FIELD_OF_VIEW: f32 = 45.;
+const Z_NEAR: f32 = 0.1;
+const Z_FAR: f32 = 10000.;
+/// Position of the orbit camera relative to the airplane when it is selected [m]
+const ORBIT_OFFSET: Vec3 = vec3(-30., 10., 25.);
+/// Rotation of the view per pixel of mouse motion [rad]
+const LOOK_SPEED: f32 = 0.005;
+/// Relative zoom per unit of mouse wheel motion
+const ZOOM_SPEED: f32 = 0.002;
+/// Range of the field of view of the cockpit camera [deg]
+const COCKPIT_FIELD_OF_VIEW: [f32; 2] = [15., 90.];
+/// Time constant of the chase camera following the flight path [s]
+const CHASE_LAG: f32 = 0.5;
+/// Range of the distance of the chase camera behind the airplane [m]
+const CHASE_DISTANCE: [f32; 2] = [10., 500.];
+/// Height of the chase camera above the flight path, relative to its distance
+const CHASE_HEIGHT: f32 = 0.2;
+/// Below this airspeed, the chase and fly-by cameras follow the nose instead of the flight
+/// path [m/s]
+const MIN_SPEED: f32 = 1.;
+const TOWER_POSITION: Vec3 = vec3(-300., 30., 0.);
+/// Range of the width of the view of the tower camera at the airplane [m]
+const TOWER_VIEW: [f32; 2] = [10., 2000.];
+/// Time ahead on the flight path the fly-by camera is placed at [s]
+const FLY_BY_LEAD: f32 = 4.;
+/// Offset of the fly-by camera to the right of and above the flight path [m]
+const FLY_BY_OFFSET: Vec2 = vec2(20., 5.);
+/// Minimum distance the fly-by camera is placed ahead [m]
+const FLY_BY_DISTANCE: f32 = 100.;
+/// Distance of the free camera moved per unit of mouse wheel motion [m]
+const FREE_SPEED: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CameraMode {
+    Orbit,
+    Cockpit,
+    Chase,
+    Tower,
+    FlyBy,
+    Free,
+}
+
+/// Pose and velocity of the airplane the cameras look at, in world coordinates
+pub(crate) struct CameraTarget {
+    /// Body to world transformation of the rendered airplane
+    pub transform: Mat4,
+    pub velocity: Vec3,
+}
+
+impl CameraTarget {
+    fn position(&self) -> Vec3 {
+        self.transform.w.truncate()
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        (self.transform * v.extend(0.)).truncate()
+    }
+
+    /// The direction of the flight path, or the nose when it is too slow to have one
+    fn direction(&self) -> Vec3 {
+        if MIN_SPEED < self.velocity.magnitude() {
+            self.velocity.normalize()
+        } else {
+            self.to_world(vec3(0., 0., -1.)).normalize()
+        }
+    }
+}
+
+/// Returns the unit vector turned right by `yaw` and up by `pitch` from -z, in radians.
+fn look_direction(yaw: f32, pitch: f32) -> Vec3 {
+    vec3(
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
+}
+
+/// Places the camera of each mode, and turns and zooms it with the mouse.
+pub(crate) struct CameraControl {
+    mode: CameraMode,
+    /// Whether the mode was selected since the last update, to place its camera
+    selected: bool,
+    orbit: OrbitControlEx,
+    /// Eye point of the pilot in the body frame
+    pilot_eye: Vec3,
+    /// View turned by the mouse from the direction of the cockpit, chase and free cameras,
+    /// right and up [rad]
+    yaw: f32,
+    pitch: f32,
+    cockpit_field_of_view: f32,
+    chase_distance: f32,
+    /// Position of the chase camera relative to the airplane
+    chase_offset: Vec3,
+    tower_view: f32,
+    /// Position of the fly-by and free cameras
+    position: Vec3,
+}
+
+impl CameraControl {
+    pub fn new(target: Vec3, pilot_eye: Vec3) -> Self {
+        Self {
+            mode: CameraMode::Orbit,
+            selected: false,
+            orbit: OrbitControlEx::builder()
+                .target(target)
+                .min_distance(0.10)
+                .max_distance(1000.0)
+                .pan_speed(0.01)
+                .zoom_speed(0.01)
+                .build(),
+            pilot_eye,
+            yaw: 0.,
+            pitch: 0.,
+            cockpit_field_of_view: FIELD_OF_VIEW,
+            chase_distance: 40.,
+            chase_offset: Vec3::zero(),
+            tower_view: 60.,
+            position: Vec3::zero(),
+        }
+    }
+
+    /// Selects the mode, whose camera is placed from the current view at the next update.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+        self.selected = true;
+        self.yaw = 0.;
+        self.pitch = 0.;
+    }
+
+    /// Handles the mouse events of the current mode and moves the camera to the airplane.
+    /// Must be called each frame.
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        target: &CameraTarget,
+        delta_time: f32,
+        events: &mut [Event],
+    ) {
+        if self.mode == CameraMode::Orbit {
+            self.update_orbit(camera, target);
+            self.orbit.handle_events(camera, events);
+            return;
+        }
+        for event in events.iter_mut() {
+            match event {
+                Event::MouseMotion {
+                    delta,
+                    button: Some(MouseButton::Left),
+                    handled,
+                    ..
+                } if !*handled => *handled = self.look(delta.0, delta.1),
+                Event::MouseWheel { delta, handled, .. } if !*handled => {
+                    *handled = self.zoom(delta.1)
+                }
+                _ => {}
+            }
+        }
+        self.place(camera, target, delta_time);
+    }
+
+    fn update_orbit(&mut self, camera: &mut Camera, target: &CameraTarget) {
+        let new_target = target.position();
+        let delta = if self.selected {
+            ORBIT_OFFSET
+        } else {
+            camera.position() - self.orbit.target()
+        };
+        self.selected = false;
+        self.orbit.set_target(new_target);
+        camera.set_perspective_projection(degrees(FIELD_OF_VIEW), Z_NEAR, Z_FAR);
+        camera.set_view(new_target + delta, new_target, vec3(0., 1., 0.));
+    }
+
+    /// Turns the view by the mouse motion in pixels, returning whether the mode turns.
+    fn look(&mut self, dx: f32, dy: f32) -> bool {
+        if !matches!(
+            self.mode,
+            CameraMode::Cockpit | CameraMode::Chase | CameraMode::Free
+        ) {
+            return false;
+        }
+        let limit = 89f32.to_radians();
+        self.yaw = (self.yaw + dx * LOOK_SPEED).rem_euclid(std::f32::consts::TAU);
+        self.pitch = (self.pitch - dy * LOOK_SPEED).clamp(-limit, limit);
+        true
+    }
+
+    /// Zooms in by the mouse wheel motion, or moves the free camera forward, returning
+    /// whether the mode zooms.
+    fn zoom(&mut self, delta: f32) -> bool {
+        let scale = (-delta * ZOOM_SPEED).exp();
+        match self.mode {
+            CameraMode::Cockpit => {
+                self.cockpit_field_of_view = (self.cockpit_field_of_view * scale)
+                    .clamp(COCKPIT_FIELD_OF_VIEW[0], COCKPIT_FIELD_OF_VIEW[1]);
+            }
+            CameraMode::Chase => {
+                self.chase_distance =
+                    (self.chase_distance * scale).clamp(CHASE_DISTANCE[0], CHASE_DISTANCE[1]);
+            }
+            CameraMode::Tower => {
+                self.tower_view = (self.tower_view * scale).clamp(TOWER_VIEW[0], TOWER_VIEW[1]);
+            }
+            CameraMode::Free => {
+                self.position += look_direction(self.yaw, self.pitch) * delta * FREE_SPEED;
+            }
+            CameraMode::Orbit | CameraMode::FlyBy => return false,
+        }
+        true
+    }
+
+    /// Places the camera of the modes other than the orbit camera.
+    fn place(&mut self, camera: &mut Camera, target: &CameraTarget, delta_time: f32) {
+        let selected = std::mem::take(&mut self.selected);
+        let up = vec3(0., 1., 0.);
+        let mut field_of_view = FIELD_OF_VIEW;
+        match self.mode {
+            CameraMode::Orbit => unreachable!(),
+            CameraMode::Cockpit => {
+                let eye = (target.transform * self.pilot_eye.extend(1.)).truncate();
+                let direction = target.to_world(look_direction(self.yaw, self.pitch));
+                camera.set_view(eye, eye + direction, target.to_world(up));
+                field_of_view = self.cockpit_field_of_view;
+            }
+            CameraMode::Chase => {
+                // The flight path turned by the mouse around the vertical and then upwards
+                let direction = target.direction();
+                let heading = direction.x.atan2(-direction.z) + self.yaw;
+                let pitch = direction.y.clamp(-1., 1.).asin() + self.pitch;
+                let offset = -look_direction(heading, pitch) * self.chase_distance
+                    + up * self.chase_distance * CHASE_HEIGHT;
+                // Lag the offset rather than the position, which would fall behind by the
+                // distance the airplane flies in the time constant.
+                if selected {
+                    self.chase_offset = offset;
+                } else {
+                    let rate = 1. - (-delta_time / CHASE_LAG).exp();
+                    self.chase_offset += (offset - self.chase_offset) * rate;
+                }
+                let position = target.position();
+                camera.set_view(position + self.chase_offset, position, up);
+            }
+            CameraMode::Tower => {
+                let distance = TOWER_POSITION.distance(target.position());
+                field_of_view = (2. * (self.tower_view / 2.).atan2(distance))
+                    .to_degrees()
+                    .min(FIELD_OF_VIEW);
+                camera.set_view(TOWER_POSITION, target.position(), up);
+            }
+            CameraMode::FlyBy => {
+                let to_camera = self.position - target.position();
+                // Wait for the airplane ahead again once it has passed and is as far as the
+                // camera was placed.
+                let passed = to_camera.dot(target.direction()) < 0.
+                    && fly_by_distance(target) < to_camera.magnitude();
+                if selected || passed {
+                    self.position = fly_by_position(target);
+                }
+                camera.set_view(self.position, target.position(), up);
+            }
+            CameraMode::Free => {
+                if selected {
+                    // Start from the current view.
+                    let direction = camera.view_direction();
+                    self.position = *camera.position();
+                    self.yaw = direction.x.atan2(-direction.z);
+                    self.pitch = direction.y.clamp(-1., 1.).asin();
+                }
+                let direction = look_direction(self.yaw, self.pitch);
+                camera.set_view(self.position, self.position + direction, up);
+            }
+        }
+        camera.set_perspective_projection(degrees(field_of_view), Z_NEAR, Z_FAR);
+    }
+}
+
+fn fly_by_distance(target: &CameraTarget) -> f32 {
+    (target.velocity.magnitude() * FLY_BY_LEAD).max(FLY_BY_DISTANCE)
+}
+
+/// Returns the position of the fly-by camera ahead on the flight path, beside and above it.
+fn fly_by_position(target: &CameraTarget) -> Vec3 {
+    let direction = target.direction();
+    let up = vec3(0., 1., 0.);
+    let right = direction.cross(up);
+    let right = if right.magnitude() < 1e-3 {
+        vec3(1., 0., 0.)
+    } else {
+        right.normalize()
+    };
+    target.position()
+        + direction * fly_by_distance(target)
+        + right * FLY_BY_OFFSET.x
+        + up * FLY_BY_OFFSET.y
+}
+
+#[test]
+fn test_camera_control() {
+    let close = |a: Vec3, b: Vec3| (a - b).magnitude() < 1e-3;
+    let mut camera = Camera::new_perspective(
+        Viewport::new_at_origo(800, 600),
+        vec3(0., 0., 10.),
+        vec3(0., 0., 0.),
+        vec3(0., 1., 0.),
+        degrees(FIELD_OF_VIEW),
+        Z_NEAR,
+        Z_FAR,
+    );
+    let pilot_eye = vec3(0., 1., -4.);
+    let mut control = CameraControl::new(Vec3::zero(), pilot_eye);
+    // Flying east, with the nose to +x
+    let mut target = CameraTarget {
+        transform: Mat4::from_translation(vec3(0., 200., 0.)) * Mat4::from_angle_y(degrees(-90.)),
+        velocity: vec3(100., 0., 0.),
+    };
+
+    control.set_mode(CameraMode::Cockpit);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert!(close(*camera.position(), vec3(4., 201., 0.)));
+    assert!(close(camera.view_direction(), vec3(1., 0., 0.)));
+    // Looking to the right turns towards the right wing, to the south.
+    control.look(90f32.to_radians() / LOOK_SPEED, 0.);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert!(close(camera.view_direction(), vec3(0., 0., 1.)));
+
+    // The chase camera is behind and above the airplane, and swings behind the new flight
+    // path with a lag.
+    control.set_mode(CameraMode::Chase);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    let offset = *camera.position() - target.position();
+    assert!(close(offset, vec3(-40., 8., 0.)));
+    target.velocity = vec3(0., 0., -100.);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    let offset = *camera.position() - target.position();
+    assert!(offset.x < -1. && 1. < offset.z);
+    for _ in 0..100 {
+        control.update(&mut camera, &target, 0.1, &mut []);
+    }
+    let offset = *camera.position() - target.position();
+    assert!(close(offset, vec3(0., 8., 40.)));
+
+    // The tower camera stays on the ground and zooms in on the airplane.
+    control.set_mode(CameraMode::Tower);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert_eq!(*camera.position(), TOWER_POSITION);
+    assert!(close(
+        camera.view_direction(),
+        (target.position() - TOWER_POSITION).normalize()
+    ));
+    let three_d_asset::ProjectionType::Perspective { field_of_view_y } = *camera.projection_type()
+    else {
+        panic!("perspective projection expected");
+    };
+    assert!(Deg::from(field_of_view_y).0 < 20.);
+
+    // The fly-by camera waits ahead, until the airplane passes it.
+    control.set_mode(CameraMode::FlyBy);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    let position = *camera.position();
+    assert!(close(position, vec3(20., 205., -400.)));
+    target.transform = Mat4::from_translation(vec3(0., 200., -600.));
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert_eq!(*camera.position(), position);
+    target.transform = Mat4::from_translation(vec3(0., 200., -900.));
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert!(close(*camera.position(), vec3(20., 205., -1300.)));
+
+    // The free camera starts from the current view and stays there.
+    let view = camera.view_direction();
+    control.set_mode(CameraMode::Free);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert!(close(*camera.position(), vec3(20., 205., -1300.)));
+    assert!(close(camera.view_direction(), view));
+    target.transform = Mat4::from_translation(vec3(0., 200., -1500.));
+    control.zoom(10. / FREE_SPEED);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert!(close(
+        *camera.position(),
+        vec3(20., 205., -1300.) + view * 10.
+    ));
+
+    // The orbit camera returns to its default offset.
+    control.set_mode(CameraMode::Orbit);
+    control.update(&mut camera, &target, 0.1, &mut []);
+    assert!(close(*camera.position(), target.position() + ORBIT_OFFSET));
+}
@@ -0,0 +1,281 @@
+//! Tracking of the airframe's contacts with the ground, classified by the part that touches.
+//!
+//! The contact points and forces are read from the contact manifolds of the narrow phase
+//! after each step rather than from rapier's contact force events. The events carry no
+//! contact points and are only sent in the steps where the force of a pair exceeds the
+//! collider's threshold, so a force kept from them goes stale when the pair stops pushing,
+//! while the manifold impulses give the force of every point at every step.
+
+use std::collections::HashSet;
+
+use rapier3d::prelude::*;
+
+use crate::physics::PhysicsSet;
+
+/// Contacts farther from the center line than this fraction of the airframe's half width
+/// are on a wingtip, if the airframe is banked against the ground.
+const WINGTIP_FRACTION: f32 = 0.5;
+/// Cosine of the bank angle relative to the ground above which an outboard contact is on
+/// a wingtip rather than flat on the belly (10 degrees)
+const WINGTIP_BANK_COS: f32 = 0.985;
+
+/// Part of the airframe that touches the ground, in the order of severity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ContactKind {
+    /// A landing gear wheel
+    Gear,
+    /// The fuselage, including the nose and the tail
+    Belly,
+    Wingtip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Contact {
+    pub kind: ContactKind,
+    /// Contact point in world coordinates
+    pub point: Point<f32>,
+    /// Unit normal of the ground pushing the airframe, in world coordinates
+    pub normal: Vector<f32>,
+    /// Normal force [N]
+    pub force: f32,
+}
+
+/// Set of colliders touching the airframe, maintained by the collision events, with the
+/// contact points and forces of the last physics step.
+pub(crate) struct ContactTracker {
+    collider: ColliderHandle,
+    /// Colliders in contact with the airframe
+    pairs: HashSet<ColliderHandle>,
+    contacts: Vec<Contact>,
+    /// Total contact force on the airframe in the last step, from the impulses of the
+    /// contact manifolds [N]
+    airframe_force: f32,
+}
+
+impl ContactTracker {
+    /// Creates a tracker of the contacts of the airframe's `collider`.
+    pub fn new(collider: ColliderHandle) -> Self {
+        Self {
+            collider,
+            pairs: HashSet::new(),
+            contacts: vec![],
+            airframe_force: 0.,
+        }
+    }
+
+    /// Returns the other collider of a pair involving the airframe.
+    fn other(&self, h1: ColliderHandle, h2: ColliderHandle) -> Option<ColliderHandle> {
+        if h1 == self.collider {
+            Some(h2)
+        } else if h2 == self.collider {
+            Some(h1)
+        } else {
+            None
+        }
+    }
+
+    pub fn collide(&mut self, collision: CollisionEvent) {
+        let Some(other) = self.other(collision.collider1(), collision.collider2()) else {
+            return;
+        };
+        if collision.started() {
+            self.pairs.insert(other);
+        } else {
+            self.pairs.remove(&other);
+        }
+    }
+
+    /// Collects the contact points of the airframe from the last physics step, along
+    /// with the landing gear's wheel contacts.
+    pub fn update(&mut self, physics: &PhysicsSet, gear: impl Iterator<Item = Contact>) {
+        self.contacts.clear();
+        self.contacts.extend(gear);
+        // Computed afresh each step, as the force falls to zero without any event when the
+        // airframe lifts off.
+        self.airframe_force = 0.;
+
+        let Some(collider) = physics.collider_set.get(self.collider) else {
+            return;
+        };
+        let half_width = collider.shape().compute_local_aabb().half_extents().x;
+        let dt = physics.integration_parameters.dt;
+        for &other in &self.pairs {
+            let Some(pair) = physics.narrow_phase.contact_pair(self.collider, other) else {
+                continue;
+            };
+            let first = pair.collider1 == self.collider;
+            for manifold in &pair.manifolds {
+                // The manifold normal points from the first collider to the second.
+                let normal = if first {
+                    -manifold.data.normal
+                } else {
+                    manifold.data.normal
+                };
+                let local_normal = collider.rotation().inverse() * normal;
+                self.airframe_force += manifold
+                    .points
+                    .iter()
+                    .map(|point| point.data.impulse)
+                    .sum::<f32>()
+                    / dt;
+                for point in manifold.points.iter().filter(|point| point.dist <= 0.) {
+                    let local = if first {
+                        point.local_p1
+                    } else {
+                        point.local_p2
+                    };
+                    let kind = if WINGTIP_FRACTION * half_width < local.x.abs()
+                        && local_normal.y < WINGTIP_BANK_COS
+                    {
+                        ContactKind::Wingtip
+                    } else {
+                        ContactKind::Belly
+                    };
+                    self.contacts.push(Contact {
+                        kind,
+                        point: collider.position() * local,
+                        normal,
+                        force: point.data.impulse / dt,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Contacts of the last physics step
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// The most severe kind of the contacts of the last physics step
+    pub fn worst(&self) -> Option<ContactKind> {
+        self.contacts.iter().map(|contact| contact.kind).max()
+    }
+
+    /// Forgets the contact points and forces, which are not recorded for the replay.
+    pub fn clear(&mut self) {
+        self.contacts.clear();
+        self.airframe_force = 0.;
+    }
+
+    /// Whether any collider touches the airframe
+    pub fn touching(&self) -> bool {
+        !self.pairs.is_empty()
+    }
+
+    /// Sum of the contact forces on the airframe, excluding the landing gear, in the last
+    /// physics step [N]
+    pub fn airframe_force(&self) -> f32 {
+        self.airframe_force
+    }
+}
+
+#[test]
+fn test_contact_tracker() {
+    use crate::{
+        aircraft::{AircraftDef, DEFAULT_AIRCRAFT},
+        control_input::ControlInput,
+        vehicle::Vehicle,
+        wind::WindField,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    let mut aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    aircraft.gears.clear();
+    let half_height = aircraft.collider_size.y * 0.5;
+
+    // Drops the airframe from the height with the roll angle, and returns the kinds of
+    // the contacts seen, the contacts at the end, the airframe force at the end and at
+    // its peak, and the mass.
+    let settle = |height: f32, roll: f32| {
+        let mut physics = PhysicsSet::new(100.);
+        let handles = physics.new_body(vector![0., height, 0.], &aircraft);
+        physics.rigid_body_set[handles.0].set_rotation(
+            Rotation::from_axis_angle(&Vector::z_axis(), roll.to_radians()),
+            true,
+        );
+        let vehicle = Rc::new(RefCell::new(Vehicle::new(handles, &aircraft)));
+        let vehicle2 = vehicle.clone();
+        physics.register_collision(move |e| vehicle2.borrow_mut().collide(e));
+        vehicle.borrow_mut().engine.stop();
+
+        let delta_time = physics.integration_parameters.dt as f64;
+        let mut kinds = vec![];
+        let mut peak_force = 0f32;
+        for _ in 0..240 {
+            vehicle.borrow_mut().update(
+                delta_time,
+                &mut physics,
+                &ControlInput::default(),
+                &WindField::default(),
+            );
+            physics.step();
+            let vehicle = vehicle.borrow();
+            kinds.extend(vehicle.contacts.contacts().iter().map(|c| c.kind));
+            peak_force = peak_force.max(vehicle.contacts.airframe_force());
+        }
+        let vehicle = vehicle.borrow();
+        let mass = physics.rigid_body_set[vehicle.body_handle].mass();
+        (
+            kinds,
+            vehicle.contacts.contacts().to_vec(),
+            vehicle.contacts.airframe_force(),
+            peak_force,
+            mass,
+        )
+    };
+
+    // Resting on the belly, the contact forces carry the weight.
+    let (_, contacts, airframe_force, _, mass) = settle(half_height + 0.1, 0.);
+    assert!(!contacts.is_empty());
+    assert!(contacts.iter().all(|c| c.kind == ContactKind::Belly));
+    assert!(contacts.iter().all(|c| 0.99 < c.normal.y));
+    let weight = mass * 9.81;
+    let force: f32 = contacts.iter().map(|c| c.force).sum();
+    assert!((force - weight).abs() < weight * 0.1, "{force} {weight}");
+    assert!((airframe_force - weight).abs() < weight * 0.1);
+
+    // After a hard belly impact, just short of a crash, the force returns to the weight
+    // as the airframe settles.
+    let (_, _, airframe_force, peak_force, _) = settle(half_height + 0.4, 0.);
+    assert!(3. * weight < peak_force, "{peak_force} {weight}");
+    assert!(
+        (airframe_force - weight).abs() < weight * 0.1,
+        "{airframe_force}"
+    );
+
+    // Dropped with a bank, a wingtip touches.
+    let (kinds, _, _, _, _) = settle(6.5, 30.);
+    assert!(kinds.contains(&ContactKind::Wingtip));
+
+    // A single Stopped event doesn't clear the other contacts.
+    let handle = |i| ColliderHandle::from_raw_parts(i, 0);
+    let mut tracker = ContactTracker::new(handle(0));
+    tracker.collide(CollisionEvent::Started(
+        handle(0),
+        handle(1),
+        CollisionEventFlags::empty(),
+    ));
+    tracker.collide(CollisionEvent::Started(
+        handle(2),
+        handle(0),
+        CollisionEventFlags::empty(),
+    ));
+    tracker.collide(CollisionEvent::Started(
+        handle(2),
+        handle(3),
+        CollisionEventFlags::empty(),
+    ));
+    tracker.collide(CollisionEvent::Stopped(
+        handle(1),
+        handle(0),
+        CollisionEventFlags::empty(),
+    ));
+    assert!(tracker.touching());
+    tracker.collide(CollisionEvent::Stopped(
+        handle(0),
+        handle(2),
+        CollisionEventFlags::empty(),
+    ));
+    assert!(!tracker.touching());
+}
@@ -0,0 +1,285 @@
+//! Commanded control values and the sources that drive them.
+
+use serde::Deserialize;
+use three_d::Event;
+
+use crate::bindings::{Action, InputState, Profile};
+
+/// Commanded or actual values of the flight controls.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ControlInput {
+    /// Engine thrust in the range [0, 1]
+    pub thrust: f32,
+    /// Aileron in the range [-1, 1]
+    pub aileron: f32,
+    /// Elevator in the range [-1, 1]
+    pub elevator: f32,
+    /// Rudder in the range [-1, 1]
+    pub rudder: f32,
+    /// Left wheel brake in the range [0, 1]
+    pub brake_left: f32,
+    /// Right wheel brake in the range [0, 1]
+    pub brake_right: f32,
+}
+
+impl ControlInput {
+    pub fn clamped(self) -> Self {
+        Self {
+            thrust: self.thrust.clamp(0., 1.),
+            aileron: self.aileron.clamp(-1., 1.),
+            elevator: self.elevator.clamp(-1., 1.),
+            rudder: self.rudder.clamp(-1., 1.),
+            brake_left: self.brake_left.clamp(0., 1.),
+            brake_right: self.brake_right.clamp(0., 1.),
+        }
+    }
+
+    /// Moves towards `target` by the dynamics of the actuators.
+    pub fn approach(&mut self, target: &ControlInput, actuators: &Actuators, delta_time: f64) {
+        let target = target.clamped();
+        let dt = delta_time as f32;
+        actuators.thrust.drive(&mut self.thrust, target.thrust, dt);
+        actuators
+            .aileron
+            .drive(&mut self.aileron, target.aileron, dt);
+        actuators
+            .elevator
+            .drive(&mut self.elevator, target.elevator, dt);
+        actuators.rudder.drive(&mut self.rudder, target.rudder, dt);
+        actuators
+            .brake
+            .drive(&mut self.brake_left, target.brake_left, dt);
+        actuators
+            .brake
+            .drive(&mut self.brake_right, target.brake_right, dt);
+    }
+}
+
+/// Trim tab settings, which offset the control surfaces from the commanded controls.
+/// Each is in the range [-1, 1], the fraction of the full deflection.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Trim {
+    pub aileron: f32,
+    pub elevator: f32,
+    pub rudder: f32,
+}
+
+/// Rate of change of the trim while its input is held, in units per second
+const TRIM_RATE: f32 = 0.1;
+
+/// Rate at which a spring centered control returns to the center after its input is
+/// released, in units per second
+const CENTERING_RATE: f32 = 2.;
+
+/// How a control moved by the keyboard behaves when its input is released
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Centering {
+    /// Stays where it was left
+    #[default]
+    Hold,
+    /// Returns to the center, like a stick with a spring
+    Spring,
+}
+
+/// Dynamics of the actuator that moves a control towards the commanded value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Actuator {
+    /// Maximum rate of change, in units per second
+    pub rate: f32,
+    /// Time constant of the first-order lag behind the command in seconds, zero for none
+    pub lag: f32,
+    pub centering: Centering,
+}
+
+impl Actuator {
+    /// Moves the control `v` towards `target` by the lag, without exceeding the rate.
+    pub fn drive(&self, v: &mut f32, target: f32, delta_time: f32) {
+        let target = if 0. < self.lag {
+            *v + (target - *v) * (1. - (-delta_time / self.lag).exp())
+        } else {
+            target
+        };
+        let max_delta = self.rate * delta_time;
+        *v += (target - *v).clamp(-max_delta, max_delta);
+    }
+}
+
+/// The actuator of each control channel. Both wheel brakes share one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Actuators {
+    pub thrust: Actuator,
+    pub aileron: Actuator,
+    pub elevator: Actuator,
+    pub rudder: Actuator,
+    pub brake: Actuator,
+}
+
+impl Default for Actuators {
+    fn default() -> Self {
+        let actuator = |rate| Actuator {
+            rate,
+            lag: 0.,
+            centering: Centering::Hold,
+        };
+        Self {
+            thrust: actuator(1.),
+            aileron: actuator(1.),
+            elevator: actuator(1.),
+            rudder: actuator(1.),
+            brake: actuator(4.),
+        }
+    }
+}
+
+/// A source of commanded control values, such as the keyboard, a gamepad, an autopilot,
+/// a replay or the network.
+pub(crate) trait ControlSource {
+    /// Returns the commanded controls, given the elapsed time in seconds since the last call
+    /// and the last commanded controls.
+    fn command(&mut self, delta_time: f64, current: &ControlInput) -> ControlInput;
+}
+
+/// Keyboard and mouse controls through the bindings of a profile, which move a control while
+/// its input is held, and return a spring centered control to the center after it is released.
+/// The brakes are applied while their inputs are held or toggled on.
+/// The trim tabs move slowly while their inputs are held.
+pub(crate) struct KeyboardControl {
+    input: InputState,
+    actuators: Actuators,
+}
+
+impl KeyboardControl {
+    pub fn new(profile: Profile, actuators: &Actuators) -> Self {
+        Self {
+            input: InputState::new(profile),
+            actuators: *actuators,
+        }
+    }
+
+    pub fn handle_events(&mut self, events: &[Event]) {
+        self.input.handle_events(events);
+    }
+
+    /// Returns the commands fired since the last call.
+    pub fn take_commands(&mut self) -> Vec<Action> {
+        self.input.take_commands()
+    }
+
+    /// Moves the trim tabs while their inputs are held.
+    pub fn trim(&self, delta_time: f64, trim: &mut Trim) {
+        if self.input.active(Action::TrimReset) {
+            *trim = Trim::default();
+            return;
+        }
+        let delta = TRIM_RATE * delta_time as f32;
+        let adjust = |v: &mut f32, action| {
+            *v = (*v + self.input.axis(action) * delta).clamp(-1., 1.);
+        };
+        adjust(&mut trim.aileron, Action::AileronTrim);
+        adjust(&mut trim.elevator, Action::ElevatorTrim);
+        adjust(&mut trim.rudder, Action::RudderTrim);
+    }
+}
+
+impl ControlSource for KeyboardControl {
+    fn command(&mut self, delta_time: f64, current: &ControlInput) -> ControlInput {
+        let mut ret = *current;
+
+        let delta = delta_time as f32;
+        let move_axis = |v: &mut f32, action, actuator: &Actuator| {
+            let axis = self.input.axis(action);
+            if axis != 0. || actuator.centering == Centering::Hold {
+                *v += axis * delta;
+            } else {
+                let max_delta = CENTERING_RATE * delta;
+                *v -= v.clamp(-max_delta, max_delta);
+            }
+        };
+        move_axis(&mut ret.thrust, Action::Thrust, &self.actuators.thrust);
+        move_axis(&mut ret.aileron, Action::Aileron, &self.actuators.aileron);
+        move_axis(
+            &mut ret.elevator,
+            Action::Elevator,
+            &self.actuators.elevator,
+        );
+        move_axis(&mut ret.rudder, Action::Rudder, &self.actuators.rudder);
+
+        let brake_both = self.input.active(Action::BrakeBoth);
+        let brake = |action| {
+            if brake_both || self.input.active(action) {
+                1.
+            } else {
+                0.
+            }
+        };
+        ret.brake_left = brake(Action::BrakeLeft);
+        ret.brake_right = brake(Action::BrakeRight);
+
+        ret.clamped()
+    }
+}
+
+#[test]
+fn test_approach() {
+    let mut controls = ControlInput::default();
+    let target = ControlInput {
+        thrust: 2.,
+        aileron: -0.5,
+        elevator: 0.1,
+        rudder: 0.,
+        brake_left: 1.,
+        brake_right: 0.,
+    };
+    controls.approach(&target, &Actuators::default(), 0.25);
+    assert_eq!(
+        controls,
+        ControlInput {
+            thrust: 0.25,
+            aileron: -0.25,
+            elevator: 0.1,
+            rudder: 0.,
+            brake_left: 1.,
+            brake_right: 0.,
+        }
+    );
+    controls.approach(&target, &Actuators::default(), 1.);
+    assert_eq!(controls.thrust, 1.);
+    assert_eq!(controls.aileron, -0.5);
+
+    // The lag slows the approach to the command within the rate.
+    let actuator = Actuator {
+        rate: 10.,
+        lag: 0.5,
+        centering: Centering::Hold,
+    };
+    let mut v = 0.;
+    actuator.drive(&mut v, 1., 0.5);
+    assert!((v - (1. - (-1f32).exp())).abs() < 1e-6, "{v}");
+    let mut v = 0.;
+    Actuator {
+        rate: 1.,
+        ..actuator
+    }
+    .drive(&mut v, 1., 0.1);
+    assert!((v - 0.1).abs() < 1e-6, "{v}");
+
+    // Released keys return the spring centered controls to the center.
+    let profile = crate::bindings::Bindings::load(crate::bindings::DEFAULT_BINDINGS)
+        .unwrap()
+        .profile(None)
+        .unwrap();
+    let mut actuators = Actuators::default();
+    actuators.aileron.centering = Centering::Spring;
+    let mut keyboard = KeyboardControl::new(profile, &actuators);
+    let current = ControlInput {
+        thrust: 0.5,
+        aileron: 0.5,
+        elevator: 0.5,
+        ..ControlInput::default()
+    };
+    let command = keyboard.command(0.1, &current);
+    assert_eq!(command.thrust, 0.5);
+    assert!((command.aileron - 0.3).abs() < 1e-6, "{command:?}");
+    assert_eq!(command.elevator, 0.5);
+}
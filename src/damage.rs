@@ -0,0 +1,175 @@
+//! Classification of ground impacts and the structural damage of the wings.
+//!
+//! The landing gear absorbs the impact with its springs, so a touchdown on the wheels is
+//! judged by the sink rate and the load on the gear. The airframe itself is rigid and its
+//! contact forces scale with the inverse of the time step, so an airframe contact is judged
+//! by the sink rate and the velocity change of the contact impulse in the step, which
+//! doesn't depend on the time step, while the force beyond the structural limit damages the
+//! nearest wing.
+
+use rapier3d::prelude::*;
+
+use crate::contact::{Contact, ContactKind};
+
+/// Sink rate above which a touchdown is a hard landing [m/s]
+const HARD_SINK_RATE: f32 = 3.;
+/// Sink rate above which a touchdown is a crash [m/s]
+const CRASH_SINK_RATE: f32 = 6.;
+/// Gear load relative to the weight above which a touchdown is a hard landing
+const HARD_LOAD: f32 = 3.;
+/// Gear load relative to the weight above which a touchdown is a crash
+const CRASH_LOAD: f32 = 8.;
+/// Velocity change of the airframe by its contact impulse in a step above which an
+/// airframe contact is a crash, as if hitting the ground at the crash sink rate [m/s]
+const CRASH_VELOCITY_CHANGE: f32 = CRASH_SINK_RATE;
+/// Airframe contact force relative to the weight that the structure bears without damage
+const DAMAGE_LOAD: f32 = 2.;
+/// Impulse of the contact force beyond the limit that destroys a wing [N s]
+const WING_STRENGTH: f32 = 5000.;
+
+/// Severity of a ground impact, in increasing order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Impact {
+    Smooth,
+    Hard,
+    Crash,
+}
+
+impl Impact {
+    /// Classifies the contacts of a physics step of `delta_time` [s], given the sink rate
+    /// before the step [m/s], the mass of the aircraft [kg] and the gravity [m/s^2].
+    /// Returns `None` without contacts.
+    pub fn classify(
+        contacts: &[Contact],
+        sink_rate: f32,
+        mass: f32,
+        gravity: f32,
+        delta_time: f32,
+    ) -> Option<Self> {
+        if contacts.is_empty() {
+            return None;
+        }
+        let (airframe, gear): (Vec<&Contact>, Vec<_>) = contacts
+            .iter()
+            .partition(|contact| contact.kind != ContactKind::Gear);
+        if !airframe.is_empty() {
+            let impulse: f32 =
+                airframe.iter().map(|contact| contact.force).sum::<f32>() * delta_time;
+            return Some(
+                if sink_rate < HARD_SINK_RATE && impulse / mass < CRASH_VELOCITY_CHANGE {
+                    Self::Hard
+                } else {
+                    Self::Crash
+                },
+            );
+        }
+        let load = gear.iter().map(|contact| contact.force).sum::<f32>() / (mass * gravity);
+        Some(if sink_rate < HARD_SINK_RATE && load < HARD_LOAD {
+            Self::Smooth
+        } else if sink_rate < CRASH_SINK_RATE && load < CRASH_LOAD {
+            Self::Hard
+        } else {
+            Self::Crash
+        })
+    }
+}
+
+/// Distributes the airframe contact force beyond the structural limit among the contact
+/// points, and returns the damage of each wing, the fraction of its strength lost in this
+/// step.
+///
+/// `airframe_force` is the total contact force on the airframe [N], `wings` the positions of
+/// the wings and `position` the pose of the airframe.
+pub(crate) fn wing_damage(
+    contacts: &[Contact],
+    airframe_force: f32,
+    weight: f32,
+    delta_time: f32,
+    position: &Isometry<f32>,
+    wings: &[Vector<f32>],
+) -> Vec<f32> {
+    let mut damage = vec![0.; wings.len()];
+    let excess = airframe_force - DAMAGE_LOAD * weight;
+    let airframe: Vec<_> = contacts
+        .iter()
+        .filter(|contact| contact.kind != ContactKind::Gear)
+        .collect();
+    if excess <= 0. || airframe.is_empty() {
+        return damage;
+    }
+    // The points share the total force in proportion to their own normal forces.
+    let total: f32 = airframe.iter().map(|contact| contact.force).sum();
+    for contact in &airframe {
+        let share = if 0. < total {
+            contact.force / total
+        } else {
+            1. / airframe.len() as f32
+        };
+        let local = position.inverse_transform_point(&contact.point).coords;
+        let nearest = wings
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - local).norm().total_cmp(&(*b - local).norm()));
+        if let Some((i, _)) = nearest {
+            damage[i] += excess * share * delta_time / WING_STRENGTH;
+        }
+    }
+    damage
+}
+
+#[test]
+fn test_damage() {
+    let gear = |force| Contact {
+        kind: ContactKind::Gear,
+        point: point![0., 0., 0.],
+        normal: Vector::y(),
+        force,
+    };
+    let (mass, gravity, delta_time) = (1000., 10., 0.01);
+    let weight = mass * gravity;
+    let classify = |contacts: &[Contact], sink_rate| {
+        Impact::classify(contacts, sink_rate, mass, gravity, delta_time)
+    };
+    assert_eq!(classify(&[], 10.), None);
+    assert_eq!(
+        classify(&[gear(5000.), gear(10000.)], 1.),
+        Some(Impact::Smooth)
+    );
+    assert_eq!(classify(&[gear(50000.)], 1.), Some(Impact::Hard));
+    assert_eq!(classify(&[gear(5000.)], 4.), Some(Impact::Hard));
+    assert_eq!(classify(&[gear(5000.)], 7.), Some(Impact::Crash));
+
+    // Scraping a wingtip is hard, but hitting the ground with it is a crash, as is an
+    // impulse that stops the airframe faster than the crash sink rate.
+    let wingtip = Contact {
+        kind: ContactKind::Wingtip,
+        point: point![-5., 1., 0.],
+        normal: Vector::y(),
+        force: 100000.,
+    };
+    assert_eq!(classify(&[gear(5000.), wingtip], 1.), Some(Impact::Hard));
+    assert_eq!(classify(&[wingtip], 4.), Some(Impact::Crash));
+    let slam = Contact {
+        force: 1000000.,
+        ..wingtip
+    };
+    assert_eq!(classify(&[slam], 1.), Some(Impact::Crash));
+
+    // The wingtip strike damages the left wing, in the body frame of the translated airframe.
+    let position = Isometry::translation(0., 1., 0.);
+    let wings = [vector![-4., 0., 0.], vector![4., 0., 0.]];
+    let damage = wing_damage(
+        &[gear(5000.), wingtip],
+        100000.,
+        weight,
+        0.1,
+        &position,
+        &wings,
+    );
+    assert!((damage[0] - 80000. * 0.1 / WING_STRENGTH).abs() < 1e-6);
+    assert_eq!(damage[1], 0.);
+
+    // The structure bears a light contact.
+    let damage = wing_damage(&[wingtip], 15000., weight, 0.1, &position, &wings);
+    assert_eq!(damage, [0., 0.]);
+}
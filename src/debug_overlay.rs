@@ -0,0 +1,317 @@
+//! Debug overlay of the aerodynamic model: a table of the airflow, angle of attack,
+//! deflection, force and moment of each wing with their totals, and arrows of the thrust,
+//! the weight and the drag at the center of gravity with a marker at the center of pressure.
+//!
+//! The table is laid out in overlay units, where the viewport is `OVERLAY_HEIGHT` units high
+//! with the origin at its center.
+
+use three_d::{
+    Camera, ColorMaterial, Context, CpuMaterial, Gm, Mesh, Object, RenderTarget, Viewport,
+};
+use three_d_asset::{
+    vec2, vec3, InnerSpace, Mat4, Quaternion, Srgba, TriMesh, Vec3, Vector3 as CgVector3,
+};
+
+use crate::{
+    strokes::{Align, Strokes},
+    vehicle::AeroDebug,
+};
+
+/// Height of the viewport in overlay units
+const OVERLAY_HEIGHT: f32 = 100.;
+const LINE_WIDTH: f32 = 0.2;
+const TEXT_SIZE: f32 = 1.5;
+const LINE_HEIGHT: f32 = 2.4;
+/// Baseline of the first row from the top of the viewport, below the autopilot annunciators
+const TOP: f32 = 22.;
+const MARGIN: f32 = 2.;
+
+const TEXT_COLOR: Srgba = Srgba::WHITE;
+const BACK_COLOR: Srgba = Srgba::new(0, 0, 0, 160);
+const THRUST_COLOR: Srgba = Srgba::new(255, 160, 0, 255);
+const GRAVITY_COLOR: Srgba = Srgba::new(255, 255, 0, 255);
+const DRAG_COLOR: Srgba = Srgba::new(255, 0, 0, 255);
+const CENTER_OF_PRESSURE_COLOR: Srgba = Srgba::new(255, 0, 255, 255);
+/// Radius of the center of pressure marker [m]
+const MARKER_RADIUS: f32 = 0.3;
+
+/// Returns the transformation of an arrow mesh of unit length along x, such as
+/// `TriMesh::arrow`, to point from `origin` along `vector`.
+fn arrow_transformation(origin: Vec3, vector: Vec3) -> Mat4 {
+    let length = vector.magnitude();
+    let rotation = if 0. < length {
+        Quaternion::from_arc(Vec3::unit_x(), vector / length, None)
+    } else {
+        Quaternion::from_sv(1., CgVector3::new(0., 0., 0.))
+    };
+    Mat4::from_translation(origin)
+        * Mat4::from(rotation)
+        * Mat4::from_nonuniform_scale(length, 0.1, 0.1)
+}
+
+/// Rows of the table with their colors. Forces are in kN and moments in kN m, in the body
+/// frame with x to the right, y up and z aft.
+fn rows(debug: &AeroDebug, arrow_scale: f32) -> Vec<(String, Srgba)> {
+    let kilo = |v: f32| v * 1e-3;
+    let vector = |v: &[f32]| {
+        v.iter()
+            .map(|v| format!("{:>7.1}", kilo(*v)))
+            .collect::<String>()
+    };
+    let mut rows = vec![
+        (
+            format!(
+                "{:<14}{:>5}{:>6}{:>6}{:>7}{:>7}{:>7}{:>7}{:>7}{:>7}",
+                "WING", "AIR", "AOA", "DEFL", "FX", "FY", "FZ", "MX", "MY", "MZ"
+            ),
+            TEXT_COLOR,
+        ),
+        (
+            format!(
+                "{:<14}{:>5}{:>6}{:>6}{:>21}{:>21}",
+                "", "M/S", "DEG", "DEG", "KN", "KN M"
+            ),
+            TEXT_COLOR,
+        ),
+    ];
+    for wing in &debug.wings {
+        rows.push((
+            format!(
+                "{:<14}{:>5.0}{:>6.1}{:>6.1}{}{}",
+                wing.name,
+                wing.airflow.norm(),
+                wing.angle_of_attack,
+                wing.deflection,
+                vector(wing.force.as_slice()),
+                vector(wing.torque.as_slice()),
+            ),
+            TEXT_COLOR,
+        ));
+    }
+    rows.push((
+        format!(
+            "{:<31}{}{}",
+            "TOTAL",
+            vector(debug.force.as_slice()),
+            vector(debug.torque.as_slice())
+        ),
+        TEXT_COLOR,
+    ));
+    rows.push((
+        match debug.center_of_pressure {
+            Some(cp) => format!(
+                "CENTER OF PRESSURE {:+.2} {:+.2} {:+.2} M FROM THE CG",
+                cp.x, cp.y, cp.z
+            ),
+            None => "CENTER OF PRESSURE -".to_string(),
+        },
+        CENTER_OF_PRESSURE_COLOR,
+    ));
+    for (name, force, color) in [
+        ("THRUST", &debug.thrust, THRUST_COLOR),
+        ("WEIGHT", &debug.gravity, GRAVITY_COLOR),
+        ("DRAG", &debug.drag, DRAG_COLOR),
+    ] {
+        rows.push((format!("{name:<7}{:>7.1} KN", kilo(force.norm())), color));
+    }
+    rows.push((
+        format!("ARROWS {:.1} M PER KN", arrow_scale * 1e3),
+        TEXT_COLOR,
+    ));
+    rows
+}
+
+/// Draws the table at the top left of a viewport `half_width` overlay units wide on either
+/// side.
+fn draw(debug: &AeroDebug, arrow_scale: f32, half_width: f32) -> Strokes {
+    let rows = rows(debug, arrow_scale);
+    let mut strokes = Strokes::new(BACK_COLOR);
+    let left = -half_width + MARGIN;
+    let top = OVERLAY_HEIGHT / 2. - TOP;
+    let width = rows
+        .iter()
+        .map(|(row, _)| crate::strokes::text_width(row, TEXT_SIZE))
+        .fold(0., f32::max);
+    let height = rows.len() as f32 * LINE_HEIGHT;
+    strokes.fill_rect(
+        vec2(
+            left + width / 2.,
+            top + TEXT_SIZE + (LINE_HEIGHT - TEXT_SIZE) / 2. - height / 2.,
+        ),
+        vec2(width / 2. + 1., height / 2. + 0.5),
+    );
+    for (i, (row, color)) in rows.iter().enumerate() {
+        strokes.color = *color;
+        strokes.text(
+            row,
+            vec2(left, top - i as f32 * LINE_HEIGHT),
+            TEXT_SIZE,
+            Align::Left,
+        );
+    }
+    strokes
+}
+
+pub(crate) struct DebugOverlay {
+    context: Context,
+    camera: Camera,
+    table: Option<Gm<Mesh, ColorMaterial>>,
+    thrust: Gm<Mesh, ColorMaterial>,
+    gravity: Gm<Mesh, ColorMaterial>,
+    drag: Gm<Mesh, ColorMaterial>,
+    center_of_pressure: Gm<Mesh, ColorMaterial>,
+    has_center_of_pressure: bool,
+}
+
+impl DebugOverlay {
+    pub fn new(context: &Context, viewport: Viewport) -> Self {
+        let camera = Camera::new_orthographic(
+            viewport,
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            OVERLAY_HEIGHT,
+            0.1,
+            1000.0,
+        );
+        let object = |mesh: &TriMesh, color: Srgba| {
+            Gm::new(
+                Mesh::new(context, mesh),
+                ColorMaterial::new(
+                    context,
+                    &CpuMaterial {
+                        albedo: color,
+                        ..Default::default()
+                    },
+                ),
+            )
+        };
+        let arrow = TriMesh::arrow(0.8, 0.5, 8);
+        Self {
+            context: context.clone(),
+            camera,
+            table: None,
+            thrust: object(&arrow, THRUST_COLOR),
+            gravity: object(&arrow, GRAVITY_COLOR),
+            drag: object(&arrow, DRAG_COLOR),
+            center_of_pressure: object(&TriMesh::sphere(8), CENTER_OF_PRESSURE_COLOR),
+            has_center_of_pressure: false,
+        }
+    }
+
+    /// Rebuilds the table and moves the arrows to the aircraft at `transform`, with the
+    /// arrows `arrow_scale` meters long per Newton.
+    pub fn update(
+        &mut self,
+        viewport: Viewport,
+        debug: &AeroDebug,
+        transform: &Mat4,
+        arrow_scale: f32,
+    ) {
+        self.camera.set_viewport(viewport);
+        let half_width = OVERLAY_HEIGHT / 2. * viewport.aspect();
+        self.table = draw(debug, arrow_scale, half_width).to_object(&self.context, LINE_WIDTH);
+
+        let to_world = |v: &rapier3d::prelude::Vector<f32>| vec3(v.x, v.y, v.z);
+        let center_of_mass = (transform * to_world(&debug.center_of_mass).extend(1.)).truncate();
+        for (arrow, force) in [
+            (&mut self.thrust, &debug.thrust),
+            (&mut self.gravity, &debug.gravity),
+            (&mut self.drag, &debug.drag),
+        ] {
+            let vector = (transform * to_world(force).extend(0.)).truncate() * arrow_scale;
+            arrow.set_transformation(arrow_transformation(center_of_mass, vector));
+        }
+        self.has_center_of_pressure = debug.center_of_pressure.is_some();
+        if let Some(cp) = &debug.center_of_pressure {
+            let position =
+                (transform * to_world(&(debug.center_of_mass + cp)).extend(1.)).truncate();
+            self.center_of_pressure.set_transformation(
+                Mat4::from_translation(position) * Mat4::from_scale(MARKER_RADIUS),
+            );
+        }
+    }
+
+    /// Renders the arrows and the marker in the scene.
+    pub fn render_arrows(&self, render: &RenderTarget, camera: &Camera) {
+        let mut objects: Vec<&dyn Object> = vec![&self.thrust, &self.gravity, &self.drag];
+        if self.has_center_of_pressure {
+            objects.push(&self.center_of_pressure);
+        }
+        render.render(camera, objects, &[]);
+    }
+
+    /// Renders the table over the scene.
+    pub fn render_table(&self, render: &RenderTarget) {
+        if let Some(table) = &self.table {
+            render.render(&self.camera, [table], &[]);
+        }
+    }
+}
+
+#[test]
+fn test_debug_overlay() {
+    use rapier3d::prelude::*;
+
+    use crate::vehicle::WingDebug;
+
+    let close = |a: Vec3, b: Vec3| (a - b).magnitude() < 1e-4;
+    let origin = vec3(1., 2., 3.);
+    for vector in [
+        vec3(0., 5., 0.),
+        vec3(-2., 0., 0.),
+        vec3(1., 1., 1.),
+        vec3(0., 0., 0.),
+    ] {
+        let transform = arrow_transformation(origin, vector);
+        assert!(close(
+            (transform * vec3(1., 0., 0.).extend(1.)).truncate(),
+            origin + vector
+        ));
+    }
+
+    let debug = AeroDebug {
+        wings: vec![WingDebug {
+            name: "MainRight".to_string(),
+            airflow: vector![0., -10., 150.],
+            angle_of_attack: 3.8,
+            deflection: -2.,
+            force: vector![0., 20000., 1000.],
+            torque: vector![-500., 0., 10000.],
+        }],
+        force: vector![0., 20000., 1000.],
+        torque: vector![-500., 0., 10000.],
+        center_of_pressure: None,
+        thrust: vector![0., 0., -50000.],
+        gravity: vector![0., -150000., 0.],
+        drag: vector![0., 0., 1000.],
+        center_of_mass: vector![0., 0., 0.],
+    };
+    let rows = rows(&debug, 1. / 60.);
+    let text: Vec<_> = rows.iter().map(|(row, _)| row.as_str()).collect();
+    assert_eq!(
+        text[2],
+        "MainRight       150   3.8  -2.0    0.0   20.0    1.0   -0.5    0.0   10.0"
+    );
+    // The totals line up with the columns of the wings.
+    assert_eq!(text[3].len(), text[2].len());
+    assert!(text[3].ends_with(&text[2][31..]));
+    assert_eq!(text[4], "CENTER OF PRESSURE -");
+    assert_eq!(text[5], "THRUST    50.0 KN");
+    assert_eq!(text[6], "WEIGHT   150.0 KN");
+    assert_eq!(text[8], "ARROWS 16.7 M PER KN");
+
+    // The table stays inside the viewport.
+    for aspect in [4. / 3., 16. / 9.] {
+        let half_width = OVERLAY_HEIGHT / 2. * aspect;
+        let strokes = draw(&debug, 1. / 60., half_width);
+        for p in strokes
+            .fills
+            .iter()
+            .flat_map(|fill| fill.points)
+            .chain(strokes.strokes.iter().flat_map(|s| [s.start, s.end]))
+        {
+            assert!(p.x.abs() <= half_width && p.y.abs() <= OVERLAY_HEIGHT / 2.);
+        }
+    }
+}
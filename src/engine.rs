@@ -0,0 +1,245 @@
+//! Jet engine with spool lag, afterburner, fuel consumption and start/stop.
+
+use crate::aircraft::EngineDef;
+
+/// Throttle position above which the afterburner is engaged, if the engine has one
+const AFTERBURNER_DETENT: f32 = 0.9;
+/// N1 required to light the afterburner
+const AFTERBURNER_MIN_N1: f32 = 0.95;
+/// Time constant of the afterburner ignition [s]
+const AFTERBURNER_TIME: f32 = 0.5;
+/// Time constant of the starter spinning up the engine, relative to the spool time
+const STARTER_TIME_FACTOR: f32 = 3.;
+/// Fuel flow at idle, relative to the fuel flow at full dry thrust
+const IDLE_FUEL_FLOW: f32 = 0.1;
+/// Exponent of the density ratio in the thrust lapse with altitude
+const ALTITUDE_LAPSE: f32 = 0.7;
+/// Coefficients of the Mach number in the thrust lapse with speed. The thrust drops at
+/// subsonic speeds due to the momentum drag and recovers with the ram compression.
+const MOMENTUM_DRAG: f32 = 0.25;
+const RAM_RECOVERY: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EngineState {
+    Off,
+    /// The starter is spinning the engine up to idle
+    Starting,
+    Running,
+}
+
+#[derive(Clone)]
+pub(crate) struct Engine {
+    pub state: EngineState,
+    /// Fan speed relative to the maximum, in the range [0, 1]
+    pub n1: f32,
+    /// Afterburner intensity in the range [0, 1]
+    pub afterburner: f32,
+    /// Remaining fuel [kg]
+    pub fuel: f32,
+    thrust: f32,
+    afterburner_thrust: f32,
+    idle: f32,
+    spool_time: f32,
+    fuel_flow: f32,
+    afterburner_fuel_flow: f32,
+}
+
+impl Engine {
+    /// Creates an engine running at idle.
+    pub fn new(def: &EngineDef) -> Self {
+        Self {
+            state: EngineState::Running,
+            n1: def.idle,
+            afterburner: 0.,
+            fuel: def.fuel,
+            thrust: def.thrust,
+            afterburner_thrust: def.afterburner_thrust,
+            idle: def.idle,
+            spool_time: def.spool_time,
+            fuel_flow: def.fuel_flow,
+            afterburner_fuel_flow: def.afterburner_fuel_flow,
+        }
+    }
+
+    fn has_afterburner(&self) -> bool {
+        self.thrust < self.afterburner_thrust
+    }
+
+    /// Engages the starter if the engine is off and has fuel.
+    pub fn start(&mut self) {
+        if self.state == EngineState::Off && 0. < self.fuel {
+            self.state = EngineState::Starting;
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.state = EngineState::Off;
+    }
+
+    pub fn toggle(&mut self) {
+        if self.state == EngineState::Off {
+            self.start();
+        } else {
+            self.stop();
+        }
+    }
+
+    /// Returns the N1 and afterburner intensity commanded by the throttle in the range [0, 1].
+    pub fn command(&self, throttle: f32) -> (f32, f32) {
+        if self.state != EngineState::Running {
+            return (0., 0.);
+        }
+        let detent = if self.has_afterburner() {
+            AFTERBURNER_DETENT
+        } else {
+            1.
+        };
+        let n1 = self.idle + (1. - self.idle) * (throttle / detent).min(1.);
+        let afterburner = if detent < throttle {
+            (throttle - detent) / (1. - detent)
+        } else {
+            0.
+        };
+        (n1, afterburner)
+    }
+
+    /// Spools the engine towards the throttle position and burns fuel.
+    pub fn update(&mut self, throttle: f32, delta_time: f32) {
+        let (target_n1, mut target_afterburner) = match self.state {
+            EngineState::Off => (0., 0.),
+            EngineState::Starting => (self.idle, 0.),
+            EngineState::Running => self.command(throttle),
+        };
+        let spool_time = match self.state {
+            EngineState::Starting => self.spool_time * STARTER_TIME_FACTOR,
+            _ => self.spool_time,
+        };
+        self.n1 += (target_n1 - self.n1) * (1. - (-delta_time / spool_time).exp());
+        if self.state == EngineState::Starting && self.idle * 0.98 <= self.n1 {
+            self.state = EngineState::Running;
+        }
+
+        if self.n1 < AFTERBURNER_MIN_N1 {
+            target_afterburner = 0.;
+        }
+        self.afterburner +=
+            (target_afterburner - self.afterburner) * (1. - (-delta_time / AFTERBURNER_TIME).exp());
+
+        if self.state != EngineState::Off {
+            let flow = self.fuel_flow
+                * (IDLE_FUEL_FLOW + (1. - IDLE_FUEL_FLOW) * self.dry_fraction())
+                + self.afterburner_fuel_flow * self.afterburner;
+            self.fuel = (self.fuel - flow * delta_time).max(0.);
+            if self.fuel == 0. {
+                // Flameout
+                self.state = EngineState::Off;
+                self.afterburner = 0.;
+            }
+        }
+    }
+
+    /// Sets the N1 and the afterburner to their steady values at the throttle position.
+    pub fn spool(&mut self, throttle: f32) {
+        (self.n1, self.afterburner) = self.command(throttle);
+    }
+
+    /// Returns the throttle position that gives the thrust [N] at the given air density
+    /// ratio and Mach number, or `None` if the engine can't produce it.
+    pub fn throttle_for(&self, thrust: f32, density_ratio: f32, mach: f32) -> Option<f32> {
+        let static_thrust = thrust / lapse(density_ratio, mach);
+        if !(0. <= static_thrust && static_thrust <= self.afterburner_thrust) {
+            return None;
+        }
+        if static_thrust == 0. {
+            return Some(0.);
+        }
+        let detent = if self.has_afterburner() {
+            AFTERBURNER_DETENT
+        } else {
+            1.
+        };
+        Some(if static_thrust <= self.thrust {
+            detent * static_thrust / self.thrust
+        } else {
+            detent
+                + (1. - detent) * (static_thrust - self.thrust)
+                    / (self.afterburner_thrust - self.thrust)
+        })
+    }
+
+    /// Fraction of the full dry thrust produced at the current N1
+    fn dry_fraction(&self) -> f32 {
+        ((self.n1 - self.idle) / (1. - self.idle)).clamp(0., 1.)
+    }
+
+    /// Returns the thrust [N] at the given air density ratio and Mach number.
+    pub fn thrust(&self, density_ratio: f32, mach: f32) -> f32 {
+        let static_thrust = self.thrust * self.dry_fraction()
+            + (self.afterburner_thrust - self.thrust) * self.afterburner;
+        static_thrust * lapse(density_ratio, mach)
+    }
+}
+
+/// Ratio of the thrust to the static thrust at sea level
+fn lapse(density_ratio: f32, mach: f32) -> f32 {
+    density_ratio.powf(ALTITUDE_LAPSE) * (1. - MOMENTUM_DRAG * mach + RAM_RECOVERY * mach * mach)
+}
+
+#[test]
+fn test_engine() {
+    let def = EngineDef {
+        thrust: 1000.,
+        afterburner_thrust: 1500.,
+        idle: 0.6,
+        spool_time: 1.,
+        fuel: 100.,
+        fuel_flow: 1.,
+        afterburner_fuel_flow: 4.,
+    };
+    let mut engine = Engine::new(&def);
+    assert_eq!(engine.thrust(1., 0.), 0.);
+
+    // Spools up with the time constant, to full dry thrust at the detent
+    let dt = 0.01;
+    for _ in 0..100 {
+        engine.update(AFTERBURNER_DETENT, dt);
+    }
+    assert!((engine.dry_fraction() - (1. - (-1f32).exp())).abs() < 1e-2);
+    assert_eq!(engine.afterburner, 0.);
+    for _ in 0..500 {
+        engine.update(1., dt);
+    }
+    assert!((engine.thrust(1., 0.) - 1500.).abs() < 10.);
+    assert!(engine.thrust(0.5, 0.) < engine.thrust(1., 0.));
+    assert!(engine.thrust(1., 0.5) < engine.thrust(1., 0.));
+
+    // The throttle for a thrust spools up to it.
+    let throttle = engine.throttle_for(1200., 0.8, 0.3).unwrap();
+    let mut spooled = Engine::new(&def);
+    spooled.spool(throttle);
+    assert!((spooled.thrust(0.8, 0.3) - 1200.).abs() < 1e-2);
+    assert_eq!(engine.throttle_for(1600., 1., 0.), None);
+
+    // Afterburner burns fuel until the flameout
+    let fuel = engine.fuel;
+    engine.update(1., 0.1);
+    assert!((fuel - engine.fuel - 0.5).abs() < 1e-2);
+    for _ in 0..2000 {
+        engine.update(1., dt);
+    }
+    assert_eq!(engine.fuel, 0.);
+    assert_eq!(engine.state, EngineState::Off);
+    engine.start();
+    assert_eq!(engine.state, EngineState::Off);
+
+    // The starter spins the engine up to idle
+    engine.fuel = 10.;
+    engine.n1 = 0.;
+    engine.start();
+    assert_eq!(engine.state, EngineState::Starting);
+    assert_eq!(engine.command(1.), (0., 0.));
+    for _ in 0..2000 {
+        engine.update(0., dt);
+    }
+    assert_eq!(engine.state, EngineState::Running);
+}
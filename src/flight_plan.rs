@@ -0,0 +1,394 @@
+//! Flight plan of waypoints, and the lateral and vertical navigation through them.
+//!
+//! The lateral navigation tracks the great circle of each leg, which is a straight line on
+//! the flat ground, by steering to a point on the leg a turn radius ahead. It sequences to the
+//! next leg ahead of the waypoint by the distance the turn takes at the maximum bank angle.
+//! The vertical navigation follows a straight path between the waypoint altitudes.
+
+use std::{error::Error, path::Path};
+
+use rapier3d::{na::Vector2, prelude::*};
+use serde::Deserialize;
+use three_d::{ColorMaterial, Context, CpuMaterial, Gm, Mesh, Object};
+use three_d_asset::{Mat4, Quat, Srgba, TriMesh, Vec3};
+
+use crate::{autopilot::FlightState, physics::GRAVITY};
+
+/// Shortest distance ahead on the leg that the course is steered to [m]
+const MIN_LOOKAHEAD: f32 = 500.;
+/// Largest intercept angle to the leg [deg]
+const MAX_INTERCEPT: f32 = 45.;
+/// Largest turn at a waypoint considered for the turn anticipation [deg]
+const MAX_ANTICIPATED_TURN: f32 = 120.;
+
+/// Radius of the waypoint markers [m]
+const WAYPOINT_RADIUS: f32 = 20.;
+/// Radius of the leg lines [m]
+const LEG_RADIUS: f32 = 2.;
+
+/// A waypoint of the flight plan
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Waypoint {
+    pub name: String,
+    /// Position in world coordinates, whose y is the altitude to cross the waypoint at
+    pub pos: Vector<f32>,
+    /// Airspeed to fly the leg to this waypoint at [m/s]
+    pub speed: Option<f32>,
+    /// Lowest altitude on the leg to this waypoint [m]
+    pub min_altitude: Option<f32>,
+    /// Highest altitude on the leg to this waypoint [m]
+    pub max_altitude: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlightPlanFile {
+    waypoints: Vec<WaypointFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WaypointFile {
+    name: String,
+    pos: [f32; 3],
+    speed: Option<f32>,
+    min_altitude: Option<f32>,
+    max_altitude: Option<f32>,
+}
+
+impl Waypoint {
+    fn from_file(waypoint: WaypointFile) -> Result<Self, String> {
+        let name = &waypoint.name;
+        if !waypoint.pos.iter().all(|v| v.is_finite()) {
+            return Err(format!(
+                "waypoint \"{name}\": pos must be finite, got {:?}",
+                waypoint.pos
+            ));
+        }
+        if let Some(speed) = waypoint.speed {
+            if !(speed.is_finite() && 0. < speed) {
+                return Err(format!(
+                    "waypoint \"{name}\": speed must be a positive number, got {speed}"
+                ));
+            }
+        }
+        for (field, altitude) in [
+            ("min_altitude", waypoint.min_altitude),
+            ("max_altitude", waypoint.max_altitude),
+        ] {
+            if let Some(altitude) = altitude {
+                if !altitude.is_finite() {
+                    return Err(format!(
+                        "waypoint \"{name}\": {field} must be finite, got {altitude}"
+                    ));
+                }
+            }
+        }
+        if let (Some(min), Some(max)) = (waypoint.min_altitude, waypoint.max_altitude) {
+            if max < min {
+                return Err(format!(
+                    "waypoint \"{name}\": min_altitude {min} is above max_altitude {max}"
+                ));
+            }
+        }
+        Ok(Self {
+            name: waypoint.name,
+            pos: Vector::from(waypoint.pos),
+            speed: waypoint.speed,
+            min_altitude: waypoint.min_altitude,
+            max_altitude: waypoint.max_altitude,
+        })
+    }
+}
+
+/// Commands of the navigation to the active waypoint
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Guidance {
+    /// Index of the active waypoint
+    pub waypoint: usize,
+    /// Course to fly, clockwise from north [deg]
+    pub course: f32,
+    /// Altitude on the vertical path [m]
+    pub altitude: f32,
+    /// Airspeed of the leg [m/s]
+    pub speed: Option<f32>,
+    /// Length of the active leg [m]
+    pub leg_length: f32,
+    /// Horizontal distance to go to the active waypoint [m]
+    pub distance_to_go: f32,
+    /// Estimated time to the active waypoint at the current ground speed [s]
+    pub eta: Option<f32>,
+}
+
+pub(crate) struct FlightPlan {
+    pub waypoints: Vec<Waypoint>,
+    /// Index of the waypoint at the end of the active leg
+    pub active: usize,
+    /// Start of the first leg, where the navigation was engaged
+    origin: Option<Vector<f32>>,
+}
+
+/// Horizontal components in the x-z plane
+fn horizontal(v: &Vector<f32>) -> Vector2<f32> {
+    vector![v.x, v.z]
+}
+
+/// Bearing of a horizontal direction, clockwise from north (-z) in the range [0, 360) [deg]
+fn bearing(dir: &Vector2<f32>) -> f32 {
+    dir.x.atan2(-dir.y).to_degrees().rem_euclid(360.)
+}
+
+impl FlightPlan {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read flight plan {}: {e}", path.display()))?;
+        Self::parse(&src).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let file: FlightPlanFile = toml::from_str(src)?;
+        if file.waypoints.is_empty() {
+            return Err("flight plan must have at least one waypoint".into());
+        }
+        let waypoints = file
+            .waypoints
+            .into_iter()
+            .map(Waypoint::from_file)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            waypoints,
+            active: 0,
+            origin: None,
+        })
+    }
+
+    /// Restarts the plan from the first waypoint, with the first leg from where the
+    /// navigation is engaged next.
+    pub fn restart(&mut self) {
+        self.active = 0;
+        self.origin = None;
+    }
+
+    /// Start of the active leg, the previous waypoint or the origin of the first leg
+    fn leg_start(&self) -> Option<Vector<f32>> {
+        if self.active == 0 {
+            self.origin
+        } else {
+            self.waypoints
+                .get(self.active - 1)
+                .map(|waypoint| waypoint.pos)
+        }
+    }
+
+    /// Returns the commands to fly the active leg from the flight state, sequencing the
+    /// waypoints as they are passed. Turns at the maximum bank angle [deg] are anticipated.
+    /// Returns `None` when the plan is complete.
+    pub fn guide(&mut self, state: &FlightState, max_bank: f32) -> Option<Guidance> {
+        let origin = *self.origin.get_or_insert(state.position);
+        let position = horizontal(&state.position);
+        let turn_radius = state.ground_speed.powi(2) / (-GRAVITY.y * max_bank.to_radians().tan());
+        loop {
+            let to = self.waypoints.get(self.active)?;
+            let from = self.leg_start().unwrap_or(origin);
+            let leg = horizontal(&to.pos) - horizontal(&from);
+            let leg_length = leg.norm();
+            let Some(dir) = leg.try_normalize(1e-3) else {
+                self.active += 1;
+                continue;
+            };
+            let along = (position - horizontal(&from)).dot(&dir);
+            let distance_to_go = leg_length - along;
+
+            let lead = self
+                .waypoints
+                .get(self.active + 1)
+                .and_then(|next| (horizontal(&next.pos) - horizontal(&to.pos)).try_normalize(1e-3))
+                .map_or(0., |next_dir| {
+                    let turn = dir.dot(&next_dir).clamp(-1., 1.).acos();
+                    turn_radius * (turn.min(MAX_ANTICIPATED_TURN.to_radians()) / 2.).tan()
+                })
+                // Don't cut a short leg entirely.
+                .min(leg_length / 2.);
+            if distance_to_go <= lead {
+                self.active += 1;
+                continue;
+            }
+
+            // Positive to the right of the leg
+            let right = vector![-dir.y, dir.x];
+            let cross_track = (position - horizontal(&from)).dot(&right);
+            // Steer to the point on the leg a turn radius ahead.
+            let intercept = cross_track
+                .atan2(turn_radius.max(MIN_LOOKAHEAD))
+                .to_degrees()
+                .clamp(-MAX_INTERCEPT, MAX_INTERCEPT);
+            let course = (bearing(&dir) - intercept).rem_euclid(360.);
+
+            let fraction = (along / leg_length).clamp(0., 1.);
+            let mut altitude = from.y + (to.pos.y - from.y) * fraction;
+            if let Some(min) = to.min_altitude {
+                altitude = altitude.max(min);
+            }
+            if let Some(max) = to.max_altitude {
+                altitude = altitude.min(max);
+            }
+
+            return Some(Guidance {
+                waypoint: self.active,
+                course,
+                altitude,
+                speed: to.speed,
+                leg_length,
+                distance_to_go,
+                eta: (0. < state.ground_speed).then(|| distance_to_go / state.ground_speed),
+            });
+        }
+    }
+}
+
+/// Markers of the waypoints and lines of the legs in the 3D scene
+#[derive(Default)]
+pub(crate) struct FlightPlanMeshes {
+    waypoints: Vec<Gm<Mesh, ColorMaterial>>,
+    /// The leg to each waypoint, the first one from the origin
+    legs: Vec<Gm<Mesh, ColorMaterial>>,
+    /// Active waypoint and origin of the plan that the meshes were built for
+    built: Option<(usize, Option<Vector<f32>>)>,
+}
+
+impl FlightPlanMeshes {
+    /// Rebuilds the meshes when the active waypoint or the origin of the plan changes,
+    /// highlighting the active waypoint and leg.
+    pub fn update(&mut self, context: &Context, plan: &FlightPlan) {
+        if self.built == Some((plan.active, plan.origin)) {
+            return;
+        }
+        self.built = Some((plan.active, plan.origin));
+
+        let material = |active: bool| {
+            ColorMaterial::new(
+                context,
+                &CpuMaterial {
+                    albedo: if active {
+                        Srgba::new(255, 0, 255, 255)
+                    } else {
+                        Srgba::new(127, 0, 127, 160)
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+        let sphere = TriMesh::sphere(8);
+        let cylinder = TriMesh::cylinder(8);
+        let to_vec3 = |v: &Vector<f32>| Vec3::new(v.x, v.y, v.z);
+
+        self.waypoints = plan
+            .waypoints
+            .iter()
+            .enumerate()
+            .map(|(i, waypoint)| {
+                let mut marker = Gm::new(Mesh::new(context, &sphere), material(i == plan.active));
+                marker.set_transformation(
+                    Mat4::from_translation(to_vec3(&waypoint.pos))
+                        * Mat4::from_scale(WAYPOINT_RADIUS),
+                );
+                marker
+            })
+            .collect();
+
+        self.legs = plan
+            .waypoints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, waypoint)| {
+                let from = if i == 0 {
+                    plan.origin?
+                } else {
+                    plan.waypoints[i - 1].pos
+                };
+                let leg = to_vec3(&(waypoint.pos - from));
+                let length = leg.x.hypot(leg.y).hypot(leg.z);
+                if length == 0. {
+                    return None;
+                }
+                let mut line = Gm::new(Mesh::new(context, &cylinder), material(i == plan.active));
+                // The cylinder lies along the x axis from 0 to 1.
+                line.set_transformation(
+                    Mat4::from_translation(to_vec3(&from))
+                        * Mat4::from(Quat::from_arc(Vec3::unit_x(), leg / length, None))
+                        * Mat4::from_nonuniform_scale(length, LEG_RADIUS, LEG_RADIUS),
+                );
+                Some(line)
+            })
+            .collect();
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &dyn Object> {
+        self.waypoints
+            .iter()
+            .chain(&self.legs)
+            .map(|gm| gm as &dyn Object)
+    }
+}
+
+#[test]
+fn test_flight_plan() {
+    let mut plan = FlightPlan::parse(
+        r#"
+[[waypoints]]
+name = "A"
+pos = [0.0, 1000.0, -4000.0]
+
+[[waypoints]]
+name = "B"
+pos = [4000.0, 1200.0, -4000.0]
+speed = 170.0
+"#,
+    )
+    .unwrap();
+    let state = |x: f32, z: f32| FlightState {
+        position: vector![x, 1000., z],
+        ground_speed: 150.,
+        ..FlightState::default()
+    };
+    let guidance = plan.guide(&state(0., 0.), 30.).unwrap();
+    assert_eq!((guidance.waypoint, guidance.course), (0, 0.));
+    assert_eq!(guidance.eta, Some(4000. / 150.));
+
+    // The turn to the east is anticipated by half the leg at most.
+    let guidance = plan.guide(&state(0., -1900.), 30.).unwrap();
+    assert_eq!(guidance.waypoint, 0);
+    let guidance = plan.guide(&state(0., -2100.), 30.).unwrap();
+    assert_eq!(guidance.waypoint, 1);
+    assert_eq!(guidance.speed, Some(170.));
+    // Steer back to the leg from the right of it.
+    assert!(
+        0. < guidance.course && guidance.course < 90.,
+        "{guidance:?}"
+    );
+
+    // The vertical path climbs along the leg.
+    let guidance = plan.guide(&state(2000., -4000.), 30.).unwrap();
+    assert!((guidance.course - 90.).abs() < 1e-3, "{guidance:?}");
+    assert!((guidance.altitude - 1100.).abs() < 1e-3, "{guidance:?}");
+    assert_eq!(guidance.distance_to_go, 2000.);
+
+    // Passing the last waypoint completes the plan.
+    assert_eq!(plan.guide(&state(4100., -4000.), 30.), None);
+    assert_eq!(plan.active, 2);
+
+    let parse_err = |src: &str| FlightPlan::parse(src).err().unwrap().to_string();
+    assert_eq!(
+        parse_err("waypoints = []"),
+        "flight plan must have at least one waypoint"
+    );
+    assert_eq!(
+        parse_err("[[waypoints]]\nname = \"A\"\npos = [0.0, 0.0, 0.0]\nspeed = -1.0"),
+        "waypoint \"A\": speed must be a positive number, got -1"
+    );
+    assert_eq!(
+        parse_err("[[waypoints]]\nname = \"A\"\npos = [0.0, 0.0, 0.0]\nmax_altitude = nan"),
+        "waypoint \"A\": max_altitude must be finite, got NaN"
+    );
+}
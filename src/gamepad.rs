@@ -0,0 +1,349 @@
+//! Analog flight controls from gamepads and joysticks.
+//!
+//! Each mapping binds an axis of the device to a control, shaped by a deadzone, an expo curve
+//! and an optional inversion. The deadzone is around the center of the stick controls and at
+//! the low end of the thrust and brakes. The mapped controls follow the axes while a device
+//! is connected and the other controls are left to the keyboard.
+//! The devices are read through the [`AxisDevice`] trait, which is implemented with gilrs
+//! behind the `gamepad` feature.
+
+use std::{error::Error, path::Path};
+
+use serde::Deserialize;
+
+use crate::control_input::{ControlInput, ControlSource};
+
+/// An axis of a gamepad or joystick, named after the standard gamepad layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DeviceAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftZ,
+    RightZ,
+    DPadX,
+    DPadY,
+}
+
+/// The control driven by an axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ControlAxis {
+    Thrust,
+    Aileron,
+    Elevator,
+    Rudder,
+    BrakeLeft,
+    BrakeRight,
+}
+
+impl ControlAxis {
+    /// Whether the control is in the range [0, 1], which the full travel of the axis covers
+    fn unipolar(&self) -> bool {
+        matches!(self, Self::Thrust | Self::BrakeLeft | Self::BrakeRight)
+    }
+}
+
+/// A source of analog axis values in the range [-1, 1]
+pub(crate) trait AxisDevice {
+    /// Processes the pending events of the devices.
+    fn poll(&mut self);
+
+    /// Returns the value of the axis, or `None` without a connected device having the axis.
+    fn axis(&self, axis: DeviceAxis) -> Option<f32>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AxisMapping {
+    pub axis: DeviceAxis,
+    pub control: ControlAxis,
+    /// Fraction of the travel around the center, or at the low end for the thrust and
+    /// brakes, that is ignored
+    #[serde(default)]
+    pub deadzone: f32,
+    /// Blend of the cubic curve into the linear response in the range [0, 1], which softens
+    /// the response around the center
+    #[serde(default)]
+    pub expo: f32,
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl AxisMapping {
+    /// Shapes the raw axis value into the control value.
+    pub fn shape(&self, value: f32) -> f32 {
+        let value = if self.invert { -value } else { value }.clamp(-1., 1.);
+        // A unipolar control rests at the low end of the travel rather than the center.
+        let value = if self.control.unipolar() {
+            (value + 1.) / 2.
+        } else {
+            value
+        };
+        let magnitude = if value.abs() <= self.deadzone {
+            0.
+        } else {
+            (value.abs() - self.deadzone) / (1. - self.deadzone)
+        };
+        let magnitude = (1. - self.expo) * magnitude + self.expo * magnitude.powi(3);
+        magnitude.copysign(value)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(0. ..1.).contains(&self.deadzone) {
+            return Err(format!(
+                "{:?} axis: deadzone must be in the range [0, 1), got {}",
+                self.control, self.deadzone
+            ));
+        }
+        if !(0. ..=1.).contains(&self.expo) {
+            return Err(format!(
+                "{:?} axis: expo must be in the range [0, 1], got {}",
+                self.control, self.expo
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingFile {
+    axes: Vec<AxisMapping>,
+}
+
+/// Parses the axis mappings from a TOML text.
+pub(crate) fn parse_mappings(src: &str) -> Result<Vec<AxisMapping>, Box<dyn Error>> {
+    let file: MappingFile = toml::from_str(src)?;
+    for mapping in &file.axes {
+        mapping.validate()?;
+    }
+    Ok(file.axes)
+}
+
+pub(crate) fn load_mappings(path: impl AsRef<Path>) -> Result<Vec<AxisMapping>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let src = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read gamepad mapping {}: {e}", path.display()))?;
+    parse_mappings(&src).map_err(|e| format!("{}: {e}", path.display()).into())
+}
+
+/// Mappings of the standard gamepad layout, the left stick for the aileron and elevator and
+/// the right stick for the rudder, leaving the thrust and brakes to the keyboard.
+pub(crate) fn default_mappings() -> Vec<AxisMapping> {
+    let mapping = |axis, control, invert| AxisMapping {
+        axis,
+        control,
+        deadzone: 0.05,
+        expo: 0.3,
+        invert,
+    };
+    vec![
+        // Positive aileron rolls to the left, where the stick reads negative.
+        mapping(DeviceAxis::LeftStickX, ControlAxis::Aileron, true),
+        // Pushing the stick forward pitches the nose down.
+        mapping(DeviceAxis::LeftStickY, ControlAxis::Elevator, false),
+        // Positive rudder yaws to the right, where the stick reads positive.
+        mapping(DeviceAxis::RightStickX, ControlAxis::Rudder, false),
+    ]
+}
+
+/// Opens the gamepads and joysticks with the axis mapping file, or the default mappings.
+/// Without a device backend, there are no analog controls.
+#[cfg(feature = "gamepad")]
+pub(crate) fn open(
+    mapping: Option<&str>,
+) -> Result<Option<Box<dyn ControlSource>>, Box<dyn Error>> {
+    let mappings = match mapping {
+        Some(path) => load_mappings(path)?,
+        None => default_mappings(),
+    };
+    match GilrsDevice::new() {
+        Ok(device) => Ok(Some(Box::new(GamepadControl::new(device, mappings)))),
+        Err(e) => {
+            eprintln!("{e}, continuing with the keyboard only");
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+pub(crate) fn open(
+    mapping: Option<&str>,
+) -> Result<Option<Box<dyn ControlSource>>, Box<dyn Error>> {
+    match mapping {
+        Some(_) => Err("Gamepads require building with the gamepad feature".into()),
+        None => Ok(None),
+    }
+}
+
+/// Controls from the analog axes of a device.
+pub(crate) struct GamepadControl<D> {
+    device: D,
+    mappings: Vec<AxisMapping>,
+}
+
+impl<D: AxisDevice> GamepadControl<D> {
+    pub fn new(device: D, mappings: Vec<AxisMapping>) -> Self {
+        Self { device, mappings }
+    }
+}
+
+impl<D: AxisDevice> ControlSource for GamepadControl<D> {
+    fn command(&mut self, _delta_time: f64, current: &ControlInput) -> ControlInput {
+        self.device.poll();
+        let mut ret = *current;
+        for mapping in &self.mappings {
+            let Some(value) = self.device.axis(mapping.axis) else {
+                continue;
+            };
+            let value = mapping.shape(value);
+            match mapping.control {
+                ControlAxis::Thrust => ret.thrust = value,
+                ControlAxis::Aileron => ret.aileron = value,
+                ControlAxis::Elevator => ret.elevator = value,
+                ControlAxis::Rudder => ret.rudder = value,
+                ControlAxis::BrakeLeft => ret.brake_left = value,
+                ControlAxis::BrakeRight => ret.brake_right = value,
+            }
+        }
+        ret.clamped()
+    }
+}
+
+/// Gamepads and joysticks read with gilrs. The device that sent the latest event is used.
+#[cfg(feature = "gamepad")]
+pub(crate) struct GilrsDevice {
+    gilrs: gilrs::Gilrs,
+    active: Option<gilrs::GamepadId>,
+}
+
+#[cfg(feature = "gamepad")]
+impl GilrsDevice {
+    pub fn new() -> Result<Self, String> {
+        let gilrs = gilrs::Gilrs::new().map_err(|e| format!("Failed to open gamepads: {e}"))?;
+        Ok(Self {
+            gilrs,
+            active: None,
+        })
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl AxisDevice for GilrsDevice {
+    fn poll(&mut self) {
+        while let Some(gilrs::Event { id, .. }) = self.gilrs.next_event() {
+            self.active = Some(id);
+        }
+        if self
+            .active
+            .is_none_or(|id| self.gilrs.connected_gamepad(id).is_none())
+        {
+            self.active = self.gilrs.gamepads().next().map(|(id, _)| id);
+        }
+    }
+
+    fn axis(&self, axis: DeviceAxis) -> Option<f32> {
+        let gamepad = self.gilrs.connected_gamepad(self.active?)?;
+        let axis = match axis {
+            DeviceAxis::LeftStickX => gilrs::Axis::LeftStickX,
+            DeviceAxis::LeftStickY => gilrs::Axis::LeftStickY,
+            DeviceAxis::RightStickX => gilrs::Axis::RightStickX,
+            DeviceAxis::RightStickY => gilrs::Axis::RightStickY,
+            DeviceAxis::LeftZ => gilrs::Axis::LeftZ,
+            DeviceAxis::RightZ => gilrs::Axis::RightZ,
+            DeviceAxis::DPadX => gilrs::Axis::DPadX,
+            DeviceAxis::DPadY => gilrs::Axis::DPadY,
+        };
+        gamepad.axis_code(axis)?;
+        Some(gamepad.value(axis))
+    }
+}
+
+/// A device whose axes are set by the caller, for testing without hardware
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockDevice {
+    pub axes: std::collections::HashMap<DeviceAxis, f32>,
+    pub polls: usize,
+}
+
+#[cfg(test)]
+impl AxisDevice for MockDevice {
+    fn poll(&mut self) {
+        self.polls += 1;
+    }
+
+    fn axis(&self, axis: DeviceAxis) -> Option<f32> {
+        self.axes.get(&axis).copied()
+    }
+}
+
+#[test]
+fn test_gamepad() {
+    let mappings = parse_mappings(
+        r#"
+[[axes]]
+axis = "left_stick_x"
+control = "aileron"
+deadzone = 0.1
+invert = true
+
+[[axes]]
+axis = "left_stick_y"
+control = "elevator"
+expo = 1.0
+
+[[axes]]
+axis = "left_z"
+control = "thrust"
+"#,
+    )
+    .unwrap();
+    let mut device = MockDevice::default();
+    device.axes.insert(DeviceAxis::LeftStickX, 0.55);
+    device.axes.insert(DeviceAxis::LeftStickY, -0.5);
+    device.axes.insert(DeviceAxis::LeftZ, 0.);
+    let mut control = GamepadControl::new(device, mappings);
+    let current = ControlInput {
+        rudder: 0.3,
+        ..ControlInput::default()
+    };
+    let command = control.command(0.1, &current);
+    assert_eq!(control.device.polls, 1);
+    // Inverted and rescaled outside the deadzone
+    assert!((command.aileron - -0.5).abs() < 1e-6, "{command:?}");
+    // Full expo is the cubic curve.
+    assert!((command.elevator - -0.125).abs() < 1e-6, "{command:?}");
+    // The center of the axis is half thrust.
+    assert_eq!(command.thrust, 0.5);
+    // The unmapped controls are kept.
+    assert_eq!(command.rudder, 0.3);
+
+    // Within the deadzone
+    control.device.axes.insert(DeviceAxis::LeftStickX, -0.05);
+    assert_eq!(control.command(0.1, &current).aileron, 0.);
+
+    // The deadzone of the thrust is at idle, not at the center of the axis.
+    control.mappings[2].deadzone = 0.1;
+    control.device.axes.insert(DeviceAxis::LeftZ, -0.9);
+    assert_eq!(control.command(0.1, &current).thrust, 0.);
+    control.device.axes.insert(DeviceAxis::LeftZ, 0.);
+    let thrust = control.command(0.1, &current).thrust;
+    assert!((thrust - 0.4 / 0.9).abs() < 1e-6, "{thrust}");
+
+    // Without the axes, the controls are left to the other sources.
+    control.device.axes.clear();
+    assert_eq!(control.command(0.1, &current), current);
+
+    assert_eq!(
+        parse_mappings("[[axes]]\naxis = \"left_z\"\ncontrol = \"thrust\"\ndeadzone = 1.0")
+            .err()
+            .unwrap()
+            .to_string(),
+        "Thrust axis: deadzone must be in the range [0, 1), got 1"
+    );
+    assert!(parse_mappings("[[axes]]\naxis = \"hat\"\ncontrol = \"thrust\"").is_err());
+}
@@ -22,15 +22,17 @@ pub(crate) fn gen_ground(context: &Context) -> Result<impl Object, String> {
         }
     }
 
-    let mut texture = Texture2D::default();
-    texture.width = tex_size as u32;
-    texture.height = tex_size as u32;
-    texture.data = TextureData::RgbF32(texture_data);
+    let texture = Texture2D {
+        width: tex_size as u32,
+        height: tex_size as u32,
+        data: TextureData::RgbF32(texture_data),
+        ..Default::default()
+    };
 
     let ground = TriMesh::square();
     let instances = Instances {
         transformations: (-10..=10)
-            .map(|x| {
+            .flat_map(|x| {
                 (-10..=10).map(move |y| {
                     Mat4::from_translation(Vec3::new(
                         x as f32 * tile_size * 2.,
@@ -39,7 +41,6 @@ pub(crate) fn gen_ground(context: &Context) -> Result<impl Object, String> {
                     ))
                 })
             })
-            .flatten()
             .collect(),
         ..Default::default()
     };
@@ -49,7 +50,7 @@ pub(crate) fn gen_ground(context: &Context) -> Result<impl Object, String> {
     let mut ground_obj = Gm::new(
         instanced_mesh,
         PhysicalMaterial::new(
-            &context,
+            context,
             &CpuMaterial {
                 roughness: 0.6,
                 metallic: 0.,
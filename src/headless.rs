@@ -0,0 +1,243 @@
+//! Headless simulation, which steps the physics and the vehicle with a fixed timestep
+//! without creating a window or a GL context.
+
+use std::{cell::RefCell, error::Error, io::Write, path::Path, rc::Rc};
+
+use crate::{
+    aircraft::AircraftDef,
+    control_input::{ControlInput, ControlSource},
+    flight_plan::FlightPlan,
+    physics::PhysicsSet,
+    recorder::FlightRecorder,
+    trim::{start_trimmed, TrimState},
+    vehicle::{Vehicle, VEHICLE_POSITION},
+    wind::WindField,
+};
+
+/// A set of commanded control values from `time` on, until the next keyframe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ControlKey {
+    time: f64,
+    command: ControlInput,
+}
+
+/// Error of a script line with a wrong number of columns
+const COLUMNS_ERROR: &str = concat!(
+    "expected 5 columns (time,thrust,aileron,elevator,rudder) ",
+    "or 7 with brake_left,brake_right"
+);
+
+/// Scripted control inputs for the headless simulation.
+///
+/// The script is a CSV text with columns `time,thrust,aileron,elevator,rudder`, optionally
+/// followed by `brake_left,brake_right`.
+/// Empty lines and lines starting with `#` are ignored.
+/// The controls follow the commanded values by the dynamics of the vehicle's actuators.
+#[derive(Default)]
+pub(crate) struct ControlScript {
+    keys: Vec<ControlKey>,
+    /// Playback time as a control source
+    time: f64,
+}
+
+impl ControlScript {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read control script {}: {e}", path.display()))?;
+        Ok(Self::parse(&src).map_err(|e| format!("{}: {e}", path.display()))?)
+    }
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut keys: Vec<ControlKey> = vec![];
+        for (i, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut values = line
+                .split(',')
+                .map(|s| s.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {e}", i + 1))?;
+            if !values.iter().all(|v| v.is_finite()) {
+                return Err(format!("line {}: values must be finite", i + 1));
+            }
+            if values.len() != 5 && values.len() != 7 {
+                return Err(format!(
+                    "line {}: {COLUMNS_ERROR}, got {}",
+                    i + 1,
+                    values.len()
+                ));
+            }
+            // The brakes are released without their columns.
+            values.resize(7, 0.);
+            let time = values[0];
+            if keys.last().is_some_and(|key| time < key.time) {
+                return Err(format!("line {}: time must not decrease", i + 1));
+            }
+            let command = ControlInput {
+                thrust: values[1] as f32,
+                aileron: values[2] as f32,
+                elevator: values[3] as f32,
+                rudder: values[4] as f32,
+                brake_left: values[5] as f32,
+                brake_right: values[6] as f32,
+            };
+            keys.push(ControlKey {
+                time,
+                command: command.clamped(),
+            });
+        }
+        Ok(Self { keys, time: 0. })
+    }
+
+    /// Returns the keyframe in effect at the given time.
+    fn at(&self, time: f64) -> Option<&ControlKey> {
+        self.keys.iter().rev().find(|key| key.time <= time)
+    }
+}
+
+impl ControlSource for ControlScript {
+    fn command(&mut self, delta_time: f64, current: &ControlInput) -> ControlInput {
+        let command = self.at(self.time).map_or(*current, |key| key.command);
+        self.time += delta_time;
+        command
+    }
+}
+
+pub(crate) struct HeadlessParams {
+    /// Total simulated time in seconds
+    pub duration: f64,
+    /// Number of physics steps per simulated second
+    pub step_rate: f64,
+    /// Airspeed and altitude of the trimmed level flight to start in
+    pub trim: Option<(f32, f32)>,
+    /// Flight plan to fly with the autopilot from the start
+    pub plan: Option<String>,
+}
+
+/// Runs the simulation from the initial state and writes the trajectory to `out`
+/// in the flight data recorder format. Returns the trimmed state it started in, if any.
+pub(crate) fn run_headless(
+    aircraft: &AircraftDef,
+    source: &mut dyn ControlSource,
+    wind: &mut WindField,
+    params: &HeadlessParams,
+    out: &mut impl Write,
+) -> Result<Option<TrimState>, Box<dyn Error>> {
+    let ground_width = 1000. * 10.;
+    let mut physics = PhysicsSet::new(ground_width);
+    let delta_time = 1. / params.step_rate;
+    physics.integration_parameters.dt = delta_time as f32;
+
+    let mut vehicle = Vehicle::new(physics.new_body(VEHICLE_POSITION, aircraft), aircraft);
+    let mut trim = None;
+    if let Some((speed, altitude)) = params.trim {
+        trim = Some(start_trimmed(&mut vehicle, &mut physics, speed, altitude)?);
+    }
+    if let Some(path) = &params.plan {
+        vehicle.autopilot.flight_plan = Some(FlightPlan::load(path)?);
+        let state = vehicle.flight_state(&physics.rigid_body_set, wind);
+        let controls = vehicle.controls;
+        vehicle.autopilot.engage_nav(&controls, &state);
+    }
+    let vehicle = Rc::new(RefCell::new(vehicle));
+    let vehicle2 = vehicle.clone();
+    physics.register_collision(move |e| vehicle2.borrow_mut().collide(e));
+
+    let mut recorder = FlightRecorder::new(out, vehicle.borrow().wing_names())?;
+
+    let steps = (params.duration * params.step_rate).round() as u64;
+    for i in 0..=steps {
+        let time = i as f64 * delta_time;
+        let mut vehicle = vehicle.borrow_mut();
+        recorder.record(&vehicle.record(&physics.rigid_body_set, time))?;
+
+        if i == steps {
+            break;
+        }
+        let command = source.command(delta_time, &vehicle.command);
+        vehicle.update(delta_time, &mut physics, &command, wind);
+        drop(vehicle);
+        physics.step();
+        wind.advance(delta_time);
+    }
+
+    recorder.flush()?;
+    Ok(trim)
+}
+
+#[test]
+fn test_control_script() {
+    let script = ControlScript::parse(
+        "# time,thrust,aileron,elevator,rudder
+0, 0.5, 0, 0, 0
+
+10, 1.0, -2, 0.2, 0
+20, 0, 0, 0, 0, 1, 0.5",
+    )
+    .unwrap();
+    assert_eq!(script.at(5.).unwrap().command.thrust, 0.5);
+    let command = script.at(10.).unwrap().command;
+    assert_eq!(command.thrust, 1.0);
+    assert_eq!(command.aileron, -1.0);
+    assert_eq!(command.elevator, 0.2);
+    assert_eq!(command.brake_left, 0.);
+    let command = script.at(20.).unwrap().command;
+    assert_eq!((command.brake_left, command.brake_right), (1., 0.5));
+
+    assert_eq!(
+        ControlScript::parse("0, 1, 0").err().unwrap(),
+        format!("line 1: {COLUMNS_ERROR}, got 3")
+    );
+    assert_eq!(
+        ControlScript::parse("1, 1, 0, 0, 0\n0, 1, 0, 0, 0")
+            .err()
+            .unwrap(),
+        "line 2: time must not decrease"
+    );
+    for line in ["inf, 1, 0, 0, 0", "0, NaN, 0, 0, 0"] {
+        assert_eq!(
+            ControlScript::parse(line).err().unwrap(),
+            "line 1: values must be finite"
+        );
+    }
+}
+
+#[test]
+fn test_run_headless() {
+    use crate::{aircraft::DEFAULT_AIRCRAFT, recorder::Replay};
+
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let mut script = ControlScript::parse("0, 1, 0, 0, 0\n0.5, 0, 0, 0, 0").unwrap();
+    let params = HeadlessParams {
+        duration: 1.,
+        step_rate: 60.,
+        trim: None,
+        plan: None,
+    };
+    let mut out = vec![];
+    run_headless(
+        &aircraft,
+        &mut script,
+        &mut WindField::default(),
+        &params,
+        &mut out,
+    )
+    .unwrap();
+
+    // A header and a line for every tick including the initial state
+    let src = std::str::from_utf8(&out).unwrap();
+    assert_eq!(src.lines().count(), 1 + 61);
+    let mut replay = Replay::parse(src, aircraft.wings.len()).unwrap();
+    assert_eq!((replay.start_time(), replay.end_time()), (0., 1.));
+    // The thrust follows the script through the actuator, up and back down.
+    let thrust = |replay: &mut Replay, time: f64| {
+        replay.seek(time);
+        replay.sample().0.controls.thrust
+    };
+    let peak = thrust(&mut replay, 0.5);
+    assert!(0. < peak && peak < 1., "{peak}");
+    assert!(thrust(&mut replay, 1.) < peak);
+}
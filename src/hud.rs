@@ -0,0 +1,380 @@
+//! Head-up display of the flight state in vector symbology: the airspeed and altitude tapes,
+//! the heading scale, the pitch ladder, the flight path marker and the readouts of the load
+//! factor, angle of attack and vertical speed, and a message such as the landing
+//! classification.
+//!
+//! The symbology is laid out in HUD units, where the viewport is `HUD_HEIGHT` units high
+//! with the origin at its center, so it scales with the viewport.
+
+use three_d::{Camera, ColorMaterial, Context, Gm, Mesh, RenderTarget, Viewport};
+use three_d_asset::{vec2, vec3, Srgba, Vec2};
+
+use crate::{
+    autopilot::FlightState,
+    strokes::{Align, Strokes},
+};
+
+/// Height of the viewport in HUD units
+const HUD_HEIGHT: f32 = 100.;
+const LINE_WIDTH: f32 = 0.3;
+const TEXT_SIZE: f32 = 2.5;
+const COLOR: Srgba = Srgba::new(0, 255, 0, 255);
+
+/// Offset of the pitch ladder and the flight path marker per degree
+const PITCH_SCALE: f32 = 2.;
+/// Pitch angle between the rungs of the ladder [deg]
+const LADDER_STEP: f32 = 5.;
+/// Range of the pitch ladder around the pitch attitude [deg]
+const LADDER_RANGE: f32 = 15.;
+/// Distance from the center that the flight path marker is limited to
+const MARKER_LIMIT: f32 = 30.;
+
+/// Horizontal position of the airspeed tape, mirrored for the altitude tape
+const TAPE_X: f32 = 35.;
+/// Half the height of the tapes
+const TAPE_HALF_HEIGHT: f32 = 20.;
+/// Offset along the airspeed tape per m/s
+const SPEED_SCALE: f32 = 0.5;
+const SPEED_TICK: f32 = 10.;
+const SPEED_LABEL: f32 = 50.;
+/// Offset along the altitude tape per meter
+const ALTITUDE_SCALE: f32 = 0.1;
+const ALTITUDE_TICK: f32 = 20.;
+const ALTITUDE_LABEL: f32 = 100.;
+
+const HEADING_Y: f32 = 38.;
+/// Offset along the heading scale per degree
+const HEADING_SCALE: f32 = 1.;
+const HEADING_HALF_RANGE: f32 = 20.;
+/// Baseline of the message, below the heading scale
+const MESSAGE_Y: f32 = 28.;
+
+/// Values of the tick marks on a moving tape or scale within `half_range` of `value`, every
+/// `spacing`, with their offsets from the center.
+fn ticks(
+    value: f32,
+    spacing: f32,
+    half_range: f32,
+    scale: f32,
+) -> impl Iterator<Item = (f32, f32)> {
+    let first = ((value - half_range) / spacing).ceil() as i64;
+    let last = ((value + half_range) / spacing).floor() as i64;
+    (first..=last).map(move |i| {
+        let tick = i as f32 * spacing;
+        (tick, (tick - value) * scale)
+    })
+}
+
+/// Whether `value` is a multiple of `spacing`, within the rounding of the ticks
+fn is_multiple(value: f32, spacing: f32) -> bool {
+    let ratio = value / spacing;
+    (ratio - ratio.round()).abs() < 1e-3
+}
+
+/// Draws the symbology of the flight state and the load factor, and the message if there is
+/// one, for a viewport `2 * half_width` HUD units wide.
+fn draw(state: &FlightState, load_factor: f32, message: Option<&str>, half_width: f32) -> Strokes {
+    let mut strokes = Strokes::new(COLOR);
+    // Keep the tapes inside narrow viewports.
+    let tape_x = TAPE_X.min(half_width - 12.);
+
+    draw_ladder(&mut strokes, state);
+    draw_flight_path_marker(&mut strokes, state);
+
+    // Waterline symbol at the nose direction
+    strokes.polyline([
+        vec2(-6., 0.),
+        vec2(-3., 0.),
+        vec2(-1.5, -1.5),
+        vec2(0., 0.),
+        vec2(1.5, -1.5),
+        vec2(3., 0.),
+        vec2(6., 0.),
+    ]);
+
+    draw_tape(
+        &mut strokes,
+        -tape_x,
+        state.airspeed,
+        SPEED_SCALE,
+        SPEED_TICK,
+        SPEED_LABEL,
+    );
+    draw_tape(
+        &mut strokes,
+        tape_x,
+        state.altitude,
+        ALTITUDE_SCALE,
+        ALTITUDE_TICK,
+        ALTITUDE_LABEL,
+    );
+    draw_heading(&mut strokes, state.heading);
+
+    // The readouts above the tapes, clear of the control bars below, line up with the outer
+    // edges of the boxes.
+    let readout_x = tape_x + 13.;
+    let readout_y = TAPE_HALF_HEIGHT + 3.;
+    strokes.text(
+        &format!("G {load_factor:.1}"),
+        vec2(-readout_x, readout_y),
+        TEXT_SIZE,
+        Align::Left,
+    );
+    strokes.text(
+        &format!("AOA {:.1}", state.angle_of_attack),
+        vec2(-readout_x, readout_y + TEXT_SIZE * 1.6),
+        TEXT_SIZE,
+        Align::Left,
+    );
+    strokes.text(
+        &format!("VS {:.1}", state.vertical_speed),
+        vec2(readout_x, readout_y),
+        TEXT_SIZE,
+        Align::Right,
+    );
+    if let Some(message) = message {
+        strokes.text(message, vec2(0., MESSAGE_Y), TEXT_SIZE, Align::Center);
+    }
+    strokes
+}
+
+/// Draws the rungs of the pitch ladder around the pitch attitude, rotated by the bank angle.
+/// The rungs above the horizon are solid and those below dashed, with their tips pointing
+/// to the horizon.
+fn draw_ladder(strokes: &mut Strokes, state: &FlightState) {
+    let (sin, cos) = state.roll.to_radians().sin_cos();
+    let rotate = |p: Vec2| vec2(p.x * cos - p.y * sin, p.x * sin + p.y * cos);
+    for (angle, y) in ticks(state.pitch, LADDER_STEP, LADDER_RANGE, PITCH_SCALE) {
+        if 90. < angle.abs() {
+            continue;
+        }
+        if angle == 0. {
+            strokes.line(rotate(vec2(-25., y)), rotate(vec2(-4., y)));
+            strokes.line(rotate(vec2(4., y)), rotate(vec2(25., y)));
+            continue;
+        }
+        let tip = if 0. < angle { -1. } else { 1. };
+        for side in [-1., 1.] {
+            let (inner, outer) = (side * 4., side * 12.);
+            strokes.line(rotate(vec2(inner, y + tip)), rotate(vec2(inner, y)));
+            if 0. < angle {
+                strokes.line(rotate(vec2(inner, y)), rotate(vec2(outer, y)));
+            } else {
+                let dash = (outer - inner) / 5.;
+                for i in [0., 2., 4.] {
+                    strokes.line(
+                        rotate(vec2(inner + dash * i, y)),
+                        rotate(vec2(inner + dash * (i + 1.), y)),
+                    );
+                }
+            }
+            let label = rotate(vec2(side * 14., y)) - vec2(0., TEXT_SIZE / 2.);
+            let align = if side < 0. { Align::Right } else { Align::Left };
+            strokes.text(&format!("{:.0}", angle.abs()), label, TEXT_SIZE, align);
+        }
+    }
+}
+
+/// Draws the flight path marker, a circle with wings and a tail in the direction of the
+/// velocity, limited to the center of the HUD.
+fn draw_flight_path_marker(strokes: &mut Strokes, state: &FlightState) {
+    let offset = vec2(state.flight_path[0], state.flight_path[1]) * PITCH_SCALE;
+    let center = vec2(
+        offset.x.clamp(-MARKER_LIMIT, MARKER_LIMIT),
+        offset.y.clamp(-MARKER_LIMIT, MARKER_LIMIT),
+    );
+    let radius = 1.2;
+    strokes.circle(center, radius, 12);
+    strokes.line(center - vec2(radius, 0.), center - vec2(radius + 2.5, 0.));
+    strokes.line(center + vec2(radius, 0.), center + vec2(radius + 2.5, 0.));
+    strokes.line(center + vec2(0., radius), center + vec2(0., radius + 1.5));
+}
+
+/// Draws a vertical tape of `value` at `x`, with the ticks and labels towards the center of
+/// the HUD and the boxed readout of the value on the outside.
+fn draw_tape(strokes: &mut Strokes, x: f32, value: f32, scale: f32, tick: f32, label: f32) {
+    let inward = -x.signum();
+    let half_range = TAPE_HALF_HEIGHT / scale;
+    strokes.line(vec2(x, -TAPE_HALF_HEIGHT), vec2(x, TAPE_HALF_HEIGHT));
+    for (tick_value, y) in ticks(value, tick, half_range, scale) {
+        let labeled = is_multiple(tick_value, label);
+        let length = if labeled { 2. } else { 1. };
+        strokes.line(vec2(x, y), vec2(x + inward * length, y));
+        if labeled {
+            let align = if 0. < inward {
+                Align::Left
+            } else {
+                Align::Right
+            };
+            strokes.text(
+                &format!("{tick_value:.0}"),
+                vec2(x + inward * 3., y - TEXT_SIZE / 2.),
+                TEXT_SIZE,
+                align,
+            );
+        }
+    }
+    let text = format!("{value:.0}");
+    let box_half = vec2(6., TEXT_SIZE * 0.9);
+    let box_center = vec2(x - inward * (box_half.x + 1.), 0.);
+    strokes.rect(box_center, box_half);
+    strokes.line(vec2(x, 0.), vec2(x - inward, 0.));
+    strokes.text(
+        &text,
+        box_center - vec2(0., TEXT_SIZE / 2.),
+        TEXT_SIZE,
+        Align::Center,
+    );
+}
+
+/// Draws the heading scale at the top, labeled every 10 degrees in tens of degrees and by the
+/// cardinal directions, with the boxed readout of the heading above.
+fn draw_heading(strokes: &mut Strokes, heading: f32) {
+    for (tick, x) in ticks(heading, 5., HEADING_HALF_RANGE, HEADING_SCALE) {
+        let tick = tick.rem_euclid(360.);
+        let labeled = is_multiple(tick, 10.);
+        let length = if labeled { 2. } else { 1. };
+        strokes.line(vec2(x, HEADING_Y), vec2(x, HEADING_Y - length));
+        if labeled {
+            let label = match tick.round() as i32 {
+                0 | 360 => "N".to_string(),
+                90 => "E".to_string(),
+                180 => "S".to_string(),
+                270 => "W".to_string(),
+                tick => format!("{:02}", tick / 10),
+            };
+            strokes.text(
+                &label,
+                vec2(x, HEADING_Y - 2. - TEXT_SIZE * 1.4),
+                TEXT_SIZE,
+                Align::Center,
+            );
+        }
+    }
+    // Caret at the heading and its readout
+    strokes.polyline([
+        vec2(-1., HEADING_Y + 1.5),
+        vec2(0., HEADING_Y + 0.3),
+        vec2(1., HEADING_Y + 1.5),
+    ]);
+    let box_center = vec2(0., HEADING_Y + 4.);
+    strokes.rect(box_center, vec2(4.5, TEXT_SIZE * 0.9));
+    strokes.text(
+        &format!("{:03.0}", heading.round().rem_euclid(360.)),
+        box_center - vec2(0., TEXT_SIZE / 2.),
+        TEXT_SIZE,
+        Align::Center,
+    );
+}
+
+pub(crate) struct Hud {
+    context: Context,
+    camera: Camera,
+    object: Option<Gm<Mesh, ColorMaterial>>,
+}
+
+impl Hud {
+    pub fn new(context: &Context, viewport: Viewport) -> Self {
+        let camera = Camera::new_orthographic(
+            viewport,
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            HUD_HEIGHT,
+            0.1,
+            1000.0,
+        );
+        Self {
+            context: context.clone(),
+            camera,
+            object: None,
+        }
+    }
+
+    /// Rebuilds the symbology for the flight state, the message and the viewport.
+    pub fn update(
+        &mut self,
+        viewport: Viewport,
+        state: &FlightState,
+        load_factor: f32,
+        message: Option<&str>,
+    ) {
+        self.camera.set_viewport(viewport);
+        let half_width = HUD_HEIGHT / 2. * viewport.aspect();
+        self.object =
+            draw(state, load_factor, message, half_width).to_object(&self.context, LINE_WIDTH);
+    }
+
+    pub fn render(&self, render: &RenderTarget) {
+        if let Some(object) = &self.object {
+            render.render(&self.camera, [object], &[]);
+        }
+    }
+}
+
+#[test]
+fn test_hud() {
+    let ticks: Vec<_> = ticks(103., 10., 20., 0.5).collect();
+    assert_eq!(ticks.first(), Some(&(90., -6.5)));
+    assert_eq!(ticks.last(), Some(&(120., 8.5)));
+    assert_eq!(ticks.len(), 4);
+
+    // The symbology stays inside the viewport in any attitude.
+    let states = [
+        FlightState::default(),
+        FlightState {
+            airspeed: 150.,
+            altitude: 1000.,
+            heading: 355.,
+            pitch: 7.3,
+            roll: 30.,
+            angle_of_attack: 7.3,
+            ..FlightState::default()
+        },
+        FlightState {
+            airspeed: 340.,
+            altitude: 12345.,
+            vertical_speed: -250.,
+            heading: 181.,
+            pitch: -88.,
+            roll: 180.,
+            flight_path: [-180., 90.],
+            ..FlightState::default()
+        },
+    ];
+    for state in &states {
+        for aspect in [1., 16. / 9.] {
+            let half_width = HUD_HEIGHT / 2. * aspect;
+            let strokes = draw(state, 9.5, Some("CRASHED - PRESS R TO RESET"), half_width);
+            for stroke in &strokes.strokes {
+                for p in [stroke.start, stroke.end] {
+                    assert!(
+                        p.x.abs() <= half_width && p.y.abs() <= HUD_HEIGHT / 2.,
+                        "{p:?} out of the viewport for {state:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    // The horizon is below the waterline when the nose is up.
+    let level = draw(&states[0], 1., None, 50.);
+    let climbing = draw(
+        &FlightState {
+            pitch: 5.,
+            ..FlightState::default()
+        },
+        1.,
+        None,
+        50.,
+    );
+    let horizon = |strokes: &Strokes| {
+        strokes
+            .strokes
+            .iter()
+            .find(|stroke| stroke.start.x == -25.)
+            .map(|stroke| stroke.start.y)
+    };
+    assert_eq!(horizon(&level), Some(0.));
+    assert_eq!(horizon(&climbing), Some(-5. * PITCH_SCALE));
+}
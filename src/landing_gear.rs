@@ -0,0 +1,278 @@
+//! Landing gear with raycast suspension springs, wheel friction, differential brakes and
+//! a steerable nose wheel.
+//!
+//! Each wheel casts a ray from its strut attachment point downwards in the body frame.
+//! The compression of the strut gives the spring and damper load, which limits the
+//! rolling, braking and cornering friction of the wheel.
+
+use rapier3d::{na::UnitQuaternion, prelude::*};
+
+use crate::{
+    aircraft::GearDef,
+    contact::{Contact, ContactKind},
+    control_input::ControlInput,
+    physics::PhysicsSet,
+};
+
+/// Time to extend or retract the gear [s]
+const GEAR_TRANSIT_TIME: f32 = 4.;
+/// Rolling resistance coefficient of a free wheel
+const ROLLING_RESISTANCE: f32 = 0.02;
+/// Friction coefficient of a tire against sliding sideways
+const SIDE_FRICTION: f32 = 0.8;
+/// Sliding velocity at which the friction saturates [m/s]. Below it, the friction is
+/// proportional to the velocity so that a stopped wheel doesn't jitter.
+const SLIP_VELOCITY: f32 = 0.5;
+
+struct Wheel {
+    #[allow(dead_code)]
+    /// Name of the gear, just for debugging
+    name: String,
+    /// Strut attachment point in the body frame
+    pos: Vector<f32>,
+    /// Length of the strut including the wheel radius, fully extended [m]
+    length: f32,
+    stiffness: f32,
+    damping: f32,
+    /// Steering angle at full rudder [rad]
+    steering: f32,
+    /// Friction coefficient at full brake
+    brake: f32,
+    /// Drag area while extended [m^2]
+    drag: f32,
+    /// Compression of the strut in the last update [m]
+    compression: f32,
+    /// Contact with the ground in the last update
+    contact: Option<Contact>,
+}
+
+pub(crate) struct LandingGear {
+    wheels: Vec<Wheel>,
+    /// Commanded gear position
+    down: bool,
+    /// Actual gear position, 0 for retracted and 1 for extended
+    position: f32,
+}
+
+impl LandingGear {
+    /// Creates landing gear, extended.
+    pub fn new(gears: &[GearDef]) -> Self {
+        let wheels = gears
+            .iter()
+            .map(|gear| Wheel {
+                name: gear.name.clone(),
+                pos: gear.pos,
+                length: gear.length,
+                stiffness: gear.stiffness,
+                damping: gear.damping,
+                steering: gear.steering,
+                brake: gear.brake,
+                drag: gear.drag,
+                compression: 0.,
+                contact: None,
+            })
+            .collect();
+        Self {
+            wheels,
+            down: true,
+            position: 1.,
+        }
+    }
+
+    /// Actual gear position, 0 for retracted and 1 for extended
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Whether any wheel carries a load
+    pub fn weight_on_wheels(&self) -> bool {
+        self.wheels.iter().any(|wheel| 0. < wheel.compression)
+    }
+
+    /// Wheel contacts with the ground in the last update
+    pub fn contacts(&self) -> impl Iterator<Item = Contact> + '_ {
+        self.wheels.iter().filter_map(|wheel| wheel.contact)
+    }
+
+    /// Commands the gear up or down. The gear can't be retracted with weight on wheels.
+    pub fn toggle(&mut self) {
+        if !self.down || !self.weight_on_wheels() {
+            self.down = !self.down;
+        }
+    }
+
+    /// Retracts the gear at once.
+    pub fn stow(&mut self) {
+        self.down = false;
+        self.position = 0.;
+    }
+
+    /// Moves the gear towards the commanded position and applies the suspension, tire
+    /// friction and drag forces to the body.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        physics: &mut PhysicsSet,
+        body_handle: RigidBodyHandle,
+        controls: &ControlInput,
+        airvel: &Vector<f32>,
+        air_density: f32,
+    ) {
+        let target = if self.down { 1. } else { 0. };
+        let max_delta = delta_time / GEAR_TRANSIT_TIME;
+        self.position += (target - self.position).clamp(-max_delta, max_delta);
+
+        let body = &physics.rigid_body_set[body_handle];
+        let rotation = *body.rotation();
+        let filter = QueryFilter::default().exclude_rigid_body(body_handle);
+        let mut impulses = vec![];
+        for wheel in &mut self.wheels {
+            let attachment = body.position() * Point::from(wheel.pos);
+
+            // Drag of the extended gear, acting on the strut
+            let drag = -airvel * 0.5 * air_density * airvel.norm() * wheel.drag * self.position;
+            impulses.push((drag * delta_time, attachment));
+
+            wheel.compression = 0.;
+            wheel.contact = None;
+            // The wheels only carry a load when the gear is locked down.
+            if self.position < 1. {
+                continue;
+            }
+            let down = rotation * -Vector::y();
+            let ray = Ray::new(attachment, down);
+            let Some((_, hit)) = physics.query_pipeline.cast_ray_and_get_normal(
+                &physics.rigid_body_set,
+                &physics.collider_set,
+                &ray,
+                wheel.length,
+                true,
+                filter,
+            ) else {
+                continue;
+            };
+            wheel.compression = wheel.length - hit.toi;
+            let contact = ray.point_at(hit.toi);
+            let normal = hit.normal;
+            let velocity = body.velocity_at_point(&contact);
+
+            let load = (wheel.stiffness * wheel.compression
+                - wheel.damping * velocity.dot(&normal))
+            .max(0.);
+
+            // Tire frame on the ground, turned by the nose wheel steering. Positive rudder
+            // steers to the right like the fin, which is negative about y.
+            let steer = UnitQuaternion::from_axis_angle(
+                &Vector::y_axis(),
+                -wheel.steering * controls.rudder,
+            );
+            let heading = rotation * (steer * -Vector::z());
+            let Some(forward) = (heading - normal * normal.dot(&heading)).try_normalize(1e-6)
+            else {
+                continue;
+            };
+            let side = normal.cross(&forward);
+
+            // Brakes on the left wheels are commanded by the left brake, and vice versa.
+            let brake = if wheel.pos.x < 0. {
+                controls.brake_left
+            } else if 0. < wheel.pos.x {
+                controls.brake_right
+            } else {
+                (controls.brake_left + controls.brake_right) * 0.5
+            };
+            let friction =
+                |v: f32, coefficient: f32| -coefficient * load * (v / SLIP_VELOCITY).clamp(-1., 1.);
+            let rolling = friction(
+                velocity.dot(&forward),
+                ROLLING_RESISTANCE + wheel.brake * brake,
+            );
+            let cornering = friction(velocity.dot(&side), SIDE_FRICTION);
+
+            wheel.contact = Some(Contact {
+                kind: ContactKind::Gear,
+                point: contact,
+                normal,
+                force: load,
+            });
+            let force = normal * load + forward * rolling + side * cornering;
+            impulses.push((force * delta_time, contact));
+        }
+
+        let body = &mut physics.rigid_body_set[body_handle];
+        for (impulse, point) in impulses {
+            body.apply_impulse_at_point(impulse, point, true);
+        }
+    }
+}
+
+#[test]
+fn test_landing_gear() {
+    use crate::{
+        aircraft::{AircraftDef, DEFAULT_AIRCRAFT},
+        vehicle::Vehicle,
+        wind::WindField,
+    };
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let half_height = aircraft.collider_size.y * 0.5;
+
+    // Rolls on the runway at 20 m/s and returns the distance to stop and the yaw rate.
+    let roll = |controls: ControlInput| {
+        let mut physics = PhysicsSet::new(1000.);
+        let handles = physics.new_body(vector![0., 3.5, 0.], &aircraft);
+        let mut vehicle = Vehicle::new(handles, &aircraft);
+        vehicle.engine.stop();
+        vehicle.engine.n1 = 0.;
+        vehicle.controls = controls;
+        let delta_time = physics.integration_parameters.dt as f64;
+        let mut yaw_rate = 0f32;
+        let step = |physics: &mut PhysicsSet, vehicle: &mut Vehicle| {
+            vehicle.update(delta_time, physics, &controls, &WindField::default());
+            physics.step();
+        };
+
+        // Settles on the wheels without touching the belly
+        for _ in 0..300 {
+            step(&mut physics, &mut vehicle);
+        }
+        let body = &physics.rigid_body_set[vehicle.body_handle];
+        assert!(vehicle.landing_gear.weight_on_wheels());
+        assert!(0.1 < body.translation().y - half_height);
+        assert!(body.linvel().norm() < 0.1);
+
+        physics.rigid_body_set[vehicle.body_handle].set_linvel(vector![0., 0., -20.], true);
+        for _ in 0..3000 {
+            step(&mut physics, &mut vehicle);
+            let body = &physics.rigid_body_set[vehicle.body_handle];
+            if yaw_rate.abs() < body.angvel().y.abs() {
+                yaw_rate = body.angvel().y;
+            }
+            if body.linvel().norm() < 0.1 {
+                break;
+            }
+        }
+        let body = &physics.rigid_body_set[vehicle.body_handle];
+        (-body.translation().z, yaw_rate)
+    };
+
+    let (coasting, _) = roll(ControlInput::default());
+    let (braking, _) = roll(ControlInput {
+        brake_left: 1.,
+        brake_right: 1.,
+        ..ControlInput::default()
+    });
+    assert!(braking < coasting * 0.5, "{braking} {coasting}");
+
+    // Positive rudder steers the nose wheel to the right, as the fin yaws the nose in the
+    // air, and the left brake turns to the left.
+    let (_, yaw_rate) = roll(ControlInput {
+        rudder: 1.,
+        ..ControlInput::default()
+    });
+    assert!(yaw_rate < -0.05, "{yaw_rate}");
+    let (_, yaw_rate) = roll(ControlInput {
+        brake_left: 1.,
+        ..ControlInput::default()
+    });
+    assert!(0. < yaw_rate, "{yaw_rate}");
+}
@@ -1,33 +1,115 @@
+mod aircraft;
+mod airfoil;
+mod args;
+mod atmosphere;
+mod autopilot;
+mod bindings;
+mod camera_control;
+mod contact;
+mod control_input;
+mod damage;
+mod debug_overlay;
+mod engine;
+mod flight_plan;
+// The device backend is behind the feature, but the mappings are tested without it.
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+mod gamepad;
 mod grid;
 mod ground;
+mod headless;
+mod hud;
+mod landing_gear;
+mod mass;
 mod mqo;
 mod orbit_control_ex;
+mod panel;
 mod perlin_noise;
 mod physics;
+mod recorder;
 mod sphere;
+mod strokes;
+mod timestep;
+mod trim;
 mod ui;
 mod vehicle;
+mod wind;
 mod xor128;
 
 use std::{cell::RefCell, error::Error, rc::Rc};
 
-use crate::{orbit_control_ex::OrbitControlEx, physics::PhysicsSet};
+use crate::{
+    aircraft::AircraftDef,
+    args::Args,
+    autopilot::{LateralMode, VerticalMode},
+    bindings::{Action, Bindings, Profile},
+    camera_control::{CameraControl, CameraMode, CameraTarget},
+    control_input::{ControlSource, KeyboardControl},
+    damage::Impact,
+    debug_overlay::DebugOverlay,
+    flight_plan::{FlightPlan, FlightPlanMeshes},
+    headless::{run_headless, ControlScript, HeadlessParams},
+    hud::Hud,
+    panel::Panel,
+    physics::PhysicsSet,
+    recorder::{FlightRecorder, Replay},
+    timestep::FixedTimestep,
+    trim::start_trimmed,
+    wind::WindField,
+};
 use grid::grid_mesh;
 use ground::gen_ground;
-use mqo::load_mqo_scale;
 use three_d::*;
-use ui::Ui;
+use ui::{Layout, Ui};
 use vehicle::{Vehicle, VEHICLE_POSITION};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<(dyn std::error::Error + 'static)>> {
-    run().await?;
+async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let args = Args::parse()?;
+    let profile = Bindings::load(&args.bindings)?.profile(args.profile.as_deref())?;
+    if args.print_controls {
+        print!("{}", profile.controls_markdown());
+        return Ok(());
+    }
+    let aircraft = AircraftDef::load(&args.aircraft)?;
+    let mut wind = match &args.weather {
+        Some(path) => WindField::load(path)?,
+        None => WindField::default(),
+    };
+    if args.headless {
+        let mut script = match &args.script {
+            Some(path) => ControlScript::load(path)?,
+            None => ControlScript::default(),
+        };
+        let params = HeadlessParams {
+            duration: args.duration,
+            step_rate: args.step_rate,
+            trim: args.trim,
+            plan: args.plan.clone(),
+        };
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+        let trim = run_headless(&aircraft, &mut script, &mut wind, &params, &mut out)?;
+        if let (Some((speed, altitude)), Some(state)) = (args.trim, trim) {
+            println!("Trimmed at {speed} m/s and {altitude} m: {state}");
+        }
+    } else {
+        run(&args, aircraft, wind, profile).await?;
+    }
     Ok(())
 }
 
-pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
+/// Length of the wing force arrows per Newton
+const FORCE_ARROW_SCALE: f32 = 1. / 60.;
+/// Time the landing messages stay on the HUD [s]
+const MESSAGE_TIME: f32 = 5.;
+
+async fn run(
+    args: &Args,
+    aircraft: AircraftDef,
+    mut wind: WindField,
+    profile: Profile,
+) -> Result<(), Box<dyn Error>> {
     let window = Window::new(WindowSettings {
-        title: "Rusflight".to_string(),
+        title: format!("Rusflight - {}", aircraft.name),
         min_size: (512, 512),
         max_size: Some((1280, 720)),
         ..Default::default()
@@ -37,8 +119,17 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
 
     let ground_width = 1000. * 10.;
     let mut physics = PhysicsSet::new(ground_width);
+    let mut timestep = FixedTimestep::new(args.step_rate, args.max_substeps);
+    physics.integration_parameters.dt = timestep.delta_time() as f32;
 
-    let vehicle = Vehicle::new(physics.new_body(VEHICLE_POSITION));
+    let mut vehicle = Vehicle::new(physics.new_body(VEHICLE_POSITION, &aircraft), &aircraft);
+    if let Some((speed, altitude)) = args.trim {
+        let state = start_trimmed(&mut vehicle, &mut physics, speed, altitude)?;
+        println!("Trimmed at {speed} m/s and {altitude} m: {state}");
+    }
+    if let Some(path) = &args.plan {
+        vehicle.autopilot.flight_plan = Some(FlightPlan::load(path)?);
+    }
     let vehicle_pos = vehicle.pos(&physics.rigid_body_set);
     let vehicle = Rc::new(RefCell::new(vehicle));
     let vehicle2 = vehicle.clone();
@@ -53,18 +144,16 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
         0.1,
         10000.0,
     );
-    let mut control = OrbitControlEx::builder()
-        .target(vehicle_pos)
-        .min_distance(0.10)
-        .max_distance(1000.0)
-        .pan_speed(0.01)
-        .zoom_speed(0.01)
-        .build();
+    let pilot_eye = aircraft.pilot_eye;
+    let mut control = CameraControl::new(vehicle_pos, vec3(pilot_eye.x, pilot_eye.y, pilot_eye.z));
 
-    let mut ui = Ui::new(&window, &context);
+    let mut ui = Ui::new(&context, window.viewport(), Layout::load(&args.layout)?);
+    let mut hud = Hud::new(&context, window.viewport());
+    let mut panel = Panel::new(&context, window.viewport());
+    let mut debug_overlay = DebugOverlay::new(&context, window.viewport());
 
     let resources = [
-        "assets/F15.mqo",
+        aircraft.model.as_str(),
         "assets/skybox_evening/front.jpg",
         "assets/skybox_evening/back.jpg",
         "assets/skybox_evening/left.jpg",
@@ -83,8 +172,8 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
         &context, &right_tex, &left_tex, &top_tex, &top_tex, &front_tex, &back_tex,
     );
 
-    let model_src = loaded.get("F15.mqo")?;
-    let mut meshes = Vehicle::load_model(&model_src, &context)?;
+    let model_src = loaded.get(&aircraft.model)?;
+    let mut meshes = Vehicle::load_model(model_src, aircraft.model_scale, &context)?;
     let mut control_meshes = vehicle.borrow().control_meshes(&context);
 
     let grid = grid_mesh(50, 50, 10., 0.1);
@@ -105,38 +194,122 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
     );
 
     let ground_obj = gen_ground(&context)?;
+    let mut plan_meshes = FlightPlanMeshes::default();
 
     let light = AmbientLight::new(&context, 0.1, Srgba::WHITE);
     let mut dir_light =
         DirectionalLight::new(&context, 1., Srgba::WHITE, &Vec3::new(-1., -0.5, 1.));
 
-    let mut follow = true;
+    let mut recorder = match &args.record {
+        Some(path) => Some(FlightRecorder::new(
+            std::io::BufWriter::new(std::fs::File::create(path)?),
+            vehicle.borrow().wing_names(),
+        )?),
+        None => None,
+    };
+    let mut replay = match &args.replay {
+        Some(path) => Some(Replay::load(path, aircraft.wings.len())?),
+        None => None,
+    };
+    let mut sim_time = 0.;
+
+    let reset_label = profile.label(Action::Reset);
+    let mut keyboard = KeyboardControl::new(profile, &aircraft.actuators);
+    let mut gamepad = gamepad::open(args.gamepad.as_deref())?;
+    let mut show_panel = false;
+    let mut show_debug = false;
+    let mut arrow_scale = FORCE_ARROW_SCALE;
     let mut paused = false;
+    let mut impact = None;
+    // Message on the HUD and the time left to show it [s]
+    let mut message: Option<(String, f32)> = None;
 
     // main loop
     window.render_loop(move |mut frame_input| {
-        if !paused {
-            physics.step();
+        keyboard.handle_events(&frame_input.events);
+        let commands = keyboard.take_commands();
+        let frame_time = if paused {
+            0.
+        } else {
+            frame_input.elapsed_time * 1e-3
+        };
+        let alpha;
+        if let Some(replay) = &mut replay {
+            for action in &commands {
+                replay.handle_action(*action);
+            }
+            replay.advance(frame_time);
+            let (prev, next, replay_alpha) = replay.sample();
+            vehicle
+                .borrow_mut()
+                .apply_replay(&mut physics.rigid_body_set, prev, next);
+            alpha = replay_alpha;
+        } else {
+            for _ in 0..timestep.advance(frame_time) {
+                let delta_time = timestep.delta_time();
+                {
+                    let mut vehicle = vehicle.borrow_mut();
+                    let mut command = keyboard.command(delta_time, &vehicle.command);
+                    if let Some(gamepad) = &mut gamepad {
+                        command = gamepad.command(delta_time, &command);
+                    }
+                    keyboard.trim(delta_time, &mut vehicle.trim);
+                    vehicle.update(delta_time, &mut physics, &command, &wind);
+                }
+                physics.step();
+                wind.advance(delta_time);
+                sim_time += delta_time;
+                if let Some(recorder) = &mut recorder {
+                    let record = vehicle.borrow().record(&physics.rigid_body_set, sim_time);
+                    if let Err(e) = recorder.record(&record) {
+                        eprintln!("Failed to record the flight: {e}");
+                    }
+                }
+            }
+            if let Some(recorder) = &mut recorder {
+                if let Err(e) = recorder.flush() {
+                    eprintln!("Failed to record the flight: {e}");
+                }
+            }
+            alpha = timestep.alpha() as f32;
         }
 
         let transform;
+        let velocity;
         {
-            let mut vehicle = vehicle.borrow_mut();
-            vehicle.update(
-                if paused {
-                    0.
-                } else {
-                    frame_input.elapsed_time * 1e-3
-                },
-                &mut physics.rigid_body_set,
-                &frame_input,
-            );
-            ui.update_thrust(vehicle.thrust);
-            ui.update_aileron(vehicle.aileron);
-            ui.update_elevator(vehicle.elevator);
-            ui.update_rudder(vehicle.rudder);
-            ui.update_has_contact(vehicle.touching_ground);
-            transform = vehicle.transform(&physics.rigid_body_set);
+            let vehicle = vehicle.borrow();
+            for (name, value) in vehicle.telemetry() {
+                match value {
+                    Some(value) => ui.update(name, value),
+                    None => ui.clear(name),
+                }
+            }
+            if let Some(plan) = &vehicle.autopilot.flight_plan {
+                plan_meshes.update(&context, plan);
+            }
+            match &replay {
+                Some(replay) => ui.update("replay", replay.progress() as f32),
+                None => ui.clear("replay"),
+            }
+            if vehicle.impact != impact {
+                impact = vehicle.impact;
+                message = impact.map(|impact| match impact {
+                    Impact::Smooth => ("SMOOTH LANDING".to_string(), MESSAGE_TIME),
+                    Impact::Hard => ("HARD LANDING".to_string(), MESSAGE_TIME),
+                    // Until the reset
+                    Impact::Crash => (
+                        match &reset_label {
+                            Some(label) => {
+                                format!("CRASHED - PRESS {} TO RESET", label.to_uppercase())
+                            }
+                            None => "CRASHED".to_string(),
+                        },
+                        f32::INFINITY,
+                    ),
+                });
+            }
+            transform = vehicle.transform(&physics.rigid_body_set, alpha);
+            velocity = *physics.rigid_body_set[vehicle.body_handle].linvel();
         }
 
         fn unrotate(transform: &Mat4) -> Mat4 {
@@ -155,13 +328,20 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
         for mesh in &mut meshes {
             mesh.set_transformation(transform * Mat4::from_angle_y(Deg(180.)));
         }
-        for (meshes, force) in control_meshes
+        let wing_healths: Vec<_> = vehicle.borrow().wing_healths().collect();
+        for (((meshes, force), health), deflection) in control_meshes
             .iter_mut()
             .zip(vehicle.borrow().wing_forces())
+            .zip(&wing_healths)
+            .zip(vehicle.borrow().wing_deflections())
         {
-            meshes
-                .surface
-                .set_transformation(transform * meshes.transform);
+            // Damaged wings turn from green to red.
+            meshes.surface.material.color =
+                Srgba::new(((1. - health) * 255.) as u8, (health * 255.) as u8, 0, 200);
+            let force = force * arrow_scale;
+            // The surfaces deflect with the controls.
+            let surface_transform = transform * meshes.deflected(&deflection);
+            meshes.surface.set_transformation(surface_transform);
             meshes.arrow.set_transformation(
                 transform
                     * meshes.transform
@@ -184,27 +364,63 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
             height: frame_input.viewport.height,
         };
         camera.set_viewport(viewport);
-        for e in &frame_input.events {
-            if let Event::KeyPress { kind, .. } = e {
-                if *kind == Key::F {
-                    follow = !follow;
-                } else if *kind == Key::R {
-                    vehicle.borrow_mut().reset(&mut physics.rigid_body_set);
-                } else if *kind == Key::P {
-                    paused = !paused;
+        for action in commands {
+            match action {
+                Action::OrbitCamera => control.set_mode(CameraMode::Orbit),
+                Action::CockpitCamera => control.set_mode(CameraMode::Cockpit),
+                Action::ChaseCamera => control.set_mode(CameraMode::Chase),
+                Action::TowerCamera => control.set_mode(CameraMode::Tower),
+                Action::FlyByCamera => control.set_mode(CameraMode::FlyBy),
+                Action::FreeCamera => control.set_mode(CameraMode::Free),
+                Action::Panel => {
+                    show_panel = !show_panel;
+                    ui.set_panel_shown(show_panel);
                 }
+                Action::Debug => show_debug = !show_debug,
+                Action::ShorterArrows => arrow_scale /= 2.,
+                Action::LongerArrows => arrow_scale *= 2.,
+                Action::Reset => vehicle.borrow_mut().reset(&mut physics.rigid_body_set),
+                Action::Pause => paused = !paused,
+                _ if replay.is_some() => {}
+                Action::Engine => vehicle.borrow_mut().engine.toggle(),
+                Action::Gear => vehicle.borrow_mut().landing_gear.toggle(),
+                _ => handle_autopilot_action(&mut vehicle.borrow_mut(), action, &physics, &wind),
             }
         }
-        if follow {
-            let new_target = vehicle.borrow().pos(&physics.rigid_body_set);
-            let target = control.target();
-            control.set_target(new_target);
-            let cpos = *camera.position();
-            let delta = cpos - target;
-            let up = *camera.up();
-            camera.set_view(new_target + delta, new_target, up);
+        {
+            let vehicle = vehicle.borrow();
+            let state = vehicle.flight_state(&physics.rigid_body_set, &wind);
+            if let Some((_, time)) = &mut message {
+                *time -= frame_input.elapsed_time as f32 * 1e-3;
+            }
+            if message.as_ref().is_some_and(|(_, time)| *time <= 0.) {
+                message = None;
+            }
+            hud.update(
+                viewport,
+                &state,
+                vehicle.load_factor,
+                message.as_ref().map(|(text, _)| text.as_str()),
+            );
+            if show_panel {
+                panel.update(viewport, &state);
+            }
+            if show_debug {
+                let debug = vehicle.aero_debug(&physics.rigid_body_set, &wind);
+                debug_overlay.update(viewport, &debug, &transform, arrow_scale);
+            }
         }
-        control.handle_events(&mut camera, &mut frame_input.events);
+        ui.redraw(viewport);
+        let target = CameraTarget {
+            transform,
+            velocity: vec3(velocity.x, velocity.y, velocity.z),
+        };
+        control.update(
+            &mut camera,
+            &target,
+            frame_time as f32,
+            &mut frame_input.events,
+        );
 
         let render_target = frame_input.screen();
 
@@ -212,21 +428,120 @@ pub async fn run<'src>() -> Result<(), Box<dyn Error>> {
 
         let c_objs = control_meshes
             .iter()
-            .map(|c| [&c.surface, &c.arrow].into_iter())
-            .flatten();
+            .zip(&wing_healths)
+            .filter(|(_, health)| 0. < **health)
+            .flat_map(|(c, _)| [&c.surface, &c.arrow].into_iter());
 
         render_target
             .clear(ClearState::default())
-            .render(&camera, &[&skybox], &[])
+            .render(&camera, [&skybox], &[])
             .render(&camera, &meshes, &[&light, &dir_light])
-            .render(&camera, &[&grid_obj], &[])
-            .render(&camera, &[&ground_obj], &[&light, &dir_light])
+            .render(&camera, [&grid_obj], &[])
+            .render(&camera, plan_meshes.objects(), &[])
+            .render(&camera, [&ground_obj], &[&light, &dir_light])
             .render(&camera, c_objs, &[]);
 
+        if show_debug {
+            debug_overlay.render_arrows(&render_target, &camera);
+        }
+
+        if show_panel {
+            panel.render(&render_target);
+        }
         ui.render(&render_target);
+        hud.render(&render_target);
+        if show_debug {
+            debug_overlay.render_table(&render_target);
+        }
 
         FrameOutput::default()
     });
 
     Ok(())
 }
+
+/// Engages or disengages the autopilot modes at the current flight state. The autopilot
+/// action toggles the heading, altitude and airspeed hold together, and the others each mode.
+fn handle_autopilot_action(
+    vehicle: &mut Vehicle,
+    action: Action,
+    physics: &PhysicsSet,
+    wind: &WindField,
+) {
+    let state = vehicle.flight_state(&physics.rigid_body_set, wind);
+    let controls = vehicle.controls;
+    let autopilot = &mut vehicle.autopilot;
+    match action {
+        Action::Autopilot => {
+            if autopilot.engaged() {
+                autopilot.disengage();
+            } else {
+                autopilot.engage_lateral(LateralMode::Heading(state.heading), &controls);
+                autopilot.engage_vertical(
+                    VerticalMode::Altitude(state.altitude),
+                    &controls,
+                    &state,
+                );
+                autopilot.engage_autothrottle(state.airspeed, &controls);
+            }
+        }
+        Action::WingsLeveler => {
+            if autopilot.lateral == Some(LateralMode::WingsLeveler) {
+                autopilot.lateral = None;
+            } else {
+                autopilot.engage_lateral(LateralMode::WingsLeveler, &controls);
+            }
+        }
+        Action::HeadingHold => {
+            if matches!(autopilot.lateral, Some(LateralMode::Heading(_))) {
+                autopilot.lateral = None;
+            } else {
+                autopilot.engage_lateral(LateralMode::Heading(state.heading), &controls);
+            }
+        }
+        Action::AltitudeHold => {
+            if matches!(autopilot.vertical, Some(VerticalMode::Altitude(_))) {
+                autopilot.vertical = None;
+            } else {
+                autopilot.engage_vertical(
+                    VerticalMode::Altitude(state.altitude),
+                    &controls,
+                    &state,
+                );
+            }
+        }
+        Action::VerticalSpeedHold => {
+            if matches!(autopilot.vertical, Some(VerticalMode::VerticalSpeed(_))) {
+                autopilot.vertical = None;
+            } else {
+                autopilot.engage_vertical(
+                    VerticalMode::VerticalSpeed(state.vertical_speed),
+                    &controls,
+                    &state,
+                );
+            }
+        }
+        Action::Autothrottle => {
+            if autopilot.autothrottle.is_some() {
+                autopilot.autothrottle = None;
+            } else {
+                autopilot.engage_autothrottle(state.airspeed, &controls);
+            }
+        }
+        Action::LateralNav => {
+            if autopilot.lateral == Some(LateralMode::Nav) {
+                autopilot.lateral = None;
+            } else if autopilot.flight_plan.is_some() {
+                autopilot.engage_lateral(LateralMode::Nav, &controls);
+            }
+        }
+        Action::VerticalNav => {
+            if autopilot.vertical == Some(VerticalMode::Nav) {
+                autopilot.vertical = None;
+            } else if autopilot.flight_plan.is_some() {
+                autopilot.engage_vertical(VerticalMode::Nav, &controls, &state);
+            }
+        }
+        _ => (),
+    }
+}
@@ -0,0 +1,134 @@
+//! Payload and fuel stations, whose point masses move the center of gravity as the fuel
+//! burns or the payload changes.
+
+use rapier3d::prelude::*;
+
+use crate::aircraft::AircraftDef;
+
+#[derive(Clone)]
+pub(crate) struct Station {
+    #[allow(dead_code)]
+    /// Name of the station, just for debugging
+    name: String,
+    /// Position in the body frame
+    pos: Vector<f32>,
+    /// Payload mass [kg], which can be changed at runtime
+    pub mass: f32,
+    /// Fuel capacity of the station's tank [kg]
+    capacity: f32,
+    /// Fuel in the station's tank [kg]
+    pub fuel: f32,
+}
+
+/// Distribution of the fuel and payload in the airframe
+#[derive(Clone)]
+pub(crate) struct Loading {
+    /// Position of the internal tanks in the body frame, the center of gravity of the
+    /// empty airframe
+    internal_pos: Vector<f32>,
+    /// Fuel capacity of the internal tanks [kg]
+    internal_capacity: f32,
+    /// Fuel in the internal tanks [kg]
+    internal_fuel: f32,
+    pub stations: Vec<Station>,
+}
+
+impl Loading {
+    /// Creates the loading of the aircraft with full tanks.
+    pub fn new(aircraft: &AircraftDef) -> Self {
+        Self {
+            internal_pos: aircraft.center_of_mass,
+            internal_capacity: aircraft.engine.fuel,
+            internal_fuel: aircraft.engine.fuel,
+            stations: aircraft
+                .stations
+                .iter()
+                .map(|station| Station {
+                    name: station.name.clone(),
+                    pos: station.pos,
+                    mass: station.mass,
+                    capacity: station.fuel,
+                    fuel: station.fuel,
+                })
+                .collect(),
+        }
+    }
+
+    /// Total fuel in the internal tanks and the stations [kg]
+    pub fn fuel(&self) -> f32 {
+        self.internal_fuel + self.stations.iter().map(|s| s.fuel).sum::<f32>()
+    }
+
+    /// Distributes the remaining `fuel` [kg] among the tanks. The stations are drawn before
+    /// the internal tanks, the last station first.
+    pub fn set_fuel(&mut self, fuel: f32) {
+        self.internal_fuel = fuel.clamp(0., self.internal_capacity);
+        let mut rest = fuel - self.internal_fuel;
+        for station in &mut self.stations {
+            station.fuel = rest.clamp(0., station.capacity);
+            rest -= station.fuel;
+        }
+    }
+
+    /// Mass properties of the fuel and payload as point masses in the body frame
+    pub fn mass_properties(&self) -> MassProperties {
+        let point_mass = |pos: &Vector<f32>, mass: f32| {
+            if 0. < mass {
+                MassProperties::new(Point::from(*pos), mass, Vector::zeros())
+            } else {
+                MassProperties::default()
+            }
+        };
+        self.stations
+            .iter()
+            .map(|station| point_mass(&station.pos, station.mass + station.fuel))
+            .fold(
+                point_mass(&self.internal_pos, self.internal_fuel),
+                |acc, props| acc + props,
+            )
+    }
+}
+
+#[test]
+fn test_loading() {
+    use crate::aircraft::{StationDef, DEFAULT_AIRCRAFT};
+
+    let mut aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    aircraft.engine.fuel = 100.;
+    aircraft.stations = vec![
+        StationDef {
+            name: "Nose".to_string(),
+            pos: vector![0., 0., -5.],
+            mass: 100.,
+            fuel: 0.,
+        },
+        StationDef {
+            name: "Tail tank".to_string(),
+            pos: vector![0., 0., 5.],
+            mass: 0.,
+            fuel: 100.,
+        },
+    ];
+    let mut loading = Loading::new(&aircraft);
+    assert_eq!(loading.fuel(), 200.);
+    let props = loading.mass_properties();
+    assert!((props.mass() - 300.).abs() < 1e-3);
+    assert!((props.local_com.z - 0.).abs() < 1e-3);
+
+    // Burning the fuel in the tail tank moves the center of gravity forward.
+    loading.set_fuel(150.);
+    assert_eq!(loading.stations[1].fuel, 50.);
+    let props = loading.mass_properties();
+    assert!((props.local_com.z - (-500. + 250.) / 250.).abs() < 1e-3);
+    loading.set_fuel(50.);
+    assert_eq!(loading.stations[1].fuel, 0.);
+    let props = loading.mass_properties();
+    assert!((props.local_com.z - -500. / 150.).abs() < 1e-3);
+    // A point mass off the center has the moment of inertia of its offset.
+    assert!(0. < props.principal_inertia().x);
+
+    // Without payload and fuel, there are no additional mass properties.
+    loading.stations[0].mass = 0.;
+    loading.set_fuel(0.);
+    assert_eq!(loading.mass_properties().mass(), 0.);
+}
@@ -85,7 +85,7 @@ pub fn load_mqo_scale(
 
 #[test]
 fn test_mqo() {
-    let mut mqo_reader = std::io::BufReader::new(std::fs::File::open("models/A10.mqo").unwrap());
+    let mut mqo_reader = std::io::BufReader::new(std::fs::File::open("assets/F15.mqo").unwrap());
     let meshes = load_mqo(&mut mqo_reader, None).unwrap();
     println!("meshes: {}", meshes.len());
 }
@@ -173,9 +173,9 @@ fn chunk_object(
                     }
                     let mut cur: &[_] = &line;
                     let mut vec = [0f32; 3];
-                    for j in 0..3 {
-                        let (next_cur, s) = quotok(&cur)?;
-                        vec[j] = std::str::from_utf8(&s)?.parse::<f32>()? * scale;
+                    for v in &mut vec {
+                        let (next_cur, s) = quotok(cur)?;
+                        *v = std::str::from_utf8(&s)?.parse::<f32>()? * scale;
                         cur = next_cur;
                     }
                     positions.push(vec.into());
@@ -207,21 +207,21 @@ fn chunk_object(
                     }
                     let mut cur = &r[2..];
                     let mut vertices = vec![0u16; dims];
-                    for j in 0..dims {
-                        let (next_cur, s) = quotok(&cur)?;
-                        vertices[j] = parse_u8(&s)?;
+                    for v in &mut vertices {
+                        let (next_cur, s) = quotok(cur)?;
+                        *v = parse_u8(&s)?;
                         cur = next_cur;
                     }
                     vertices.reverse();
                     for seq in vertices.windows(3) {
-                        faces.extend_from_slice(&seq);
+                        faces.extend_from_slice(seq);
                     }
                     cur = skip_whitespace(cur);
                     if &r[..2] != b"M(" {
                         continue;
                     }
                     for _j in 0..dims {
-                        let (next_cur, s) = quotok(&cur)?;
+                        let (next_cur, s) = quotok(cur)?;
                         materials.push(parse_u8(&s)?);
                         cur = next_cur;
                     }
@@ -233,19 +233,19 @@ fn chunk_object(
                 // dbg!(&faces[..30]);
             }
             b"shading" => {
-                let (_, s) = quotok(&r)?;
+                let (_, s) = quotok(r)?;
                 _shading = parse_u8(&s)?;
             }
             b"facet" => {
-                let (_, s) = quotok(&r)?;
+                let (_, s) = quotok(r)?;
                 _facet = parse_u8(&s)?;
             }
             b"depth" => {}
             b"mirror" => {
-                mirror = parse_u8::<i32>(&quotok(&r)?.1)? != 0;
+                mirror = parse_u8::<i32>(&quotok(r)?.1)? != 0;
             }
             b"mirror_axis" => {
-                mirror_axis = parse_u8(&quotok(&r)?.1)?;
+                mirror_axis = parse_u8(&quotok(r)?.1)?;
             }
             b"}" => break,
             _ => {
@@ -260,12 +260,12 @@ fn chunk_object(
     }
 
     if mirror {
-        for m in 0..3 {
+        for (m, nv) in mirrornv.iter_mut().enumerate() {
             // Check for each axis if it's flagged for mirroring.
             if (mirror_axis & (1 << m)) == 0 {
                 continue;
             }
-            mirrornv[m] = positions.len() as u16;
+            *nv = positions.len() as u16;
             writeln!(logger, "Object {name}: Mirroring axis {m}")?;
             // Mirrored vertices have simply negated coordinate along axis perpendicular to the mirror.
             for i in 0..positions.len() {
@@ -281,7 +281,7 @@ fn chunk_object(
             }
         }
 
-        for m in 0..3 {
+        for (m, &nv) in mirrornv.iter().enumerate() {
             if (mirror_axis & (1 << m)) == 0 {
                 continue;
             }
@@ -290,7 +290,7 @@ fn chunk_object(
                 let mut dest = [0u16; 3];
                 for (j, d) in dest.iter_mut().enumerate() {
                     // Flip face direction because it's mirrored.
-                    *d = faces[i * 3 + 2 - j] + mirrornv[m];
+                    *d = faces[i * 3 + 2 - j] + nv;
                 }
                 faces.extend_from_slice(&dest);
             }
@@ -359,7 +359,9 @@ fn read_line(r: &mut impl Read) -> Result<Vec<u8>, Box<dyn Error>> {
     Ok(line_buf)
 }
 
+#[allow(clippy::type_complexity)]
 fn quotok(src: &[u8]) -> Result<(&[u8], Vec<u8>), Box<dyn Error>> {
+    #[allow(clippy::enum_variant_names)]
     #[derive(Clone, Copy, PartialEq, Eq, Debug)]
     enum Quote {
         None,
@@ -0,0 +1,491 @@
+//! Cockpit instrument panel of the six classic flight instruments, in the T arrangement:
+//! the airspeed indicator, the attitude indicator and the altimeter on the top row, and the
+//! turn coordinator, the heading indicator and the vertical speed indicator below.
+//!
+//! The panel is laid out in panel units, where the viewport is `PANEL_HEIGHT` units high
+//! with the origin at its center, and sits at the bottom center of the viewport. The dials
+//! are drawn as strokes and the attitude indicator as a textured ball turning with the
+//! attitude behind them.
+
+use three_d::{
+    Camera, ClearState, ColorMaterial, Context, CpuMaterial, CpuTexture, Gm, Mesh, RenderTarget,
+    TextureData, Viewport,
+};
+use three_d_asset::{vec2, vec3, Deg, Mat4, Srgba, Vec2};
+
+use crate::{
+    autopilot::FlightState,
+    sphere::uv_sphere,
+    strokes::{Align, Strokes},
+};
+
+/// Height of the viewport in panel units
+const PANEL_HEIGHT: f32 = 100.;
+const LINE_WIDTH: f32 = 0.25;
+const TEXT_SIZE: f32 = 1.6;
+const RADIUS: f32 = 9.;
+/// Distance between the centers of neighboring instruments
+const SPACING: f32 = 20.;
+/// Height of the centers of the bottom and top rows above the bottom of the viewport
+const ROWS: [f32; 2] = [11., 31.];
+
+const PANEL_COLOR: Srgba = Srgba::new(40, 40, 40, 255);
+const FACE_COLOR: Srgba = Srgba::new(0, 0, 0, 255);
+const MARKING_COLOR: Srgba = Srgba::WHITE;
+const SYMBOL_COLOR: Srgba = Srgba::new(255, 160, 0, 255);
+const SKY_COLOR: [u8; 4] = [40, 120, 210, 255];
+const GROUND_COLOR: [u8; 4] = [130, 80, 30, 255];
+const LINE_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+/// Angle of the airspeed needle at 0 and at the end of the scale, clockwise from the top
+const AIRSPEED_SWEEP: f32 = 150.;
+/// Airspeed at the end of the scale [m/s]
+const AIRSPEED_MAX: f32 = 400.;
+/// Altitude per turn of the long and the short hand of the altimeter [m]
+const ALTIMETER_TURNS: [f32; 2] = [1000., 10000.];
+/// Vertical speed at either end of the scale [m/s]
+const VERTICAL_SPEED_MAX: f32 = 50.;
+/// Angle of the vertical speed needle from 0 to either end of the scale [deg]
+const VERTICAL_SPEED_SWEEP: f32 = 170.;
+/// Bank of the turn coordinator's airplane at the standard rate turn [deg]
+const STANDARD_RATE_BANK: f32 = 20.;
+/// Standard rate of turn, a full turn in 2 minutes [deg/s]
+const STANDARD_RATE: f32 = 3.;
+/// Sideslip that moves the slip ball to the end of its tube [deg]
+const SLIP_RANGE: f32 = 10.;
+/// Pitch between the lines on the attitude ball [deg]
+const BALL_LINE_STEP: f32 = 10.;
+const BALL_TEXTURE_SIZE: [u32; 2] = [64, 512];
+
+/// Centers of the instruments, the top row from the left and then the bottom row
+fn centers() -> [Vec2; 6] {
+    let [bottom, top] = ROWS.map(|row| row - PANEL_HEIGHT / 2.);
+    [
+        vec2(-SPACING, top),
+        vec2(0., top),
+        vec2(SPACING, top),
+        vec2(-SPACING, bottom),
+        vec2(0., bottom),
+        vec2(SPACING, bottom),
+    ]
+}
+
+/// Point at the angle clockwise from the top and the distance from the center
+fn at(center: Vec2, angle: f32, distance: f32) -> Vec2 {
+    let angle = angle.to_radians();
+    center + vec2(angle.sin(), angle.cos()) * distance
+}
+
+fn airspeed_angle(airspeed: f32) -> f32 {
+    -AIRSPEED_SWEEP + 2. * AIRSPEED_SWEEP * (airspeed / AIRSPEED_MAX).clamp(0., 1.)
+}
+
+/// Angles of the long and the short hand of the altimeter
+fn altimeter_angles(altitude: f32) -> [f32; 2] {
+    ALTIMETER_TURNS.map(|turn| (altitude / turn).rem_euclid(1.) * 360.)
+}
+
+fn vertical_speed_angle(vertical_speed: f32) -> f32 {
+    -90. + VERTICAL_SPEED_SWEEP * (vertical_speed / VERTICAL_SPEED_MAX).clamp(-1., 1.)
+}
+
+/// Bank of the turn coordinator's airplane, right wing down
+fn turn_angle(turn_rate: f32) -> f32 {
+    (turn_rate / STANDARD_RATE * STANDARD_RATE_BANK).clamp(-45., 45.)
+}
+
+/// Color of the attitude ball at the pitch angle: sky above the horizon and ground below,
+/// with a line at the horizon and every `BALL_LINE_STEP` degrees
+fn ball_color(pitch: f32) -> [u8; 4] {
+    let line = (pitch / BALL_LINE_STEP).round() * BALL_LINE_STEP;
+    if (pitch - line).abs() < 0.4 && line.abs() < 90. {
+        LINE_COLOR
+    } else if 0. < pitch {
+        SKY_COLOR
+    } else {
+        GROUND_COLOR
+    }
+}
+
+/// Texture of the attitude ball for the latitude mapping of `uv_sphere`, whose v runs from
+/// the sky pole at 0 to the ground pole at 1. The rows of the image run from the top at
+/// v = 1.
+fn ball_texture() -> CpuTexture {
+    let [width, height] = BALL_TEXTURE_SIZE;
+    let data = (0..height)
+        .flat_map(|row| {
+            let v = 1. - (row as f32 + 0.5) / height as f32;
+            let color = ball_color(90. - 180. * v);
+            (0..width).map(move |_| color)
+        })
+        .collect();
+    CpuTexture {
+        data: TextureData::RgbaU8(data),
+        width,
+        height,
+        ..Default::default()
+    }
+}
+
+/// Transformation of the attitude ball, a unit sphere with the sky pole at +z, at the
+/// attitude indicator. It shows the world as seen forward from the aircraft, so the body's
+/// forward axis is mirrored to face the viewer.
+fn ball_transformation(state: &FlightState) -> Mat4 {
+    let center = centers()[1];
+    // Behind the dial markings at z = 0
+    Mat4::from_translation(vec3(center.x, center.y, -RADIUS - 1.))
+        * Mat4::from_nonuniform_scale(RADIUS, RADIUS, -RADIUS)
+        // The inverse of the attitude turns the world into the body frame.
+        * Mat4::from_angle_z(Deg(state.roll))
+        * Mat4::from_angle_x(Deg(-state.pitch))
+        * Mat4::from_angle_y(Deg(state.heading))
+        * Mat4::from_angle_x(Deg(-90.))
+}
+
+/// Draws the face of an instrument with the scale ticks at the angles, long ones where
+/// `long` is true.
+fn face(strokes: &mut Strokes, center: Vec2, ticks: impl IntoIterator<Item = (f32, bool)>) {
+    strokes.color = FACE_COLOR;
+    strokes.fill_circle(center, RADIUS, 32);
+    strokes.color = MARKING_COLOR;
+    strokes.circle(center, RADIUS, 32);
+    for (angle, long) in ticks {
+        let inner = if long { 0.78 } else { 0.88 };
+        strokes.line(at(center, angle, RADIUS * inner), at(center, angle, RADIUS));
+    }
+}
+
+/// Draws the text centered at the angle and the distance from the center.
+fn label(strokes: &mut Strokes, text: &str, center: Vec2, angle: f32, distance: f32) {
+    let pos = at(center, angle, distance) - vec2(0., TEXT_SIZE / 2.);
+    strokes.text(text, pos, TEXT_SIZE, Align::Center);
+}
+
+fn needle(strokes: &mut Strokes, center: Vec2, angle: f32, length: f32) {
+    strokes.color = MARKING_COLOR;
+    strokes.line(
+        at(center, angle + 180., RADIUS * 0.15),
+        at(center, angle, length),
+    );
+}
+
+/// Draws the plate behind the instruments.
+fn draw_plate() -> Strokes {
+    let mut strokes = Strokes::new(PANEL_COLOR);
+    // From the bottom of the viewport to a margin above the top row
+    let height = ROWS[1] + RADIUS + 2.;
+    strokes.fill_rect(
+        vec2(0., (height - PANEL_HEIGHT) / 2.),
+        vec2(SPACING + RADIUS + 2., height / 2.),
+    );
+    strokes
+}
+
+/// Draws the instrument faces, the markings and the needles for the flight state.
+fn draw(state: &FlightState) -> Strokes {
+    let mut strokes = Strokes::default();
+    let [airspeed, attitude, altimeter, turn, heading, vertical_speed] = centers();
+
+    face(
+        &mut strokes,
+        airspeed,
+        (0..=20).map(|i| (airspeed_angle(i as f32 * 20.), i % 5 == 0)),
+    );
+    for speed in [0, 100, 200, 300, 400] {
+        let angle = airspeed_angle(speed as f32);
+        label(
+            &mut strokes,
+            &speed.to_string(),
+            airspeed,
+            angle,
+            RADIUS * 0.55,
+        );
+    }
+    label(&mut strokes, "M/S", airspeed, 180., RADIUS * 0.25);
+    needle(
+        &mut strokes,
+        airspeed,
+        airspeed_angle(state.airspeed),
+        RADIUS * 0.85,
+    );
+
+    // The ball is drawn behind, so only the rim is drawn here.
+    strokes.color = MARKING_COLOR;
+    strokes.circle(attitude, RADIUS, 32);
+    for bank in [-60., -45., -30., -20., -10., 0., 10., 20., 30., 45., 60.] {
+        let inner = if bank % 30. == 0. { 0.82 } else { 0.9 };
+        strokes.line(
+            at(attitude, bank, RADIUS * inner),
+            at(attitude, bank, RADIUS),
+        );
+    }
+    // The bank pointer turns with the horizon.
+    strokes.polyline([
+        at(attitude, -state.roll, RADIUS * 0.8),
+        at(attitude, -state.roll - 5., RADIUS * 0.68),
+        at(attitude, -state.roll + 5., RADIUS * 0.68),
+        at(attitude, -state.roll, RADIUS * 0.8),
+    ]);
+    strokes.color = SYMBOL_COLOR;
+    strokes.polyline([
+        attitude + vec2(-RADIUS * 0.6, 0.),
+        attitude + vec2(-RADIUS * 0.2, 0.),
+        attitude + vec2(-RADIUS * 0.1, -RADIUS * 0.1),
+        attitude,
+        attitude + vec2(RADIUS * 0.1, -RADIUS * 0.1),
+        attitude + vec2(RADIUS * 0.2, 0.),
+        attitude + vec2(RADIUS * 0.6, 0.),
+    ]);
+
+    face(
+        &mut strokes,
+        altimeter,
+        (0..50).map(|i| (i as f32 * 7.2, i % 5 == 0)),
+    );
+    for digit in 0..10 {
+        label(
+            &mut strokes,
+            &digit.to_string(),
+            altimeter,
+            digit as f32 * 36.,
+            RADIUS * 0.6,
+        );
+    }
+    label(&mut strokes, "X100M", altimeter, 180., RADIUS * 0.3);
+    let [long, short] = altimeter_angles(state.altitude);
+    needle(&mut strokes, altimeter, short, RADIUS * 0.5);
+    needle(&mut strokes, altimeter, long, RADIUS * 0.85);
+
+    face(
+        &mut strokes,
+        turn,
+        [-1., 1.].into_iter().flat_map(|side| {
+            [
+                (side * 90., true),
+                (side * (90. + STANDARD_RATE_BANK), true),
+            ]
+        }),
+    );
+    label(&mut strokes, "L", turn, -135., RADIUS * 0.6);
+    label(&mut strokes, "R", turn, 135., RADIUS * 0.6);
+    let bank = turn_angle(state.turn_rate);
+    strokes.color = MARKING_COLOR;
+    strokes.line(
+        at(turn, bank - 90., RADIUS * 0.7),
+        at(turn, bank + 90., RADIUS * 0.7),
+    );
+    strokes.line(turn, at(turn, bank, RADIUS * 0.2));
+    strokes.circle(turn, RADIUS * 0.1, 8);
+    // The slip ball is in the tube below the airplane.
+    let tube = turn - vec2(0., RADIUS * 0.45);
+    strokes.rect(tube, vec2(RADIUS * 0.45, RADIUS * 0.1));
+    let slip = (state.sideslip / SLIP_RANGE).clamp(-1., 1.);
+    strokes.fill_circle(tube + vec2(slip * RADIUS * 0.35, 0.), RADIUS * 0.08, 8);
+
+    // The compass card turns with the heading.
+    face(
+        &mut strokes,
+        heading,
+        (0..72).map(|i| (i as f32 * 5. - state.heading, i % 2 == 0)),
+    );
+    for (i, text) in [
+        "N", "3", "6", "E", "12", "15", "S", "21", "24", "W", "30", "33",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let angle = i as f32 * 30. - state.heading;
+        label(&mut strokes, text, heading, angle, RADIUS * 0.6);
+    }
+    strokes.color = SYMBOL_COLOR;
+    strokes.line(at(heading, 0., RADIUS * 0.7), at(heading, 0., RADIUS));
+    strokes.line(
+        heading + vec2(-RADIUS * 0.2, 0.),
+        heading + vec2(RADIUS * 0.2, 0.),
+    );
+    strokes.line(
+        heading + vec2(0., RADIUS * 0.25),
+        heading + vec2(0., -RADIUS * 0.2),
+    );
+
+    face(
+        &mut strokes,
+        vertical_speed,
+        (-10..=10).map(|i| (vertical_speed_angle(i as f32 * 5.), i % 2 == 0)),
+    );
+    for speed in [0, 10, 20, 30, 40] {
+        for sign in [-1., 1.] {
+            let angle = vertical_speed_angle(sign * speed as f32);
+            label(
+                &mut strokes,
+                &speed.to_string(),
+                vertical_speed,
+                angle,
+                RADIUS * 0.58,
+            );
+        }
+    }
+    label(&mut strokes, "VS", vertical_speed, 90., RADIUS * 0.3);
+    needle(
+        &mut strokes,
+        vertical_speed,
+        vertical_speed_angle(state.vertical_speed),
+        RADIUS * 0.85,
+    );
+
+    strokes
+}
+
+pub(crate) struct Panel {
+    context: Context,
+    camera: Camera,
+    plate: Gm<Mesh, ColorMaterial>,
+    ball: Gm<Mesh, ColorMaterial>,
+    object: Option<Gm<Mesh, ColorMaterial>>,
+}
+
+impl Panel {
+    pub fn new(context: &Context, viewport: Viewport) -> Self {
+        let camera = Camera::new_orthographic(
+            viewport,
+            vec3(0.0, 0.0, 100.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            PANEL_HEIGHT,
+            0.1,
+            1000.0,
+        );
+        let plate = draw_plate()
+            .to_object(context, LINE_WIDTH)
+            .expect("the plate is not empty");
+        let ball = Gm::new(
+            Mesh::new(context, &uv_sphere(24)),
+            ColorMaterial::new_opaque(
+                context,
+                &CpuMaterial {
+                    albedo: Srgba::WHITE,
+                    albedo_texture: Some(ball_texture()),
+                    ..Default::default()
+                },
+            ),
+        );
+        Self {
+            context: context.clone(),
+            camera,
+            plate,
+            ball,
+            object: None,
+        }
+    }
+
+    /// Turns the instruments to the flight state.
+    pub fn update(&mut self, viewport: Viewport, state: &FlightState) {
+        self.camera.set_viewport(viewport);
+        self.ball.set_transformation(ball_transformation(state));
+        self.object = draw(state).to_object(&self.context, LINE_WIDTH);
+    }
+
+    pub fn render(&self, render: &RenderTarget) {
+        // The ball is depth tested against itself only, not the scene behind the panel.
+        render
+            .clear(ClearState::depth(1.0))
+            .render(&self.camera, [&self.plate], &[])
+            .render(&self.camera, [&self.ball], &[]);
+        if let Some(object) = &self.object {
+            render.render(&self.camera, [object], &[]);
+        }
+    }
+}
+
+#[test]
+fn test_panel() {
+    use three_d_asset::{InnerSpace, Vec4};
+
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+    assert!(close(airspeed_angle(0.), -150.));
+    assert!(close(airspeed_angle(200.), 0.));
+    assert!(close(airspeed_angle(1000.), 150.));
+    assert_eq!(altimeter_angles(1250.), [90., 45.]);
+    let [long, short] = altimeter_angles(-250.);
+    assert!(close(long, 270.) && close(short, 351.));
+    assert!(close(vertical_speed_angle(0.), -90.));
+    assert!(close(vertical_speed_angle(25.), -5.));
+    assert!(close(vertical_speed_angle(-100.), -260.));
+    assert!(close(turn_angle(STANDARD_RATE), STANDARD_RATE_BANK));
+    assert!(close(turn_angle(-100.), -45.));
+
+    assert_eq!(ball_color(45.), SKY_COLOR);
+    assert_eq!(ball_color(-45.), GROUND_COLOR);
+    assert_eq!(ball_color(0.), LINE_COLOR);
+    assert_eq!(ball_color(20.1), LINE_COLOR);
+    assert_eq!(ball_color(-89.9), GROUND_COLOR);
+    let texture = ball_texture();
+    let TextureData::RgbaU8(data) = &texture.data else {
+        unreachable!()
+    };
+    // The top row of the image is the ground pole.
+    assert_eq!(data.first(), Some(&GROUND_COLOR));
+    assert_eq!(data.last(), Some(&SKY_COLOR));
+
+    // Direction of the sky pole of the ball on the screen, and its depth towards the viewer
+    let sky = |state: &FlightState| {
+        (ball_transformation(state) * Vec4::new(0., 0., 1., 0.))
+            .truncate()
+            .normalize()
+    };
+    let level = sky(&FlightState::default());
+    assert!(close(level.y, 1.) && close(level.x, 0.));
+    // Nose up, the horizon moves down and the sky pole comes towards the viewer.
+    let climb = sky(&FlightState {
+        pitch: 30.,
+        heading: 123.,
+        ..FlightState::default()
+    });
+    assert!(close(climb.y, 30f32.to_radians().cos()) && 0. < climb.z && close(climb.x, 0.));
+    // In a right bank, the horizon rises on the right and the sky is up to the left.
+    let bank = sky(&FlightState {
+        roll: 30.,
+        ..FlightState::default()
+    });
+    assert!(bank.x < 0. && close(bank.y, 30f32.to_radians().cos()));
+
+    // The instruments stay on the plate.
+    let plate = draw_plate();
+    let (min, max) = plate.fills.iter().flat_map(|fill| fill.points).fold(
+        (vec2(f32::MAX, f32::MAX), vec2(f32::MIN, f32::MIN)),
+        |(min, max), p| {
+            (
+                vec2(min.x.min(p.x), min.y.min(p.y)),
+                vec2(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    );
+    assert_eq!(min.y, -PANEL_HEIGHT / 2.);
+    for state in [
+        FlightState::default(),
+        FlightState {
+            airspeed: 500.,
+            altitude: 12345.,
+            vertical_speed: -80.,
+            heading: 271.,
+            pitch: -30.,
+            roll: -120.,
+            turn_rate: 10.,
+            sideslip: -30.,
+            ..FlightState::default()
+        },
+    ] {
+        let strokes = draw(&state);
+        let points = strokes
+            .fills
+            .iter()
+            .flat_map(|fill| fill.points)
+            .chain(strokes.strokes.iter().flat_map(|s| [s.start, s.end]));
+        for p in points {
+            assert!(
+                min.x <= p.x && p.x <= max.x && min.y <= p.y && p.y <= max.y,
+                "{p:?} off the plate for {state:?}"
+            );
+        }
+    }
+}
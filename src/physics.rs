@@ -1,5 +1,9 @@
 use rapier3d::{math::Vector, prelude::*};
 
+use crate::aircraft::AircraftDef;
+
+pub(crate) const GRAVITY: Vector<f32> = vector![0.0, -9.81, 0.0];
+
 pub(crate) struct PhysicsSet {
     pub rigid_body_set: RigidBodySet,
     pub collider_set: ColliderSet,
@@ -12,8 +16,9 @@ pub(crate) struct PhysicsSet {
     pub impulse_joint_set: ImpulseJointSet,
     pub multibody_joint_set: MultibodyJointSet,
     pub ccd_solver: CCDSolver,
+    /// Scene queries such as the landing gear's raycasts, updated by each step
+    pub query_pipeline: QueryPipeline,
     collision_notify: Vec<Box<dyn FnMut(CollisionEvent)>>,
-    contact_notify: Vec<Box<dyn FnMut(ContactForceEvent)>>,
 }
 
 impl PhysicsSet {
@@ -26,7 +31,7 @@ impl PhysicsSet {
         collider_set.insert(collider);
 
         /* Create other structures necessary for the simulation. */
-        let gravity = vector![0.0, -9.81, 0.0];
+        let gravity = GRAVITY;
         let integration_parameters = IntegrationParameters::default();
         let physics_pipeline = PhysicsPipeline::new();
         let island_manager = IslandManager::new();
@@ -48,21 +53,38 @@ impl PhysicsSet {
             impulse_joint_set,
             multibody_joint_set,
             ccd_solver,
+            query_pipeline: QueryPipeline::new(),
             collision_notify: vec![],
-            contact_notify: vec![],
         }
     }
 
-    pub(crate) fn new_body(&mut self, position: Vector<f32>) -> (RigidBodyHandle, ColliderHandle) {
-        /* Create the bounding ball. */
+    pub(crate) fn new_body(
+        &mut self,
+        position: Vector<f32>,
+        aircraft: &AircraftDef,
+    ) -> (RigidBodyHandle, ColliderHandle) {
+        // The fuel and payload are added by the vehicle as additional mass properties.
         let rigid_body = RigidBodyBuilder::dynamic()
             .translation(position)
             .linear_damping(0.001)
             .build();
-        let collider = ColliderBuilder::cuboid(13.06 * 0.5, 5.64 * 0.5, 19.43 * 0.5)
-            .restitution(0.7)
+        let builder = match &aircraft.hull {
+            Some(hull) => ColliderBuilder::convex_decomposition(&hull.vertices, &hull.indices),
+            None => {
+                let half_extents = aircraft.collider_size * 0.5;
+                ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z)
+            }
+        };
+        // The mass properties are given explicitly so that they don't depend on the shape.
+        let collider = builder
+            .mass_properties(MassProperties::new(
+                aircraft.center_of_mass.into(),
+                aircraft.mass,
+                aircraft.inertia,
+            ))
+            .restitution(0.2)
             .friction(0.001)
-            .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
             .build();
         let body_handle = self.rigid_body_set.insert(rigid_body);
         let collider_handle =
@@ -75,17 +97,14 @@ impl PhysicsSet {
         self.collision_notify.push(Box::new(f));
     }
 
-    pub(crate) fn _register_contact(&mut self, f: impl FnMut(ContactForceEvent) + 'static) {
-        self.contact_notify.push(Box::new(f));
-    }
-
     pub(crate) fn step(&mut self) {
         let physics_hooks = ();
         // let event_handler = ();
 
-        // Initialize the event collector.
+        // Initialize the event collector. The contact forces are read from the contact
+        // manifolds, so no contact force events are requested.
         let (collision_send, collision_recv) = rapier3d::crossbeam::channel::unbounded();
-        let (contact_force_send, contact_force_recv) = rapier3d::crossbeam::channel::unbounded();
+        let (contact_force_send, _) = rapier3d::crossbeam::channel::unbounded();
         let event_handler = ChannelEventCollector::new(collision_send, contact_force_send);
 
         self.physics_pipeline.step(
@@ -99,7 +118,7 @@ impl PhysicsSet {
             &mut self.impulse_joint_set,
             &mut self.multibody_joint_set,
             &mut self.ccd_solver,
-            None,
+            Some(&mut self.query_pipeline),
             &physics_hooks,
             &event_handler,
         );
@@ -111,13 +130,5 @@ impl PhysicsSet {
                 notify(collision_event);
             }
         }
-
-        while let Ok(contact_force_event) = contact_force_recv.try_recv() {
-            // Handle the contact force event.
-            // println!("Received contact force event: {:?}", contact_force_event);
-            for notify in &mut self.contact_notify {
-                notify(contact_force_event);
-            }
-        }
     }
 }
@@ -0,0 +1,357 @@
+//! Flight data recorder, which writes the state of every simulation tick as CSV,
+//! and the replay that plays it back.
+
+use std::{error::Error, io::Write, path::Path};
+
+use rapier3d::{
+    na::{Quaternion, UnitQuaternion},
+    prelude::*,
+};
+
+use crate::{bindings::Action, control_input::ControlInput};
+
+/// Number of columns before the wing forces
+const FIXED_COLUMNS: usize = 24;
+
+/// Maximum playback speed multiplier
+const MAX_SPEED: f64 = 16.;
+
+/// State of the vehicle at a simulation tick.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FlightRecord {
+    pub time: f64,
+    pub position: Isometry<f32>,
+    pub linvel: Vector<f32>,
+    pub angvel: Vector<f32>,
+    pub controls: ControlInput,
+    pub touching_ground: bool,
+    /// Contact force on the airframe, excluding the landing gear [N]
+    pub airframe_force: f32,
+    /// Engine N1 relative to the maximum
+    pub n1: f32,
+    /// Remaining fuel [kg]
+    pub fuel: f32,
+    /// Force of each wing in world coordinates
+    pub wing_forces: Vec<Vector<f32>>,
+}
+
+pub(crate) struct FlightRecorder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> FlightRecorder<W> {
+    /// Creates a recorder and writes the CSV header, which has a force column triplet
+    /// for each of `wing_names`.
+    pub fn new<'a>(mut out: W, wing_names: impl Iterator<Item = &'a str>) -> std::io::Result<Self> {
+        write!(
+            out,
+            "time,x,y,z,qw,qx,qy,qz,vx,vy,vz,wx,wy,wz,thrust,aileron,elevator,rudder,brake_left,brake_right,touching_ground,airframe_force,n1,fuel"
+        )?;
+        for name in wing_names {
+            write!(out, ",{name}.fx,{name}.fy,{name}.fz")?;
+        }
+        writeln!(out)?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, record: &FlightRecord) -> std::io::Result<()> {
+        let pos = record.position.translation.vector;
+        let rot = record.position.rotation;
+        let linvel = record.linvel;
+        let angvel = record.angvel;
+        let controls = &record.controls;
+        write!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            record.time,
+            pos.x,
+            pos.y,
+            pos.z,
+            rot.w,
+            rot.i,
+            rot.j,
+            rot.k,
+            linvel.x,
+            linvel.y,
+            linvel.z,
+            angvel.x,
+            angvel.y,
+            angvel.z,
+            controls.thrust,
+            controls.aileron,
+            controls.elevator,
+            controls.rudder,
+            controls.brake_left,
+            controls.brake_right,
+            record.touching_ground as u8,
+            record.airframe_force,
+            record.n1,
+            record.fuel,
+        )?;
+        for force in &record.wing_forces {
+            write!(self.out, ",{},{},{}", force.x, force.y, force.z)?;
+        }
+        writeln!(self.out)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Playback of a recorded flight with pause, scrubbing and speed control.
+pub(crate) struct Replay {
+    records: Vec<FlightRecord>,
+    /// Current playback time
+    time: f64,
+    /// Playback speed multiplier, negative for playing backwards
+    speed: f64,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>, wings: usize) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read flight record {}: {e}", path.display()))?;
+        Ok(Self::parse(&src, wings).map_err(|e| format!("{}: {e}", path.display()))?)
+    }
+
+    /// Parses a flight record of an aircraft with the number of `wings`.
+    pub fn parse(src: &str, wings: usize) -> Result<Self, String> {
+        let mut lines = src.lines().enumerate();
+        let Some((_, header)) = lines.next() else {
+            return Err("flight record is empty".to_string());
+        };
+        let columns = header.split(',').count();
+        if columns < FIXED_COLUMNS || !(columns - FIXED_COLUMNS).is_multiple_of(3) {
+            return Err(format!("unexpected number of columns {columns} in header"));
+        }
+        let recorded_wings = (columns - FIXED_COLUMNS) / 3;
+        if recorded_wings != wings {
+            return Err(format!(
+                "recorded with {recorded_wings} wings, but the aircraft has {wings}"
+            ));
+        }
+
+        let mut records: Vec<FlightRecord> = vec![];
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let cells: Vec<_> = line.split(',').map(|s| s.trim()).collect();
+            if cells.len() != columns {
+                return Err(format!(
+                    "line {}: expected {columns} columns, got {}",
+                    i + 1,
+                    cells.len()
+                ));
+            }
+            let time = cells[0]
+                .parse::<f64>()
+                .map_err(|e| format!("line {}: {e}", i + 1))?;
+            let v = cells[1..]
+                .iter()
+                .map(|s| s.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {e}", i + 1))?;
+            if !(time.is_finite() && v.iter().all(|v| v.is_finite())) {
+                return Err(format!("line {}: values must be finite", i + 1));
+            }
+            // Column index to the value
+            let f = |i: usize| v[i - 1];
+            let rotation = Quaternion::new(f(4), f(5), f(6), f(7));
+            if rotation.norm() == 0. {
+                return Err(format!("line {}: rotation must not be zero", i + 1));
+            }
+            let record = FlightRecord {
+                time,
+                position: Isometry::from_parts(
+                    Translation::new(f(1), f(2), f(3)),
+                    UnitQuaternion::from_quaternion(rotation),
+                ),
+                linvel: vector![f(8), f(9), f(10)],
+                angvel: vector![f(11), f(12), f(13)],
+                controls: ControlInput {
+                    thrust: f(14),
+                    aileron: f(15),
+                    elevator: f(16),
+                    rudder: f(17),
+                    brake_left: f(18),
+                    brake_right: f(19),
+                },
+                touching_ground: f(20) != 0.,
+                airframe_force: f(21),
+                n1: f(22),
+                fuel: f(23),
+                wing_forces: (FIXED_COLUMNS..columns)
+                    .step_by(3)
+                    .map(|i| vector![f(i), f(i + 1), f(i + 2)])
+                    .collect(),
+            };
+            if records.last().is_some_and(|prev| record.time < prev.time) {
+                return Err(format!("line {}: time must not decrease", i + 1));
+            }
+            records.push(record);
+        }
+        if records.is_empty() {
+            return Err("flight record has no ticks".to_string());
+        }
+
+        let time = records[0].time;
+        Ok(Self {
+            records,
+            time,
+            speed: 1.,
+        })
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.records[0].time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.records[self.records.len() - 1].time
+    }
+
+    /// Playback position in the range [0, 1]
+    pub fn progress(&self) -> f64 {
+        let duration = self.end_time() - self.start_time();
+        if 0. < duration {
+            (self.time - self.start_time()) / duration
+        } else {
+            1.
+        }
+    }
+
+    pub fn seek(&mut self, time: f64) {
+        self.time = time.clamp(self.start_time(), self.end_time());
+    }
+
+    /// Advances the playback by the elapsed real time in seconds, multiplied by the speed.
+    pub fn advance(&mut self, delta_time: f64) {
+        self.seek(self.time + delta_time * self.speed);
+    }
+
+    /// Scrubs, changes the speed of, reverses or restarts the playback.
+    pub fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::ReplayBackward => self.seek(self.time - 1.),
+            Action::ReplayForward => self.seek(self.time + 1.),
+            Action::ReplayFaster => {
+                self.speed = (self.speed.abs() * 2.).min(MAX_SPEED).copysign(self.speed)
+            }
+            Action::ReplaySlower => {
+                self.speed = (self.speed.abs() * 0.5)
+                    .max(1. / MAX_SPEED)
+                    .copysign(self.speed)
+            }
+            Action::ReplayReverse => self.speed = -self.speed,
+            Action::ReplayRestart => self.seek(self.start_time()),
+            _ => {}
+        }
+    }
+
+    /// Returns the records before and after the playback time and the interpolation
+    /// factor between them.
+    pub fn sample(&self) -> (&FlightRecord, &FlightRecord, f32) {
+        let next = self
+            .records
+            .partition_point(|record| record.time <= self.time);
+        let prev = &self.records[next.saturating_sub(1)];
+        let Some(next) = self.records.get(next) else {
+            return (prev, prev, 0.);
+        };
+        let span = next.time - prev.time;
+        let alpha = if 0. < span {
+            ((self.time - prev.time) / span).clamp(0., 1.)
+        } else {
+            1.
+        };
+        (prev, next, alpha as f32)
+    }
+}
+
+#[test]
+fn test_record_roundtrip() {
+    let records: Vec<_> = (0..3)
+        .map(|i| FlightRecord {
+            time: i as f64 * 0.5,
+            position: Isometry::new(vector![1., 2., i as f32], vector![0., 0.5, 0.]),
+            linvel: vector![0., 0., -100.],
+            angvel: vector![0.1, 0., 0.],
+            controls: ControlInput {
+                thrust: 0.5,
+                aileron: -0.25,
+                elevator: 0.,
+                rudder: 1.,
+                brake_left: 0.5,
+                brake_right: 0.,
+            },
+            touching_ground: i == 2,
+            airframe_force: if i == 2 { 5000. } else { 0. },
+            n1: 0.75,
+            fuel: 100. - i as f32,
+            wing_forces: vec![vector![1., 2., 3.], vector![4., 5., 6.]],
+        })
+        .collect();
+
+    let mut buf = vec![];
+    let mut recorder = FlightRecorder::new(&mut buf, ["Left", "Right"].into_iter()).unwrap();
+    for record in &records {
+        recorder.record(record).unwrap();
+    }
+    let src = std::str::from_utf8(&buf).unwrap();
+    assert_eq!(
+        Replay::parse(src, 3).err().unwrap(),
+        "recorded with 2 wings, but the aircraft has 3"
+    );
+    // A non-finite value or a zero rotation would poison the rigid body.
+    let header = src.lines().next().unwrap();
+    let line = src.lines().nth(1).unwrap();
+    for (bad, err) in [
+        (
+            line.replacen("0.5", "NaN", 1),
+            "line 2: values must be finite",
+        ),
+        (
+            line.replacen("1", "inf", 1),
+            "line 2: values must be finite",
+        ),
+    ] {
+        assert_eq!(
+            Replay::parse(&format!("{header}\n{bad}"), 2).err().unwrap(),
+            err
+        );
+    }
+    let mut cells: Vec<_> = line.split(',').collect();
+    cells[4..8].fill("0");
+    assert_eq!(
+        Replay::parse(&format!("{header}\n{}", cells.join(",")), 2)
+            .err()
+            .unwrap(),
+        "line 2: rotation must not be zero"
+    );
+    let mut replay = Replay::parse(src, 2).unwrap();
+    for (actual, expected) in replay.records.iter().zip(&records) {
+        assert!(
+            actual
+                .position
+                .rotation
+                .angle_to(&expected.position.rotation)
+                < 1e-6
+        );
+        let expected = FlightRecord {
+            position: Isometry::from_parts(expected.position.translation, actual.position.rotation),
+            ..expected.clone()
+        };
+        assert_eq!(*actual, expected);
+    }
+
+    replay.seek(0.75);
+    let (prev, next, alpha) = replay.sample();
+    assert_eq!((prev.time, next.time, alpha), (0.5, 1., 0.5));
+    replay.advance(10.);
+    assert_eq!(replay.time, 1.);
+    let (prev, next, alpha) = replay.sample();
+    assert_eq!((prev.time, next.time, alpha), (1., 1., 0.));
+}
@@ -0,0 +1,354 @@
+//! Vector graphics of line strokes and a stroke font, drawn as thin quads like the
+//! symbology of a head-up display, over filled shapes.
+
+use three_d::{ColorMaterial, Context, CpuMaterial, Gm, Indices, Mesh, Positions};
+use three_d_asset::{vec2, InnerSpace, Srgba, TriMesh, Vec2, Vec3};
+
+/// Height of the glyph cell in font units, which the text size scales to
+const GLYPH_HEIGHT: f32 = 6.;
+/// Advance of a character in font units, including the gap between the glyphs
+const GLYPH_ADVANCE: f32 = 6.;
+
+type Glyph = &'static [&'static [(u8, u8)]];
+
+/// Returns the polylines of the character in a cell 4 units wide and 6 units high, or `None`
+/// if the font has no glyph for it. Lowercase letters are drawn in uppercase.
+fn glyph(c: char) -> Option<Glyph> {
+    Some(match c.to_ascii_uppercase() {
+        ' ' => &[],
+        '0' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0), (4, 6)]],
+        '1' => &[&[(1, 5), (2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        '2' => &[&[(0, 6), (4, 6), (4, 3), (0, 3), (0, 0), (4, 0)]],
+        '3' => &[&[(0, 6), (4, 6), (4, 0), (0, 0)], &[(0, 3), (4, 3)]],
+        '4' => &[&[(0, 6), (0, 3), (4, 3)], &[(4, 6), (4, 0)]],
+        '5' => &[&[(4, 6), (0, 6), (0, 3), (4, 3), (4, 0), (0, 0)]],
+        '6' => &[&[(4, 6), (0, 6), (0, 0), (4, 0), (4, 3), (0, 3)]],
+        '7' => &[&[(0, 6), (4, 6), (4, 0)]],
+        '8' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0)], &[(0, 3), (4, 3)]],
+        '9' => &[&[(4, 3), (0, 3), (0, 6), (4, 6), (4, 0), (0, 0)]],
+        'A' => &[&[(0, 0), (0, 4), (2, 6), (4, 4), (4, 0)], &[(0, 3), (4, 3)]],
+        'B' => &[
+            &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+            &[(3, 3), (4, 2), (4, 1), (3, 0), (0, 0)],
+        ],
+        'C' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)]],
+        'D' => &[&[(0, 0), (0, 6), (2, 6), (4, 4), (4, 2), (2, 0), (0, 0)]],
+        'E' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 6), (0, 6), (0, 0)], &[(0, 3), (3, 3)]],
+        'G' => &[&[(4, 6), (0, 6), (0, 0), (4, 0), (4, 3), (2, 3)]],
+        'H' => &[&[(0, 6), (0, 0)], &[(4, 6), (4, 0)], &[(0, 3), (4, 3)]],
+        'I' => &[&[(1, 6), (3, 6)], &[(2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        'J' => &[&[(4, 6), (4, 0), (0, 0), (0, 2)]],
+        'K' => &[&[(0, 6), (0, 0)], &[(4, 6), (0, 3), (4, 0)]],
+        'L' => &[&[(0, 6), (0, 0), (4, 0)]],
+        'M' => &[&[(0, 0), (0, 6), (2, 3), (4, 6), (4, 0)]],
+        'N' => &[&[(0, 0), (0, 6), (4, 0), (4, 6)]],
+        'O' => &[&[
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+        ]],
+        'P' => &[&[(0, 0), (0, 6), (4, 6), (4, 3), (0, 3)]],
+        'Q' => &[
+            &[
+                (1, 0),
+                (3, 0),
+                (4, 1),
+                (4, 5),
+                (3, 6),
+                (1, 6),
+                (0, 5),
+                (0, 1),
+                (1, 0),
+            ],
+            &[(2, 2), (4, 0)],
+        ],
+        'R' => &[&[(0, 0), (0, 6), (4, 6), (4, 3), (0, 3), (4, 0)]],
+        'S' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 4),
+            (1, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+            (0, 1),
+        ]],
+        'T' => &[&[(0, 6), (4, 6)], &[(2, 6), (2, 0)]],
+        'U' => &[&[(0, 6), (0, 0), (4, 0), (4, 6)]],
+        'V' => &[&[(0, 6), (2, 0), (4, 6)]],
+        'W' => &[&[(0, 6), (1, 0), (2, 3), (3, 0), (4, 6)]],
+        'X' => &[&[(0, 6), (4, 0)], &[(4, 6), (0, 0)]],
+        'Y' => &[&[(0, 6), (2, 3), (4, 6)], &[(2, 3), (2, 0)]],
+        'Z' => &[&[(0, 6), (4, 6), (0, 0), (4, 0)]],
+        '-' => &[&[(1, 3), (3, 3)]],
+        '+' => &[&[(1, 3), (3, 3)], &[(2, 2), (2, 4)]],
+        '.' => &[&[(2, 0), (2, 1)]],
+        ':' => &[&[(2, 1), (2, 2)], &[(2, 4), (2, 5)]],
+        '/' => &[&[(0, 0), (4, 6)]],
+        '%' => &[&[(0, 0), (4, 6)], &[(0, 6), (0, 5)], &[(4, 0), (4, 1)]],
+        _ => return None,
+    })
+}
+
+/// Horizontal alignment of a text to its position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Returns the width of the text of `size` high characters.
+pub(crate) fn text_width(text: &str, size: f32) -> f32 {
+    let n = text.chars().count();
+    // The gap after the last character doesn't count.
+    (n as f32 * GLYPH_ADVANCE - (GLYPH_ADVANCE - 4.)).max(0.) * size / GLYPH_HEIGHT
+}
+
+/// A line segment from `start` to `end`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Stroke {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub color: Srgba,
+}
+
+/// A filled triangle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Fill {
+    pub points: [Vec2; 3],
+    pub color: Srgba,
+}
+
+/// A list of strokes and filled shapes in 2D, built every frame and drawn in a single mesh.
+/// The fills are drawn first, in the order they were added, and the strokes over them.
+#[derive(Default)]
+pub(crate) struct Strokes {
+    pub strokes: Vec<Stroke>,
+    pub fills: Vec<Fill>,
+    /// Color of the strokes and fills added next
+    pub color: Srgba,
+}
+
+impl Strokes {
+    pub fn new(color: Srgba) -> Self {
+        Self {
+            strokes: vec![],
+            fills: vec![],
+            color,
+        }
+    }
+
+    pub fn line(&mut self, start: Vec2, end: Vec2) {
+        self.strokes.push(Stroke {
+            start,
+            end,
+            color: self.color,
+        });
+    }
+
+    pub fn polyline(&mut self, points: impl IntoIterator<Item = Vec2>) {
+        let mut points = points.into_iter();
+        let Some(mut prev) = points.next() else {
+            return;
+        };
+        for point in points {
+            self.line(prev, point);
+            prev = point;
+        }
+    }
+
+    /// Adds the outline of the rectangle of the center and the half extents.
+    pub fn rect(&mut self, center: Vec2, half: Vec2) {
+        self.polyline([
+            center + vec2(-half.x, -half.y),
+            center + vec2(half.x, -half.y),
+            center + vec2(half.x, half.y),
+            center + vec2(-half.x, half.y),
+            center + vec2(-half.x, -half.y),
+        ]);
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, segments: usize) {
+        self.polyline((0..=segments).map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            center + vec2(angle.cos(), angle.sin()) * radius
+        }));
+    }
+
+    /// Fills the rectangle of the center and the half extents.
+    pub fn fill_rect(&mut self, center: Vec2, half: Vec2) {
+        let corners = [
+            center + vec2(-half.x, -half.y),
+            center + vec2(half.x, -half.y),
+            center + vec2(half.x, half.y),
+            center + vec2(-half.x, half.y),
+        ];
+        for points in [
+            [corners[0], corners[1], corners[2]],
+            [corners[0], corners[2], corners[3]],
+        ] {
+            self.fills.push(Fill {
+                points,
+                color: self.color,
+            });
+        }
+    }
+
+    pub fn fill_circle(&mut self, center: Vec2, radius: f32, segments: usize) {
+        let point = |i: usize| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            center + vec2(angle.cos(), angle.sin()) * radius
+        };
+        for i in 0..segments {
+            self.fills.push(Fill {
+                points: [center, point(i), point(i + 1)],
+                color: self.color,
+            });
+        }
+    }
+
+    /// Adds the text with its baseline at `pos`, aligned horizontally by `align`.
+    /// Characters without a glyph are left blank.
+    pub fn text(&mut self, text: &str, pos: Vec2, size: f32, align: Align) {
+        let scale = size / GLYPH_HEIGHT;
+        let width = text_width(text, size);
+        let left = match align {
+            Align::Left => pos.x,
+            Align::Center => pos.x - width / 2.,
+            Align::Right => pos.x - width,
+        };
+        for (i, c) in text.chars().enumerate() {
+            let origin = vec2(left + i as f32 * GLYPH_ADVANCE * scale, pos.y);
+            for polyline in glyph(c).unwrap_or(&[]) {
+                self.polyline(
+                    polyline
+                        .iter()
+                        .map(|(x, y)| origin + vec2(*x as f32, *y as f32) * scale),
+                );
+            }
+        }
+    }
+
+    /// Builds the mesh of the fills and the strokes as quads of `width` in the z = 0 plane,
+    /// or `None` if there is nothing to draw.
+    pub fn to_mesh(&self, width: f32) -> Option<TriMesh> {
+        if self.strokes.is_empty() && self.fills.is_empty() {
+            return None;
+        }
+        let vertices = self.fills.len() * 3 + self.strokes.len() * 4;
+        let mut positions = Vec::with_capacity(vertices);
+        let mut colors = Vec::with_capacity(vertices);
+        let mut indices = Vec::with_capacity(self.fills.len() * 3 + self.strokes.len() * 6);
+        for fill in &self.fills {
+            let base = positions.len() as u32;
+            for point in fill.points {
+                positions.push(Vec3::new(point.x, point.y, 0.));
+                colors.push(fill.color);
+            }
+            indices.extend([base, base + 1, base + 2]);
+        }
+        for stroke in &self.strokes {
+            let delta = stroke.end - stroke.start;
+            let length = delta.magnitude();
+            // Extend the ends by half the width, so that the polylines join without gaps.
+            let along = if 0. < length {
+                delta / length * width / 2.
+            } else {
+                vec2(width / 2., 0.)
+            };
+            let across = vec2(-along.y, along.x);
+            let base = positions.len() as u32;
+            for corner in [
+                stroke.start - along - across,
+                stroke.start - along + across,
+                stroke.end + along - across,
+                stroke.end + along + across,
+            ] {
+                positions.push(Vec3::new(corner.x, corner.y, 0.));
+                colors.push(stroke.color);
+            }
+            indices.extend([base, base + 2, base + 1, base + 1, base + 2, base + 3]);
+        }
+        Some(TriMesh {
+            positions: Positions::F32(positions),
+            indices: Indices::U32(indices),
+            colors: Some(colors),
+            ..Default::default()
+        })
+    }
+
+    /// Builds the object to render the fills and the strokes, or `None` if there is nothing
+    /// to draw.
+    pub fn to_object(&self, context: &Context, width: f32) -> Option<Gm<Mesh, ColorMaterial>> {
+        let mesh = self.to_mesh(width)?;
+        Some(Gm::new(
+            Mesh::new(context, &mesh),
+            ColorMaterial::new_transparent(
+                context,
+                &CpuMaterial {
+                    albedo: Srgba::WHITE,
+                    ..Default::default()
+                },
+            ),
+        ))
+    }
+}
+
+#[test]
+fn test_strokes() {
+    // Every character that the HUD draws has a glyph within the cell.
+    for c in "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ -+.:/%".chars() {
+        let glyph = glyph(c).unwrap_or_else(|| panic!("no glyph for {c:?}"));
+        assert!(glyph
+            .iter()
+            .flat_map(|polyline| polyline.iter())
+            .all(|(x, y)| *x <= 4 && *y <= 6));
+    }
+    assert_eq!(glyph('a'), glyph('A'));
+    assert!(glyph('~').is_none());
+
+    assert_eq!(text_width("", 6.), 0.);
+    assert_eq!(text_width("1", 6.), 4.);
+    assert_eq!(text_width("123", 3.), 8.);
+
+    let mut strokes = Strokes::new(Srgba::GREEN);
+    strokes.text("17", vec2(10., 0.), 6., Align::Right);
+    // The right edge of the 7 is at the position.
+    let right = strokes
+        .strokes
+        .iter()
+        .flat_map(|stroke| [stroke.start.x, stroke.end.x])
+        .fold(f32::MIN, f32::max);
+    assert_eq!(right, 10.);
+    let count = strokes.strokes.len();
+    strokes.text("~", vec2(0., 0.), 6., Align::Left);
+    assert_eq!(strokes.strokes.len(), count);
+
+    let mesh = strokes.to_mesh(0.5).unwrap();
+    assert_eq!(mesh.positions.len(), count * 4);
+    assert_eq!(mesh.colors.unwrap().len(), count * 4);
+    assert!(Strokes::default().to_mesh(0.5).is_none());
+
+    // The fills come before the strokes in the mesh, so that the strokes are drawn over them.
+    strokes.fill_rect(vec2(0., 0.), vec2(1., 2.));
+    strokes.fill_circle(vec2(0., 0.), 1., 8);
+    assert_eq!(strokes.fills.len(), 10);
+    let mesh = strokes.to_mesh(0.5).unwrap();
+    assert_eq!(mesh.positions.len(), 30 + count * 4);
+    assert_eq!(mesh.positions.to_f32()[2], Vec3::new(1., 2., 0.));
+    let mut fills = Strokes::default();
+    fills.fill_rect(vec2(0., 0.), vec2(1., 1.));
+    assert!(fills.to_mesh(0.5).is_some());
+}
@@ -0,0 +1,57 @@
+//! Fixed timestep accumulator, which decouples the simulation rate from the frame rate.
+
+pub(crate) const DEFAULT_STEP_RATE: f64 = 60.;
+pub(crate) const DEFAULT_MAX_SUBSTEPS: u32 = 8;
+
+pub(crate) struct FixedTimestep {
+    delta_time: f64,
+    max_substeps: u32,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    /// Creates a timestep with `step_rate` steps per second, which runs at most
+    /// `max_substeps` steps per frame so that a slow frame cannot stall the simulation.
+    pub fn new(step_rate: f64, max_substeps: u32) -> Self {
+        Self {
+            delta_time: 1. / step_rate,
+            max_substeps: max_substeps.max(1),
+            accumulator: 0.,
+        }
+    }
+
+    /// Duration of a single step in seconds
+    pub fn delta_time(&self) -> f64 {
+        self.delta_time
+    }
+
+    /// Accumulates the elapsed frame time in seconds and returns the number of steps to run.
+    /// Time exceeding `max_substeps` steps is dropped.
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.accumulator += frame_time;
+        let steps = (self.accumulator / self.delta_time).floor();
+        if self.max_substeps as f64 <= steps {
+            self.accumulator = 0.;
+            return self.max_substeps;
+        }
+        self.accumulator -= steps * self.delta_time;
+        steps as u32
+    }
+
+    /// Fraction of a step remaining in the accumulator, used to interpolate
+    /// the rendered state between the last two steps.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.delta_time).clamp(0., 1.)
+    }
+}
+
+#[test]
+fn test_fixed_timestep() {
+    let mut timestep = FixedTimestep::new(100., 4);
+    assert_eq!(timestep.advance(0.005), 0);
+    assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+    assert_eq!(timestep.advance(0.02), 2);
+    assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+    assert_eq!(timestep.advance(1.), 4);
+    assert_eq!(timestep.alpha(), 0.);
+}
@@ -0,0 +1,205 @@
+//! Solver of the trimmed state for steady level flight.
+//!
+//! In steady level flight the lift and thrust carry the weight, the thrust cancels the drag
+//! and the pitching moment about the center of gravity vanishes. Given the airspeed and the
+//! altitude, the solver finds the pitch attitude and the elevator deflection by Newton's
+//! method on the wing forces of the vehicle, with the thrust balancing the drag at each step.
+
+use rapier3d::{
+    na::{Matrix2, UnitQuaternion},
+    prelude::*,
+};
+
+use crate::{
+    atmosphere::Atmosphere,
+    control_input::{ControlInput, Trim},
+    physics::PhysicsSet,
+    vehicle::Vehicle,
+};
+
+/// Maximum iterations of Newton's method
+const MAX_ITERATIONS: usize = 50;
+/// Residual of the normalized force and moment balance regarded as converged
+const TOLERANCE: f32 = 1e-4;
+/// Step of the numerical derivatives
+const DERIVATIVE_STEP: f32 = 1e-3;
+/// Largest pitch attitude considered for level flight [rad]
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_4;
+
+/// Trimmed state of steady level flight
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TrimState {
+    /// True airspeed [m/s]
+    pub speed: f32,
+    /// Pitch attitude, nose up, which equals the angle of attack in level flight [rad]
+    pub pitch: f32,
+    /// Elevator deflection in the range [-1, 1]
+    pub elevator: f32,
+    /// Required thrust [N]
+    pub thrust: f32,
+    /// Throttle position giving the thrust
+    pub throttle: f32,
+}
+
+impl std::fmt::Display for TrimState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pitch {:.2} degrees, elevator {:.3}, thrust {:.0} N, throttle {:.3}",
+            self.pitch.to_degrees(),
+            self.elevator,
+            self.thrust,
+            self.throttle
+        )
+    }
+}
+
+/// Finds the trimmed state for steady level flight at the airspeed [m/s] and the altitude [m]
+/// with the vehicle's current wings, mass and engine, under the gravity [m/s^2].
+pub(crate) fn solve_trim(
+    vehicle: &Vehicle,
+    speed: f32,
+    altitude: f32,
+    gravity: f32,
+) -> Result<TrimState, String> {
+    if !(speed.is_finite() && 0. < speed) {
+        return Err(format!("airspeed must be positive, got {speed}"));
+    }
+    let atmosphere = Atmosphere::at_altitude(altitude);
+    let weight = vehicle.mass_properties().mass() * gravity;
+    let velocity = vector![0., 0., -speed];
+
+    // Returns the normalized residuals of the vertical force and the pitching moment, the
+    // thrust and whether a wing stalls at the pitch and the elevator.
+    let residual = |pitch: f32, elevator: f32| {
+        let rotation = UnitQuaternion::from_axis_angle(&Vector::x_axis(), pitch);
+        let controls = ControlInput {
+            elevator,
+            ..ControlInput::default()
+        };
+        let (force, moment, stalled) = vehicle.aerodynamics(
+            &rotation,
+            &velocity,
+            &controls,
+            &Trim::default(),
+            &atmosphere,
+        );
+        // The thrust along the body's nose balances the drag.
+        let thrust = force.z / pitch.cos();
+        let lift = force.y + thrust * pitch.sin();
+        (
+            vector![(lift - weight) / weight, moment.x / weight],
+            thrust,
+            stalled,
+        )
+    };
+
+    let mut x = vector![0f32, 0.];
+    let mut converged = false;
+    for _ in 0..MAX_ITERATIONS {
+        let (r, _, _) = residual(x[0], x[1]);
+        if r.norm() < TOLERANCE {
+            converged = true;
+            break;
+        }
+        let (r_pitch, _, _) = residual(x[0] + DERIVATIVE_STEP, x[1]);
+        let (r_elevator, _, _) = residual(x[0], x[1] + DERIVATIVE_STEP);
+        let jacobian = Matrix2::from_columns(&[
+            (r_pitch - r) / DERIVATIVE_STEP,
+            (r_elevator - r) / DERIVATIVE_STEP,
+        ]);
+        let Some(inverse) = jacobian.try_inverse() else {
+            return Err(format!(
+                "no trim exists at {speed} m/s: the pitch and the elevator don't balance the forces"
+            ));
+        };
+        x -= inverse * r;
+        x[0] = x[0].clamp(-MAX_PITCH, MAX_PITCH);
+        x[1] = x[1].clamp(-2., 2.);
+    }
+    let (pitch, elevator) = (x[0], x[1]);
+    if !converged {
+        return Err(format!(
+            "no trim exists at {speed} m/s: the solver did not converge"
+        ));
+    }
+    if 1. < elevator.abs() {
+        return Err(format!(
+            "no trim exists at {speed} m/s: the elevator would need {elevator:.2} of its range"
+        ));
+    }
+    let (_, thrust, stalled) = residual(pitch, elevator);
+    if stalled {
+        return Err(format!(
+            "no trim exists at {speed} m/s: the wings stall at {:.1} degrees pitch",
+            pitch.to_degrees()
+        ));
+    }
+    let mach = speed / atmosphere.speed_of_sound;
+    let throttle = vehicle
+        .engine
+        .throttle_for(thrust, atmosphere.density_ratio(), mach)
+        .ok_or_else(|| {
+            format!(
+                "no trim exists at {speed} m/s: the engine can't produce {thrust:.0} N of thrust"
+            )
+        })?;
+    Ok(TrimState {
+        speed,
+        pitch,
+        elevator,
+        thrust,
+        throttle,
+    })
+}
+
+/// Solves the trim at the airspeed [m/s] and the altitude [m] and puts the vehicle in the
+/// trimmed state, which it returns.
+pub(crate) fn start_trimmed(
+    vehicle: &mut Vehicle,
+    physics: &mut PhysicsSet,
+    speed: f32,
+    altitude: f32,
+) -> Result<TrimState, String> {
+    let state = solve_trim(vehicle, speed, altitude, physics.gravity.norm())?;
+    vehicle.set_trimmed(physics, &state, altitude);
+    Ok(state)
+}
+
+#[test]
+fn test_trim() {
+    use crate::{
+        aircraft::{AircraftDef, DEFAULT_AIRCRAFT},
+        wind::WindField,
+    };
+
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let mut physics = PhysicsSet::new(1000.);
+    let handles = physics.new_body(vector![0., 1000., 0.], &aircraft);
+    let mut vehicle = Vehicle::new(handles, &aircraft);
+    let gravity = physics.gravity.norm();
+
+    let state = solve_trim(&vehicle, 150., 1000., gravity).unwrap();
+    assert!(0. < state.pitch && 0. < state.thrust, "{state:?}");
+
+    // The trimmed aircraft holds its altitude and attitude without touching the stick.
+    vehicle.set_trimmed(&mut physics, &state, 1000.);
+    let delta_time = physics.integration_parameters.dt as f64;
+    let controls = vehicle.controls;
+    for _ in 0..300 {
+        vehicle.update(delta_time, &mut physics, &controls, &WindField::default());
+        physics.step();
+    }
+    let body = &physics.rigid_body_set[vehicle.body_handle];
+    assert!(
+        (body.translation().y - 1000.).abs() < 5.,
+        "{}",
+        body.translation().y
+    );
+    assert!(body.angvel().norm() < 0.05, "{}", body.angvel());
+    assert!((body.linvel().norm() - 150.).abs() < 2.);
+
+    // Too slow to fly level
+    let err = solve_trim(&vehicle, 10., 1000., gravity).unwrap_err();
+    assert!(err.starts_with("no trim exists at 10 m/s"), "{err}");
+}
@@ -1,313 +1,620 @@
-use three_d::{Camera, ColorMaterial, Context, CpuMaterial, Gm, Mesh, RenderTarget, Window};
-use three_d_asset::{vec3, Mat4, Srgba, TriMesh, Vec3};
-
-const THRUST_BAR_X: f32 = -3.0;
-const THRUST_BAR_Y: f32 = -3.0;
-const THRUST_BAR_WIDTH: f32 = 0.2;
-const THRUST_BAR_HEIGHT: f32 = 1.0;
-const AILERON_BAR_X: f32 = 0.;
-const AILERON_BAR_Y: f32 = -3.5;
-const AILERON_BAR_WIDTH: f32 = 1.0;
-const AILERON_BAR_HEIGHT: f32 = 0.2;
-const ELEVATOR_BAR_X: f32 = 2.5;
-const ELEVATOR_BAR_Y: f32 = -3.0;
-const ELEVATOR_BAR_WIDTH: f32 = 0.2;
-const ELEVATOR_BAR_HEIGHT: f32 = 1.0;
-const RUDDER_BAR_X: f32 = 0.;
-const RUDDER_BAR_Y: f32 = -3.0;
-const RUDDER_BAR_WIDTH: f32 = 1.0;
-const RUDDER_BAR_HEIGHT: f32 = 0.2;
-const CONTACT_BAR_X: f32 = 3.0;
-const CONTACT_BAR_Y: f32 = -3.0;
-const CONTACT_BAR_WIDTH: f32 = 0.2;
-const CONTACT_BAR_HEIGHT: f32 = 0.2;
+//! Widgets of the telemetry overlay: bar gauges, round dials, indicator lights and
+//! labels, laid out from a file and anchored to the edges of the viewport.
+//!
+//! The widgets are laid out in UI units, where the viewport is `UI_HEIGHT` units high with
+//! the origin at its center, so they scale with the window height and keep their distance
+//! from the edges they are anchored to at any aspect ratio.
+//! Each widget shows the value last given to its name with [`Ui::update`], so new telemetry
+//! only needs an entry in the layout file.
+
+use std::{error::Error, path::Path};
+
+use serde::Deserialize;
+use three_d::{Camera, ColorMaterial, Context, Gm, Mesh, RenderTarget, Viewport};
+use three_d_asset::{vec2, vec3, Srgba, Vec2};
+
+use crate::strokes::{Align, Strokes};
+
+pub(crate) const DEFAULT_LAYOUT: &str = "assets/ui.toml";
+
+/// Height of the viewport in UI units
+const UI_HEIGHT: f32 = 100.;
+const LINE_WIDTH: f32 = 0.3;
+const BACK_COLOR: Srgba = Srgba::new(0, 0, 0, 191);
+const SCALE_COLOR: Srgba = Srgba::WHITE;
+/// Angle of the dial needle at either end of the range, clockwise from the top [deg]
+const DIAL_SWEEP: f32 = 135.;
+/// Number of divisions of the dial scale
+const DIAL_DIVISIONS: usize = 10;
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Point of the viewport that a widget is positioned from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position of the anchor in a viewport `half_width` UI units wide on either side
+    fn position(self, half_width: f32) -> Vec2 {
+        use Anchor::*;
+        let x = match self {
+            TopLeft | Left | BottomLeft => -half_width,
+            Top | Center | Bottom => 0.,
+            TopRight | Right | BottomRight => half_width,
+        };
+        let y = match self {
+            TopLeft | Top | TopRight => UI_HEIGHT / 2.,
+            Left | Center | Right => 0.,
+            BottomLeft | Bottom | BottomRight => -UI_HEIGHT / 2.,
+        };
+        vec2(x, y)
+    }
+}
+
+/// Direction that a bar gauge fills towards with increasing value
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Direction {
+    #[default]
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum KindName {
+    Bar,
+    Dial,
+    Light,
+    Label,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WidgetFile {
+    /// Name of the value that the widget shows, not needed by a fixed label
+    name: Option<String>,
+    kind: KindName,
+    anchor: Anchor,
+    /// Position of the center from the anchor, with y up
+    #[serde(default)]
+    offset: [f32; 2],
+    size: [f32; 2],
+    #[serde(default)]
+    hide_empty: bool,
+    /// Whether the widget lies under the instrument panel and is hidden while it is shown
+    #[serde(default)]
+    under_panel: bool,
+    range: Option<[f32; 2]>,
+    origin: Option<f32>,
+    #[serde(default)]
+    direction: Direction,
+    color: Option<[u8; 4]>,
+    colors: Option<Vec<[u8; 4]>>,
+    text: Option<String>,
+    #[serde(default)]
+    precision: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayoutFile {
+    widgets: Vec<WidgetFile>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum WidgetKind {
+    /// A bar filled from `origin` to the value in the `range`
+    Bar {
+        range: [f32; 2],
+        origin: f32,
+        direction: Direction,
+        color: Srgba,
+    },
+    /// A needle sweeping over the scale of the `range`
+    Dial { range: [f32; 2], color: Srgba },
+    /// A light that is off at a value of 0 or less, and lit in the color of the value,
+    /// counting from 1
+    Light { colors: Vec<Srgba> },
+    /// The text, followed by the value with the precision if there is one
+    Label {
+        text: String,
+        precision: usize,
+        color: Srgba,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Widget {
+    name: Option<String>,
+    kind: WidgetKind,
+    anchor: Anchor,
+    offset: Vec2,
+    size: Vec2,
+    /// Whether the widget is hidden without a value, instead of drawn empty
+    hide_empty: bool,
+    under_panel: bool,
+    value: Option<f32>,
+}
+
+fn color([r, g, b, a]: [u8; 4]) -> Srgba {
+    Srgba::new(r, g, b, a)
+}
+
+impl Widget {
+    fn from_file(file: WidgetFile) -> Result<Self, String> {
+        let label = file.name.as_deref().unwrap_or("unnamed");
+        if !(0. < file.size[0] && 0. < file.size[1]) {
+            return Err(format!(
+                "widget {label}: size must be positive, got {:?}",
+                file.size
+            ));
+        }
+        let range = file.range.unwrap_or([0., 1.]);
+        if !(range[0].is_finite() && range[1].is_finite() && range[0] < range[1]) {
+            return Err(format!(
+                "widget {label}: range must be finite and increasing, got {range:?}"
+            ));
+        }
+        let required_color = || {
+            file.color
+                .map(color)
+                .ok_or_else(|| format!("widget {label}: {:?} requires a color", file.kind))
+        };
+        let kind = match file.kind {
+            KindName::Bar => WidgetKind::Bar {
+                range,
+                origin: file.origin.unwrap_or(range[0]),
+                direction: file.direction,
+                color: required_color()?,
+            },
+            KindName::Dial => WidgetKind::Dial {
+                range,
+                color: required_color()?,
+            },
+            KindName::Light => match file.colors {
+                Some(colors) if !colors.is_empty() => WidgetKind::Light {
+                    colors: colors.into_iter().map(color).collect(),
+                },
+                _ => return Err(format!("widget {label}: Light requires colors")),
+            },
+            KindName::Label => WidgetKind::Label {
+                text: file.text.unwrap_or_default(),
+                precision: file.precision,
+                color: file.color.map_or(SCALE_COLOR, color),
+            },
+        };
+        Ok(Self {
+            name: file.name,
+            kind,
+            anchor: file.anchor,
+            offset: file.offset.into(),
+            size: file.size.into(),
+            hide_empty: file.hide_empty,
+            under_panel: file.under_panel,
+            value: None,
+        })
+    }
+
+    /// Draws the widget in a viewport `half_width` UI units wide on either side.
+    fn draw(&self, strokes: &mut Strokes, half_width: f32) {
+        if self.hide_empty && self.value.is_none() {
+            return;
+        }
+        let center = self.anchor.position(half_width) + self.offset;
+        let half = self.size / 2.;
+        let fraction = |range: [f32; 2], value: f32| {
+            ((value - range[0]) / (range[1] - range[0])).clamp(0., 1.)
+        };
+        match &self.kind {
+            WidgetKind::Bar {
+                range,
+                origin,
+                direction,
+                color,
+            } => {
+                strokes.color = BACK_COLOR;
+                strokes.fill_rect(center, half);
+                let Some(value) = self.value else {
+                    return;
+                };
+                let (start, end) = (fraction(*range, *origin), fraction(*range, value));
+                // Extent of the fill along the bar from its bottom or left edge in [0, 1]
+                let (low, high) = match direction {
+                    Direction::Up | Direction::Right => (start.min(end), start.max(end)),
+                    Direction::Down | Direction::Left => (1. - start.max(end), 1. - start.min(end)),
+                };
+                let (fill_center, fill_half) = match direction {
+                    Direction::Up | Direction::Down => (
+                        vec2(
+                            center.x,
+                            center.y - half.y + self.size.y * (low + high) / 2.,
+                        ),
+                        vec2(half.x, half.y * (high - low)),
+                    ),
+                    Direction::Left | Direction::Right => (
+                        vec2(
+                            center.x - half.x + self.size.x * (low + high) / 2.,
+                            center.y,
+                        ),
+                        vec2(half.x * (high - low), half.y),
+                    ),
+                };
+                strokes.color = *color;
+                strokes.fill_rect(fill_center, fill_half);
+            }
+            WidgetKind::Dial { range, color } => {
+                let radius = half.x.min(half.y);
+                let at = |fraction: f32, distance: f32| {
+                    let angle = (DIAL_SWEEP * (2. * fraction - 1.)).to_radians();
+                    center + vec2(angle.sin(), angle.cos()) * distance
+                };
+                strokes.color = BACK_COLOR;
+                strokes.fill_circle(center, radius, CIRCLE_SEGMENTS);
+                strokes.color = SCALE_COLOR;
+                strokes.circle(center, radius, CIRCLE_SEGMENTS);
+                for i in 0..=DIAL_DIVISIONS {
+                    let fraction = i as f32 / DIAL_DIVISIONS as f32;
+                    strokes.line(at(fraction, radius * 0.8), at(fraction, radius));
+                }
+                if let Some(value) = self.value {
+                    strokes.color = *color;
+                    strokes.line(center, at(fraction(*range, value), radius * 0.7));
+                }
+            }
+            WidgetKind::Light { colors } => {
+                strokes.color = BACK_COLOR;
+                strokes.fill_rect(center, half);
+                let index = self.value.map_or(0, |value| value.round() as i64);
+                if 0 < index {
+                    strokes.color = colors[(index as usize).min(colors.len()) - 1];
+                    strokes.fill_rect(center, half);
+                }
+            }
+            WidgetKind::Label {
+                text,
+                precision,
+                color,
+            } => {
+                let text = match self.value {
+                    Some(value) => format!("{text}{value:.precision$}"),
+                    None => text.clone(),
+                };
+                strokes.color = *color;
+                strokes.text(&text, center - vec2(0., half.y), self.size.y, Align::Center);
+            }
+        }
+    }
+}
+
+/// The widgets of the overlay with their values
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Layout {
+    widgets: Vec<Widget>,
+    panel_shown: bool,
+}
+
+impl Layout {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read UI layout {}: {e}", path.display()))?;
+        Self::parse(&src).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let file: LayoutFile = toml::from_str(src)?;
+        let widgets = file
+            .widgets
+            .into_iter()
+            .map(Widget::from_file)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            widgets,
+            panel_shown: false,
+        })
+    }
+
+    /// Shows the value on the widgets of the name. Names without widgets are ignored, so
+    /// that a layout can leave out any value.
+    pub fn update(&mut self, name: &str, value: f32) {
+        self.set(name, Some(value));
+    }
+
+    /// Removes the value of the widgets of the name, which hides those with `hide_empty`.
+    pub fn clear(&mut self, name: &str) {
+        self.set(name, None);
+    }
+
+    /// Hides the widgets with `under_panel` while the instrument panel is shown.
+    pub fn set_panel_shown(&mut self, shown: bool) {
+        self.panel_shown = shown;
+    }
+
+    fn set(&mut self, name: &str, value: Option<f32>) {
+        for widget in &mut self.widgets {
+            if widget.name.as_deref() == Some(name) {
+                widget.value = value;
+            }
+        }
+    }
+
+    /// Draws the widgets in a viewport `half_width` UI units wide on either side of the
+    /// center, in the order of the layout.
+    pub fn draw(&self, half_width: f32) -> Strokes {
+        let mut strokes = Strokes::default();
+        for widget in &self.widgets {
+            if widget.under_panel && self.panel_shown {
+                continue;
+            }
+            widget.draw(&mut strokes, half_width);
+        }
+        strokes
+    }
+}
 
 pub(crate) struct Ui {
+    context: Context,
     camera: Camera,
-    // ui_grid_obj: Box<dyn Object>,
-    thrust_bar_back: Gm<Mesh, ColorMaterial>,
-    thrust_bar: Gm<Mesh, ColorMaterial>,
-    aileron_bar_back: Gm<Mesh, ColorMaterial>,
-    aileron_bar: Gm<Mesh, ColorMaterial>,
-    elevator_bar_back: Gm<Mesh, ColorMaterial>,
-    elevator_bar: Gm<Mesh, ColorMaterial>,
-    rudder_bar_back: Gm<Mesh, ColorMaterial>,
-    rudder_bar: Gm<Mesh, ColorMaterial>,
-    contact_back: Gm<Mesh, ColorMaterial>,
-    contact: Gm<Mesh, ColorMaterial>,
-    has_contact: bool,
+    layout: Layout,
+    object: Option<Gm<Mesh, ColorMaterial>>,
 }
 
 impl Ui {
-    pub(crate) fn new(window: &Window, context: &Context) -> Self {
+    pub(crate) fn new(context: &Context, viewport: Viewport, layout: Layout) -> Self {
         let camera = Camera::new_orthographic(
-            window.viewport(),
+            viewport,
             vec3(0.0, 0.0, 1.0),
             vec3(0.0, 0.0, 0.0),
             vec3(0.0, 1.0, 0.0),
-            10.0,
+            UI_HEIGHT,
             0.1,
             1000.0,
         );
+        Self {
+            context: context.clone(),
+            camera,
+            layout,
+            object: None,
+        }
+    }
 
-        // let ui_grid = grid_mesh(10, 10, 0.5, 0.01);
-        // let mut ui_grid_obj = Gm::new(
-        //     Mesh::new(&context, &ui_grid),
-        //     ColorMaterial::new(
-        //         &context,
-        //         &CpuMaterial {
-        //             albedo: Srgba {
-        //                 r: 0,
-        //                 g: 255,
-        //                 b: 0,
-        //                 a: 255,
-        //             },
-        //             ..Default::default()
-        //         },
-        //     ),
-        // );
-        // ui_grid_obj.set_transformation(Mat4::from_angle_x(Deg(90.)));
-
-        let bar = TriMesh::square();
-        let mut thrust_bar_back = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(0, 0, 0, 191),
-                    ..Default::default()
-                },
-            ),
-        );
-        thrust_bar_back.set_transformation(
-            Mat4::from_translation(Vec3::new(THRUST_BAR_X, THRUST_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(THRUST_BAR_WIDTH, THRUST_BAR_HEIGHT, 1.),
-        );
+    /// Shows the value on the widgets of the name, see [`Layout::update`].
+    pub(crate) fn update(&mut self, name: &str, value: f32) {
+        self.layout.update(name, value);
+    }
 
-        let mut thrust_bar = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(255, 0, 0, 255),
-                    ..Default::default()
-                },
-            ),
-        );
-        thrust_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(THRUST_BAR_X, THRUST_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(THRUST_BAR_WIDTH, THRUST_BAR_HEIGHT, 1.),
-        );
+    pub(crate) fn clear(&mut self, name: &str) {
+        self.layout.clear(name);
+    }
 
-        let mut aileron_bar_back = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(0, 0, 0, 191),
-                    ..Default::default()
-                },
-            ),
-        );
-        aileron_bar_back.set_transformation(
-            Mat4::from_translation(Vec3::new(AILERON_BAR_X, AILERON_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(AILERON_BAR_WIDTH, AILERON_BAR_HEIGHT, 1.),
-        );
+    pub(crate) fn set_panel_shown(&mut self, shown: bool) {
+        self.layout.set_panel_shown(shown);
+    }
 
-        let mut aileron_bar = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(0, 127, 255, 255),
-                    ..Default::default()
-                },
-            ),
-        );
-        aileron_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(AILERON_BAR_X, AILERON_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(AILERON_BAR_WIDTH, AILERON_BAR_HEIGHT, 1.),
-        );
+    /// Rebuilds the widgets for the current values and the viewport.
+    pub(crate) fn redraw(&mut self, viewport: Viewport) {
+        self.camera.set_viewport(viewport);
+        let half_width = UI_HEIGHT / 2. * viewport.aspect();
+        self.object = self
+            .layout
+            .draw(half_width)
+            .to_object(&self.context, LINE_WIDTH);
+    }
 
-        let mut elevator_bar_back = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(0, 0, 0, 191),
-                    ..Default::default()
-                },
-            ),
-        );
-        elevator_bar_back.set_transformation(
-            Mat4::from_translation(Vec3::new(ELEVATOR_BAR_X, ELEVATOR_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(ELEVATOR_BAR_WIDTH, ELEVATOR_BAR_HEIGHT, 1.),
-        );
+    pub(crate) fn render(&self, render: &RenderTarget) {
+        if let Some(object) = &self.object {
+            render.render(&self.camera, [object], &[]);
+        }
+    }
+}
 
-        let mut elevator_bar = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(255, 0, 255, 255),
-                    ..Default::default()
-                },
-            ),
-        );
-        elevator_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(ELEVATOR_BAR_X, ELEVATOR_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(ELEVATOR_BAR_WIDTH, ELEVATOR_BAR_HEIGHT, 1.),
-        );
+#[test]
+fn test_ui() {
+    let mut layout = Layout::parse(
+        r#"
+[[widgets]]
+name = "thrust"
+kind = "bar"
+anchor = "bottom_left"
+offset = [10.0, 20.0]
+size = [4.0, 20.0]
+color = [255, 0, 0, 255]
 
-        let mut rudder_bar_back = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(0, 0, 0, 191),
-                    ..Default::default()
-                },
-            ),
-        );
-        rudder_bar_back.set_transformation(
-            Mat4::from_translation(Vec3::new(RUDDER_BAR_X, RUDDER_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(RUDDER_BAR_WIDTH, RUDDER_BAR_HEIGHT, 1.),
-        );
+[[widgets]]
+name = "aileron"
+kind = "bar"
+anchor = "bottom"
+offset = [0.0, 10.0]
+size = [20.0, 2.0]
+range = [-1.0, 1.0]
+origin = 0.0
+direction = "left"
+under_panel = true
+color = [0, 127, 255, 255]
 
-        let mut rudder_bar = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(255, 255, 0, 255),
-                    ..Default::default()
-                },
-            ),
-        );
-        rudder_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(RUDDER_BAR_X, RUDDER_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(RUDDER_BAR_WIDTH, RUDDER_BAR_HEIGHT, 1.),
-        );
+[[widgets]]
+name = "contact"
+kind = "light"
+anchor = "top_right"
+offset = [-5.0, -5.0]
+size = [4.0, 4.0]
+colors = [[255, 255, 0, 255], [255, 0, 0, 255]]
 
-        let mut contact_back = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(0, 0, 0, 191),
-                    ..Default::default()
-                },
-            ),
-        );
-        contact_back.set_transformation(
-            Mat4::from_translation(Vec3::new(CONTACT_BAR_X, CONTACT_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(CONTACT_BAR_WIDTH, CONTACT_BAR_HEIGHT, 1.),
-        );
+[[widgets]]
+name = "replay"
+kind = "bar"
+anchor = "top"
+size = [80.0, 2.0]
+direction = "right"
+hide_empty = true
+color = [255, 255, 255, 255]
+"#,
+    )
+    .unwrap();
 
-        let mut contact = Gm::new(
-            Mesh::new(&context, &bar),
-            ColorMaterial::new(
-                &context,
-                &CpuMaterial {
-                    albedo: Srgba::new(255, 255, 0, 255),
-                    ..Default::default()
-                },
-            ),
-        );
-        contact.set_transformation(
-            Mat4::from_translation(Vec3::new(CONTACT_BAR_X, CONTACT_BAR_Y, 0.))
-                * Mat4::from_nonuniform_scale(CONTACT_BAR_WIDTH, CONTACT_BAR_HEIGHT, 1.),
-        );
+    let fill_bounds = |strokes: &Strokes, color: Srgba| {
+        let points: Vec<_> = strokes
+            .fills
+            .iter()
+            .filter(|fill| fill.color == color)
+            .flat_map(|fill| fill.points)
+            .collect();
+        let min = points.iter().fold(vec2(f32::MAX, f32::MAX), |a, p| {
+            vec2(a.x.min(p.x), a.y.min(p.y))
+        });
+        let max = points.iter().fold(vec2(f32::MIN, f32::MIN), |a, p| {
+            vec2(a.x.max(p.x), a.y.max(p.y))
+        });
+        (!points.is_empty()).then_some((min, max))
+    };
+    let close = |a: Vec2, b: Vec2| (a - b).x.abs() < 1e-4 && (a - b).y.abs() < 1e-4;
+    let red = Srgba::new(255, 0, 0, 255);
+    let blue = Srgba::new(0, 127, 255, 255);
 
-        Self {
-            camera,
-            // ui_grid_obj: Box::new(ui_grid_obj),
-            thrust_bar_back,
-            thrust_bar,
-            aileron_bar_back,
-            aileron_bar,
-            elevator_bar_back,
-            elevator_bar,
-            rudder_bar_back,
-            rudder_bar,
-            contact_back,
-            contact,
-            has_contact: false,
-        }
-    }
+    // Without values, only the backgrounds are drawn, and the replay bar is hidden.
+    assert_eq!(layout.draw(50.).fills.len(), 2 * 3);
 
-    pub(crate) fn render(&self, render: &RenderTarget) {
-        let mut objects = vec![
-            // self.ui_grid_obj.as_ref(),
-            &self.thrust_bar_back,
-            &self.thrust_bar,
-            &self.aileron_bar_back,
-            &self.aileron_bar,
-            &self.elevator_bar_back,
-            &self.elevator_bar,
-            &self.rudder_bar_back,
-            &self.rudder_bar,
-            &self.contact_back,
-        ];
-        if self.has_contact {
-            objects.push(&self.contact);
-        }
-        render.render(&self.camera, &objects, &[]);
+    layout.update("thrust", 0.5);
+    layout.update("aileron", 0.5);
+    layout.update("contact", 2.);
+    layout.update("unknown", 1.);
+    for half_width in [50., 50. * 16. / 9.] {
+        let strokes = layout.draw(half_width);
+        // The thrust bar is anchored to the bottom left corner and filled from the bottom
+        // to the middle, in red as the second contact light at the top right corner.
+        let (min, max) = fill_bounds(&strokes, red).unwrap();
+        assert!(close(min, vec2(-half_width + 8., -40.)), "{min:?}");
+        assert!(close(max, vec2(half_width - 3., 47.)), "{max:?}");
+        let thrust = strokes.fills.iter().find(|fill| fill.color == red).unwrap();
+        assert!(close(thrust.points[2], vec2(-half_width + 12., -30.)));
+        // A positive aileron fills to the left of the center.
+        let (min, max) = fill_bounds(&strokes, blue).unwrap();
+        assert!(close(min, vec2(-5., -41.)) && close(max, vec2(0., -39.)));
     }
 
-    pub(crate) fn update_thrust(&mut self, thrust: f32) {
-        self.thrust_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(
-                THRUST_BAR_X,
-                THRUST_BAR_Y + THRUST_BAR_HEIGHT * (thrust - 1.),
-                0.,
-            )) * Mat4::from_nonuniform_scale(THRUST_BAR_WIDTH, THRUST_BAR_HEIGHT * thrust, 1.),
-        );
-    }
+    // The aileron bar under the instrument panel is hidden while the panel is shown.
+    let fills = layout.draw(50.).fills.len();
+    layout.set_panel_shown(true);
+    assert!(fill_bounds(&layout.draw(50.), blue).is_none());
+    assert_eq!(layout.draw(50.).fills.len(), fills - 2 * 2);
+    layout.set_panel_shown(false);
 
-    pub(crate) fn update_aileron(&mut self, aileron: f32) {
-        self.aileron_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(
-                AILERON_BAR_X + AILERON_BAR_WIDTH * -aileron * 0.5,
-                AILERON_BAR_Y,
-                0.,
-            )) * Mat4::from_nonuniform_scale(
-                AILERON_BAR_WIDTH * -aileron * 0.5,
-                AILERON_BAR_HEIGHT,
-                1.,
-            ),
-        );
-    }
+    layout.update("replay", 0.25);
+    layout.clear("thrust");
+    let strokes = layout.draw(50.);
+    let (min, max) = fill_bounds(&strokes, Srgba::WHITE).unwrap();
+    assert!(close(min, vec2(-40., 49.)) && close(max, vec2(-20., 51.)));
+    let (min, _) = fill_bounds(&strokes, red).unwrap();
+    assert!(close(min, vec2(43., 43.)));
 
-    pub(crate) fn update_elevator(&mut self, elevator: f32) {
-        self.elevator_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(
-                ELEVATOR_BAR_X,
-                ELEVATOR_BAR_Y + ELEVATOR_BAR_HEIGHT * -elevator * 0.5,
-                0.,
-            )) * Mat4::from_nonuniform_scale(
-                ELEVATOR_BAR_WIDTH,
-                ELEVATOR_BAR_HEIGHT * -elevator * 0.5,
-                1.,
-            ),
-        );
-    }
+    let mut dial = Layout::parse(
+        r#"
+[[widgets]]
+name = "n1"
+kind = "dial"
+anchor = "center"
+size = [10.0, 10.0]
+range = [0.0, 100.0]
+color = [0, 255, 0, 255]
 
-    pub(crate) fn update_rudder(&mut self, rudder: f32) {
-        self.rudder_bar.set_transformation(
-            Mat4::from_translation(Vec3::new(
-                RUDDER_BAR_X + RUDDER_BAR_WIDTH * -rudder * 0.5,
-                RUDDER_BAR_Y,
-                0.,
-            )) * Mat4::from_nonuniform_scale(
-                RUDDER_BAR_WIDTH * -rudder * 0.5,
-                RUDDER_BAR_HEIGHT,
-                1.,
-            ),
-        );
-    }
+[[widgets]]
+name = "n1"
+kind = "label"
+anchor = "center"
+offset = [0.0, -8.0]
+size = [10.0, 3.0]
+text = "N1 "
+"#,
+    )
+    .unwrap();
+    dial.update("n1", 50.);
+    let strokes = dial.draw(50.);
+    // The needle at the middle of the range points up.
+    let needle = strokes
+        .strokes
+        .iter()
+        .find(|stroke| stroke.color == Srgba::GREEN)
+        .unwrap();
+    assert!(close(needle.start, vec2(0., 0.)) && close(needle.end, vec2(0., 3.5)));
+    // The label shows the value after the text, above its baseline.
+    assert!(strokes
+        .strokes
+        .iter()
+        .filter(|stroke| stroke.color == SCALE_COLOR)
+        .any(|stroke| stroke.start.y == -9.5));
+
+    let err = |src: &str| Layout::parse(src).unwrap_err().to_string();
+    assert!(err(r#"
+[[widgets]]
+name = "gear"
+kind = "bar"
+anchor = "bottom"
+size = [4.0, 4.0]
+"#)
+    .contains("widget gear: Bar requires a color"));
+    assert!(err(r#"
+[[widgets]]
+name = "gear"
+kind = "light"
+anchor = "bottom"
+size = [4.0, 4.0]
+range = [1.0, 0.0]
+colors = [[0, 255, 0, 255]]
+"#)
+    .contains("widget gear: range must be finite and increasing, got [1.0, 0.0]"));
+    assert!(err(r#"
+[[widgets]]
+name = "gear"
+kind = "light"
+anchor = "bottom"
+size = [4.0, 4.0]
+range = [0.0, nan]
+colors = [[0, 255, 0, 255]]
+"#)
+    .contains("widget gear: range must be finite and increasing, got [0.0, NaN]"));
+    assert!(err(r#"
+[[widgets]]
+kind = "light"
+anchor = "bottom"
+size = [4.0, 4.0]
+"#)
+    .contains("widget unnamed: Light requires colors"));
+    assert!(err(r#"
+[[widgets]]
+kind = "label"
+anchor = "middle"
+size = [10.0, 3.0]
+"#)
+    .contains("unknown variant `middle`"));
 
-    pub(crate) fn update_has_contact(&mut self, v: bool) {
-        self.has_contact = v;
+    // The widgets of the default layout stay inside the viewport.
+    let layout = Layout::load(DEFAULT_LAYOUT).unwrap();
+    for aspect in [1., 4. / 3., 16. / 9.] {
+        let half_width = UI_HEIGHT / 2. * aspect;
+        let strokes = layout.draw(half_width);
+        let points = strokes
+            .fills
+            .iter()
+            .flat_map(|fill| fill.points)
+            .chain(strokes.strokes.iter().flat_map(|s| [s.start, s.end]));
+        for p in points {
+            assert!(
+                p.x.abs() <= half_width && p.y.abs() <= UI_HEIGHT / 2.,
+                "{p:?} out of the viewport at the aspect ratio {aspect}"
+            );
+        }
     }
 }
@@ -1,221 +1,488 @@
 use std::error::Error;
 
 use rapier3d::{
-    na::{Rotation3, UnitQuaternion, Vector3},
+    na::{UnitQuaternion, Vector3},
     prelude::*,
 };
-use three_d::{
-    ColorMaterial, Context, CpuMaterial, Cull, Event, FrameInput, Gm, Key, Mesh, PhysicalMaterial,
-};
+use three_d::{ColorMaterial, Context, CpuMaterial, Cull, Gm, Mesh, PhysicalMaterial};
 use three_d_asset::{
-    Deg, GeometryFunction, InnerSpace, LightingModel, Mat4, NormalDistributionFunction, Quat,
+    Deg, GeometryFunction, InnerSpace, LightingModel, Mat4, NormalDistributionFunction, Quat, Rad,
     Srgba, TriMesh, Vec3, Zero,
 };
 
-use crate::mqo::load_mqo_scale;
+use crate::{
+    aircraft::AircraftDef,
+    airfoil::Airfoil,
+    atmosphere::Atmosphere,
+    autopilot::{Autopilot, FlightState, LateralMode, VerticalMode},
+    contact::{ContactKind, ContactTracker},
+    control_input::{Actuators, ControlInput, Trim},
+    damage::{wing_damage, Impact},
+    engine::Engine,
+    landing_gear::LandingGear,
+    mass::Loading,
+    mqo::load_mqo_scale,
+    physics::{PhysicsSet, GRAVITY},
+    recorder::FlightRecord,
+    trim::TrimState,
+    wind::WindField,
+};
 
 pub(crate) const VEHICLE_POSITION: Vector<f32> = vector![0.0, 200.0, 0.0];
 
 pub(crate) struct Vehicle {
     pub body_handle: RigidBodyHandle,
-    pub collider_handle: ColliderHandle,
-    pub thrust: f32,
-    thrust_increase: bool,
-    thrust_decrease: bool,
-    pub aileron: f32,
-    aileron_increase: bool,
-    aileron_decrease: bool,
-    pub elevator: f32,
-    elevator_increase: bool,
-    elevator_decrease: bool,
-    pub rudder: f32,
-    rudder_increase: bool,
-    rudder_decrease: bool,
+    /// Commanded controls after the autopilot, which the actuators follow
+    pub command: ControlInput,
+    /// Actual state of the controls, following `command` by the dynamics of the actuators
+    pub controls: ControlInput,
+    pub actuators: Actuators,
+    pub trim: Trim,
+    pub autopilot: Autopilot,
+    /// Whether the airframe or a wheel touches the ground
     pub touching_ground: bool,
+    pub contacts: ContactTracker,
+    /// The most severe impact since the last touchdown
+    pub impact: Option<Impact>,
+    /// Whether the aircraft has crashed, which freezes it until reset
+    pub crashed: bool,
+    /// Sink rate before the last physics step [m/s]
+    sink_rate: f32,
+    /// Load factor, the specific force along the body's up axis over the gravity [g]
+    pub load_factor: f32,
+    /// Velocity at the last update, to derive the acceleration
+    prev_linvel: Option<Vector<f32>>,
+    /// Whether there were contacts in the last update, to detect the touchdown
+    in_contact: bool,
+    pub engine: Engine,
+    pub landing_gear: LandingGear,
+    /// Fuel and payload distribution
+    pub loading: Loading,
+    /// Mass properties of the airframe without fuel and payload
+    airframe: MassProperties,
+    /// Engine and loading at the start, restored by the reset
+    initial_engine: Engine,
+    initial_loading: Loading,
+    /// Position before the last physics step, used to interpolate the rendered transformation
+    prev_position: Option<Isometry<f32>>,
     wings: Vec<Wing>,
 }
 
 impl Vehicle {
-    pub fn new((body_handle, collider_handle): (RigidBodyHandle, ColliderHandle)) -> Self {
-        use std::f32::consts::PI;
-        let wings = vec![
-            Wing {
-                name: "MainRight".to_string(),
-                pos: Vector::new(4., 1., 0.0),
-                aero: MAIN_WING_TENSOR,
-                control: Control::Aileron,
-                sensitivity: -0.05 * PI,
-                axis: Vector::new(1., 0., 0.),
-                force: Vector::zero(),
-            },
-            Wing {
-                name: "MainLeft".to_string(),
-                pos: Vector::new(-4., 1., 0.0),
-                aero: MAIN_WING_TENSOR,
-                control: Control::Aileron,
-                sensitivity: 0.05 * PI,
-                axis: Vector::new(1., 0., 0.),
-                force: Vector::zero(),
-            },
-            Wing {
-                name: "TailRight".to_string(),
-                pos: Vector::new(3., 0.0, 8.),
-                aero: TAIL_WING_TENSOR,
-                control: Control::Elevator,
-                sensitivity: -0.1 * PI,
-                axis: Vector::new(1., 0., 0.),
-                force: Vector::zero(),
-            },
-            Wing {
-                name: "TailLeft".to_string(),
-                pos: Vector::new(-3., 0.0, 8.),
-                aero: TAIL_WING_TENSOR,
-                control: Control::Elevator,
-                sensitivity: -0.1 * PI,
-                axis: Vector::new(1., 0., 0.),
-                force: Vector::zero(),
-            },
-            Wing {
-                name: "VerticalLeft".to_string(),
-                pos: Vector::new(2., 2., 7.),
-                aero: RUDDER_TENSOR,
-                control: Control::Rudder,
-                sensitivity: -0.15 * PI,
-                axis: Vector::new(0., 1., 0.),
-                force: Vector::zero(),
-            },
-            Wing {
-                name: "VerticalRight".to_string(),
-                pos: Vector::new(-2., 2., 7.),
-                aero: RUDDER_TENSOR,
-                control: Control::Rudder,
-                sensitivity: -0.15 * PI,
-                axis: Vector::new(0., 1., 0.),
+    pub fn new(
+        (body_handle, collider_handle): (RigidBodyHandle, ColliderHandle),
+        aircraft: &AircraftDef,
+    ) -> Self {
+        let wings = aircraft
+            .wings
+            .iter()
+            .map(|wing| Wing {
+                name: wing.name.clone(),
+                pos: wing.pos,
+                aero: wing.aero,
+                control: wing.control,
+                sensitivity: wing.sensitivity,
+                axis: wing.axis,
+                airfoil: wing.airfoil.clone(),
+                stalled: false,
+                health: 1.,
                 force: Vector::zero(),
-            },
-        ];
+            })
+            .collect();
+
+        let loading = Loading::new(aircraft);
+        let mut engine = Engine::new(&aircraft.engine);
+        engine.fuel = loading.fuel();
 
         Self {
             body_handle,
-            collider_handle,
-            thrust: 0.,
-            thrust_increase: false,
-            thrust_decrease: false,
-            aileron: 0.,
-            aileron_increase: false,
-            aileron_decrease: false,
-            elevator: 0.,
-            elevator_increase: false,
-            elevator_decrease: false,
-            rudder: 0.,
-            rudder_increase: false,
-            rudder_decrease: false,
+            command: ControlInput::default(),
+            controls: ControlInput::default(),
+            actuators: aircraft.actuators,
+            trim: Trim::default(),
+            autopilot: Autopilot::new(&aircraft.autopilot),
             touching_ground: false,
+            contacts: ContactTracker::new(collider_handle),
+            impact: None,
+            crashed: false,
+            sink_rate: 0.,
+            load_factor: 1.,
+            prev_linvel: None,
+            in_contact: false,
+            initial_engine: engine.clone(),
+            initial_loading: loading.clone(),
+            engine,
+            landing_gear: LandingGear::new(&aircraft.gears),
+            loading,
+            airframe: MassProperties::new(
+                aircraft.center_of_mass.into(),
+                aircraft.mass,
+                aircraft.inertia,
+            ),
+            prev_position: None,
             wings,
         }
     }
 
+    /// Moves the controls towards the commanded values and applies the aerodynamic, engine
+    /// and landing gear forces.
     pub fn update(
         &mut self,
         delta_time: f64,
-        rigid_body_set: &mut RigidBodySet,
-        frame_input: &FrameInput,
+        physics: &mut PhysicsSet,
+        command: &ControlInput,
+        wind: &WindField,
     ) {
-        macro_rules! handle_keys {
-            ($($field:ident => $key:path),* $(,)?) => {
-                for e in &frame_input.events {
-                    match e {
-                        $(
-                            Event::KeyPress { kind: $key, .. } => {
-                                self.$field = true;
-                            }
-                            Event::KeyRelease { kind: $key, .. } => {
-                                self.$field = false;
-                            }
-                        )*
-                        _ => {}
-                    }
-                }
-            }
+        let gravity = physics.gravity;
+        let body = &mut physics.rigid_body_set[self.body_handle];
+        if delta_time == 0. {
+            return; // Skip computing physics if paused
         }
+        self.prev_position = Some(*body.position());
+        if self.crashed {
+            return; // The wreck stays where it stopped
+        }
+        let dt = delta_time as f32;
+        if let Some(prev_linvel) = self.prev_linvel.replace(*body.linvel()) {
+            let acceleration = (body.linvel() - prev_linvel) / dt;
+            self.load_factor =
+                load_factor(&acceleration, &gravity, &(body.rotation() * Vector::y()));
+        }
+        // The contacts of the last step are judged by the sink rate before it.
+        let sink_rate = std::mem::replace(&mut self.sink_rate, -body.linvel().y);
+
+        self.command = self
+            .autopilot
+            .command(dt, &flight_state(body, wind), *command);
+        self.controls
+            .approach(&self.command, &self.actuators, delta_time);
+
+        // Aerodynamic forces and engine thrust scale with the air density.
+        let atmosphere = Atmosphere::at_altitude(body.translation().y);
+        let density_ratio = atmosphere.density_ratio();
 
-        handle_keys! {
-            thrust_increase => Key::Q,
-            thrust_decrease => Key::Z,
-            aileron_increase => Key::A,
-            aileron_decrease => Key::D,
-            elevator_increase => Key::W,
-            elevator_decrease => Key::S,
-            rudder_increase => Key::X,
-            rudder_decrease => Key::C,
+        for wing in &mut self.wings {
+            if wing.health <= 0. {
+                wing.force = Vector::zero();
+                continue; // Torn off
+            }
+            let deflection = wing.deflection(&self.controls, &self.trim);
+            // Airflow relative to the wing, including the velocity from the body's rotation,
+            // which gives the aerodynamic damping. The wind is sampled at the wing's position.
+            let wing_pos = body.position() * Point::from(wing.pos);
+            let wing_vel = body.velocity_at_point(&wing_pos);
+            let airvel = wing_vel - wind.velocity_at(&wing_pos);
+            let mut stalled = wing.stalled;
+            let force = wing.force(
+                body.rotation(),
+                &airvel,
+                deflection,
+                &atmosphere,
+                &mut stalled,
+            );
+            wing.stalled = stalled;
+            // Applied at the wing, the force turns the body around the center of gravity.
+            body.apply_impulse_at_point(force * dt, wing_pos, true);
+            wing.force = force;
         }
 
-        let body = &mut rigid_body_set[self.body_handle];
-        if delta_time == 0. {
-            return; // Handle key events and skip computing physics if paused
+        self.engine.update(self.controls.thrust, dt);
+        let airvel = body.linvel() - wind.velocity_at(&Point::from(*body.translation()));
+        let mach = airvel.norm() / atmosphere.speed_of_sound;
+        let force = Vector3::new(0., 0., -self.engine.thrust(density_ratio, mach));
+        let forward_force = body.rotation().transform_vector(&force);
+        body.apply_impulse(forward_force * dt, true);
+        // Burnt fuel lightens the airframe and moves its center of gravity from the next step.
+        self.loading.set_fuel(self.engine.fuel);
+        body.set_additional_mass_properties(self.loading.mass_properties(), true);
+
+        self.landing_gear.update(
+            dt,
+            physics,
+            self.body_handle,
+            &self.controls,
+            &airvel,
+            atmosphere.density,
+        );
+        self.contacts.update(physics, self.landing_gear.contacts());
+        self.touching_ground = !self.contacts.contacts().is_empty();
+        self.assess_damage(physics, sink_rate, dt);
+    }
+
+    /// Classifies the impact of the contacts, damages the wings hit by the airframe contact
+    /// forces and freezes the aircraft on a crash.
+    fn assess_damage(&mut self, physics: &mut PhysicsSet, sink_rate: f32, delta_time: f32) {
+        let body = &mut physics.rigid_body_set[self.body_handle];
+        let gravity = physics.gravity.norm();
+        let weight = body.mass() * gravity;
+        let contacts = self.contacts.contacts();
+        let impact = Impact::classify(contacts, sink_rate, body.mass(), gravity, delta_time);
+        if self.in_contact {
+            self.impact = self.impact.max(impact);
+        } else if impact.is_some() {
+            self.impact = impact;
         }
+        self.in_contact = impact.is_some();
 
-        macro_rules! handle_holds {
-            ($([$field:ident => ($incr:ident, $decr:ident) ($min:literal, $max:literal)]),* $(,)?) => {
-                $(
-                    if self.$incr {
-                        self.$field = (self.$field + delta_time as f32).min($max);
-                    }
-                    if self.$decr {
-                        self.$field = (self.$field - delta_time as f32).max($min);
-                    }
-                )*
-            }
+        let wings: Vec<_> = self.wings.iter().map(|wing| wing.pos).collect();
+        let damage = wing_damage(
+            contacts,
+            self.contacts.airframe_force(),
+            weight,
+            delta_time,
+            body.position(),
+            &wings,
+        );
+        for (wing, damage) in self.wings.iter_mut().zip(damage) {
+            wing.health = (wing.health - damage).max(0.);
         }
 
-        handle_holds! {
-            [thrust => (thrust_increase, thrust_decrease) (0., 1.)],
-            [aileron => (aileron_increase, aileron_decrease) (-1., 1.)],
-            [elevator => (elevator_increase, elevator_decrease) (-1., 1.)],
-            [rudder => (rudder_increase, rudder_decrease) (-1., 1.)],
+        if impact == Some(Impact::Crash) {
+            self.crashed = true;
+            self.engine.stop();
+            self.autopilot.disengage();
+            body.set_linvel(Vector::zero(), false);
+            body.set_angvel(Vector::zero(), false);
+            body.set_body_type(RigidBodyType::Fixed, false);
         }
+    }
 
-        let invrot = body.rotation().inverse();
-        for wing in &mut self.wings {
-            let control = match wing.control {
-                Control::Aileron => self.aileron,
-                Control::Elevator => self.elevator,
-                Control::Rudder => self.rudder,
-                _ => 0.,
-            };
-            let wing_rot;
-            let wing_invrot;
-            if control != 0. {
-                wing_rot = body.rotation() * Rotation3::new(wing.axis * wing.sensitivity * control);
-                wing_invrot = wing_rot.inverse();
-            } else {
-                wing_rot = *body.rotation();
-                wing_invrot = invrot;
-            }
-            let linvel = wing_invrot.transform_vector(&body.linvel());
-            let drag = wing.aero * linvel;
-            let global_drag = wing_rot.transform_vector(&drag);
-            body.apply_impulse(global_drag, true);
-            let relpos = body.rotation().transform_vector(&wing.pos);
-            let torque = global_drag.cross(&relpos);
-            body.apply_torque_impulse(torque, true);
-            wing.force = global_drag;
+    pub fn flight_state(&self, rigid_body_set: &RigidBodySet, wind: &WindField) -> FlightState {
+        flight_state(&rigid_body_set[self.body_handle], wind)
+    }
+
+    /// Named values of the vehicle state for the widgets of the UI, `None` if a value has
+    /// nothing to show at the moment.
+    pub fn telemetry(&self) -> Vec<(&'static str, Option<f32>)> {
+        // The contact points are not recorded, so a replay only shows the gear contact.
+        let contact = self
+            .contacts
+            .worst()
+            .or(self.touching_ground.then_some(ContactKind::Gear));
+        let autopilot = &self.autopilot;
+        vec![
+            ("thrust", Some(self.controls.thrust)),
+            ("n1", Some(self.engine.n1)),
+            ("aileron", Some(self.controls.aileron)),
+            ("elevator", Some(self.controls.elevator)),
+            ("rudder", Some(self.controls.rudder)),
+            ("brake_left", Some(self.controls.brake_left)),
+            ("brake_right", Some(self.controls.brake_right)),
+            ("aileron_trim", Some(self.trim.aileron)),
+            ("elevator_trim", Some(self.trim.elevator)),
+            ("rudder_trim", Some(self.trim.rudder)),
+            ("gear", Some(self.landing_gear.position())),
+            ("fuel", Some(self.loading.fuel())),
+            ("load_factor", Some(self.load_factor)),
+            // The number of the contact kind in the order of severity, 0 without contact
+            (
+                "contact",
+                Some(contact.map_or(0., |kind| kind as u8 as f32 + 1.)),
+            ),
+            // The number of the engaged mode of each autopilot axis, 0 if disengaged
+            (
+                "autopilot_lateral",
+                Some(autopilot.lateral.map_or(0., |mode| match mode {
+                    LateralMode::WingsLeveler => 1.,
+                    LateralMode::Heading(_) => 2.,
+                    LateralMode::Nav => 3.,
+                })),
+            ),
+            (
+                "autopilot_vertical",
+                Some(autopilot.vertical.map_or(0., |mode| match mode {
+                    VerticalMode::Altitude(_) => 1.,
+                    VerticalMode::VerticalSpeed(_) => 2.,
+                    VerticalMode::Nav => 3.,
+                })),
+            ),
+            (
+                "autothrottle",
+                Some(if autopilot.autothrottle.is_some() {
+                    1.
+                } else {
+                    0.
+                }),
+            ),
+            // The heading [deg], altitude [m], vertical speed [m/s] and airspeed [m/s] that
+            // the engaged modes hold
+            (
+                "autopilot_heading",
+                match autopilot.lateral {
+                    Some(LateralMode::Heading(heading)) => Some(heading),
+                    _ => None,
+                },
+            ),
+            (
+                "autopilot_altitude",
+                match autopilot.vertical {
+                    Some(VerticalMode::Altitude(altitude)) => Some(altitude),
+                    _ => None,
+                },
+            ),
+            (
+                "autopilot_vertical_speed",
+                match autopilot.vertical {
+                    Some(VerticalMode::VerticalSpeed(vertical_speed)) => Some(vertical_speed),
+                    _ => None,
+                },
+            ),
+            ("autopilot_airspeed", autopilot.autothrottle),
+            // The progress along the active leg of the flight plan
+            (
+                "nav_progress",
+                autopilot.guidance.map(|guidance| {
+                    (1. - guidance.distance_to_go / guidance.leg_length).clamp(0., 1.)
+                }),
+            ),
+            // The distance [km] and the estimated time [s] to the active waypoint
+            (
+                "distance_to_go",
+                autopilot
+                    .guidance
+                    .map(|guidance| guidance.distance_to_go * 1e-3),
+            ),
+            ("eta", autopilot.guidance.and_then(|guidance| guidance.eta)),
+        ]
+    }
+
+    /// Breaks down the forces on the aircraft in the body frame, with the wing forces of the
+    /// last physics step and the airflow of the current state.
+    pub fn aero_debug(&self, rigid_body_set: &RigidBodySet, wind: &WindField) -> AeroDebug {
+        let body = &rigid_body_set[self.body_handle];
+        let rotation = body.rotation();
+        let center_of_mass = self.mass_properties().local_com;
+        let wings: Vec<_> = self
+            .wings
+            .iter()
+            .map(|wing| {
+                let wing_pos = body.position() * Point::from(wing.pos);
+                let airvel = body.velocity_at_point(&wing_pos) - wind.velocity_at(&wing_pos);
+                let airflow = -rotation.inverse_transform_vector(&airvel);
+                let angle = wing.sensitivity * wing.deflection(&self.controls, &self.trim);
+                // The angle of attack in the frame of the deflected surface, as the airfoil
+                // sees it
+                let local = UnitQuaternion::from_scaled_axis(wing.axis * angle)
+                    .inverse_transform_vector(&-airflow);
+                let force = rotation.inverse_transform_vector(&wing.force);
+                WingDebug {
+                    name: wing.name.clone(),
+                    airflow,
+                    angle_of_attack: (-local.dot(&wing.normal())).atan2(-local.z).to_degrees(),
+                    deflection: angle.to_degrees(),
+                    force,
+                    torque: (wing.pos - center_of_mass.coords).cross(&force),
+                }
+            })
+            .collect();
+        let force: Vector<f32> = wings.iter().map(|wing| wing.force).sum();
+        let torque: Vector<f32> = wings.iter().map(|wing| wing.torque).sum();
+        // The point of the line of action closest to the center of gravity
+        let center_of_pressure =
+            (0. < force.norm_squared()).then(|| force.cross(&torque) / force.norm_squared());
+
+        let airvel = body.linvel() - wind.velocity_at(&Point::from(*body.translation()));
+        let airflow = -rotation.inverse_transform_vector(&airvel);
+        let drag = if 0. < airflow.norm() {
+            let direction = airflow.normalize();
+            direction * force.dot(&direction)
+        } else {
+            Vector::zero()
+        };
+        let atmosphere = Atmosphere::at_altitude(body.translation().y);
+        let mach = airvel.norm() / atmosphere.speed_of_sound;
+        let thrust = self.engine.thrust(atmosphere.density_ratio(), mach);
+        AeroDebug {
+            wings,
+            force,
+            torque,
+            center_of_pressure,
+            thrust: vector![0., 0., -thrust],
+            gravity: rotation.inverse_transform_vector(&GRAVITY) * self.mass_properties().mass(),
+            drag,
+            center_of_mass: center_of_mass.coords,
         }
-        if self.touching_ground {
-            let torque = Vector3::new(0., 300. * self.thrust * self.rudder, 0.);
-            let global_torque = body.rotation().transform_vector(&torque);
-            body.apply_torque_impulse(global_torque, true);
+    }
+
+    /// Mass properties of the airframe with the fuel and payload
+    pub fn mass_properties(&self) -> MassProperties {
+        self.airframe + self.loading.mass_properties()
+    }
+
+    /// Returns the aerodynamic force and its moment about the center of gravity in world
+    /// coordinates, and whether any wing stalls, for a body in the rotation moving with the
+    /// velocity relative to the air without rotating.
+    pub fn aerodynamics(
+        &self,
+        rotation: &UnitQuaternion<f32>,
+        velocity: &Vector<f32>,
+        controls: &ControlInput,
+        trim: &Trim,
+        atmosphere: &Atmosphere,
+    ) -> (Vector<f32>, Vector<f32>, bool) {
+        let center_of_mass = self.mass_properties().local_com;
+        let mut force = Vector::zero();
+        let mut moment = Vector::zero();
+        let mut any_stalled = false;
+        for wing in self.wings.iter().filter(|wing| 0. < wing.health) {
+            let mut stalled = false;
+            let wing_force = wing.force(
+                rotation,
+                velocity,
+                wing.deflection(controls, trim),
+                atmosphere,
+                &mut stalled,
+            );
+            force += wing_force;
+            moment += (rotation * (wing.pos - center_of_mass.coords)).cross(&wing_force);
+            any_stalled |= stalled;
         }
-        let impulse = Vector3::new(0., 0., -500. * self.thrust);
-        let forward_impulse = body.rotation().transform_vector(&impulse);
-        body.apply_impulse(forward_impulse, true);
+        (force, moment, any_stalled)
     }
 
-    pub fn transform(&self, rigid_body_set: &RigidBodySet) -> Mat4 {
-        let ball_body = &rigid_body_set[self.body_handle];
-        let trans_vec = ball_body.translation();
+    /// Puts the aircraft in the trimmed level flight at the altitude, heading north, with
+    /// the trim tabs holding the elevator and the gear retracted.
+    pub fn set_trimmed(&mut self, physics: &mut PhysicsSet, state: &TrimState, altitude: f32) {
+        let body = &mut physics.rigid_body_set[self.body_handle];
+        let rotation = UnitQuaternion::from_axis_angle(&Vector::x_axis(), state.pitch);
+        body.set_position(
+            Isometry::from_parts(vector![0., altitude, 0.].into(), rotation),
+            true,
+        );
+        body.set_linvel(vector![0., 0., -state.speed], true);
+        body.set_angvel(Vector::zero(), true);
+        self.prev_position = None;
+        self.load_factor = 1.;
+        self.prev_linvel = None;
+        self.controls = ControlInput {
+            thrust: state.throttle,
+            ..ControlInput::default()
+        };
+        self.command = self.controls;
+        self.trim = Trim {
+            elevator: state.elevator,
+            ..Trim::default()
+        };
+        self.engine.spool(state.throttle);
+        self.landing_gear.stow();
+    }
+
+    /// Health of each wing, 1 for intact and 0 for torn off
+    pub fn wing_healths(&self) -> impl Iterator<Item = f32> + '_ {
+        self.wings.iter().map(|wing| wing.health)
+    }
+
+    /// Returns the transformation for rendering, interpolated by `alpha` between the states
+    /// before and after the last physics step.
+    pub fn transform(&self, rigid_body_set: &RigidBodySet, alpha: f32) -> Mat4 {
+        let body = &rigid_body_set[self.body_handle];
+        let position = match self.prev_position {
+            Some(prev) => prev.lerp_slerp(body.position(), alpha),
+            None => *body.position(),
+        };
+        let trans_vec = position.translation.vector;
         let trans = Mat4::from_translation(Vec3::new(trans_vec.x, trans_vec.y, trans_vec.z));
-        let rot = ball_body.rotation();
+        let rot = position.rotation;
         let rv = rot.vector();
         let rot = Mat4::from(Quat::new(rot.w, rv.x, rv.y, rv.z));
         trans * rot
@@ -231,45 +498,95 @@ impl Vehicle {
         let body = &mut rigid_body_set[self.body_handle];
         body.set_position(Isometry::new(VEHICLE_POSITION, Vector3::zero()), true);
         body.set_rotation(UnitQuaternion::identity(), true);
+        body.set_linvel(Vector::zero(), true);
+        body.set_angvel(Vector::zero(), true);
+        body.set_body_type(RigidBodyType::Dynamic, true);
+        self.prev_position = None;
+        self.crashed = false;
+        // Refuel and restart the engine, which may have flamed out or stopped in a crash.
+        self.engine = self.initial_engine.clone();
+        self.loading = self.initial_loading.clone();
+        self.impact = None;
+        self.autopilot.disengage();
+        if let Some(plan) = &mut self.autopilot.flight_plan {
+            plan.restart();
+        }
+        self.sink_rate = 0.;
+        self.load_factor = 1.;
+        self.prev_linvel = None;
+        self.in_contact = false;
+        for wing in &mut self.wings {
+            wing.health = 1.;
+            wing.stalled = false;
+        }
     }
 
-    pub fn _contact(&mut self, contact: ContactForceEvent) {
-        if 0. < contact.total_force_magnitude {
-            self.touching_ground = true;
-        } else {
-            self.touching_ground = false;
+    /// Returns the state of this vehicle for the flight data recorder.
+    pub fn record(&self, rigid_body_set: &RigidBodySet, time: f64) -> FlightRecord {
+        let body = &rigid_body_set[self.body_handle];
+        FlightRecord {
+            time,
+            position: *body.position(),
+            linvel: *body.linvel(),
+            angvel: *body.angvel(),
+            controls: self.controls,
+            touching_ground: self.touching_ground,
+            airframe_force: self.contacts.airframe_force(),
+            n1: self.engine.n1,
+            fuel: self.engine.fuel,
+            wing_forces: self.wings.iter().map(|wing| wing.force).collect(),
         }
     }
 
-    pub fn collide(&mut self, collision: CollisionEvent) {
-        match collision {
-            CollisionEvent::Started(h1, h2, _) | CollisionEvent::Stopped(h1, h2, _) => {
-                if h1 != self.collider_handle && h2 != self.collider_handle {
-                    return;
-                }
-            }
+    /// Puts this vehicle in the recorded state at `next`, so that `transform` interpolates
+    /// from `prev`. The physics should not be stepped during a replay.
+    pub fn apply_replay(
+        &mut self,
+        rigid_body_set: &mut RigidBodySet,
+        prev: &FlightRecord,
+        next: &FlightRecord,
+    ) {
+        let body = &mut rigid_body_set[self.body_handle];
+        body.set_position(next.position, false);
+        body.set_linvel(next.linvel, false);
+        body.set_angvel(next.angvel, false);
+        self.prev_position = Some(prev.position);
+        if prev.time < next.time {
+            let acceleration = (next.linvel - prev.linvel) / (next.time - prev.time) as f32;
+            let up = prev.position.rotation * Vector::y();
+            self.load_factor = load_factor(&acceleration, &GRAVITY, &up);
         }
-        if collision.started() {
-            self.touching_ground = true;
-        } else if collision.stopped() {
-            self.touching_ground = false;
+        self.controls = prev.controls;
+        self.command = prev.controls;
+        self.touching_ground = prev.touching_ground;
+        self.contacts.clear();
+        self.engine.n1 = prev.n1;
+        self.engine.fuel = prev.fuel;
+        for (wing, force) in self.wings.iter_mut().zip(&prev.wing_forces) {
+            wing.force = *force;
         }
     }
 
+    pub fn collide(&mut self, collision: CollisionEvent) {
+        self.contacts.collide(collision);
+        self.touching_ground = self.contacts.touching() || self.landing_gear.weight_on_wheels();
+    }
+
     pub fn load_model(
         mut model_src: &[u8],
+        scale: f32,
         context: &Context,
     ) -> Result<Vec<Gm<Mesh, PhysicalMaterial>>, Box<dyn Error>> {
-        let models = load_mqo_scale(&mut model_src, None, 1. / 30.0, &|| ())?;
+        let models = load_mqo_scale(&mut model_src, None, scale, &|| ())?;
         // let models = vec![uv_sphere(10)];
         let meshes: Vec<_> = models
             .iter()
             .take(1)
             .map(|model| {
                 let mut obj = Gm::new(
-                    Mesh::new(&context, model),
+                    Mesh::new(context, model),
                     PhysicalMaterial::new(
-                        &context,
+                        context,
                         &CpuMaterial {
                             roughness: 0.6,
                             metallic: 0.6,
@@ -289,11 +606,55 @@ impl Vehicle {
     }
 }
 
+/// Aerodynamic state of a wing for the debug overlay, in the body frame
+pub(crate) struct WingDebug {
+    pub name: String,
+    /// Velocity of the air relative to the wing [m/s]
+    pub airflow: Vector<f32>,
+    /// Angle of attack of the deflected surface, around its normal [deg]
+    pub angle_of_attack: f32,
+    /// Deflection of the control surface, including the trim [deg]
+    pub deflection: f32,
+    /// Force of the last physics step [N]
+    pub force: Vector<f32>,
+    /// Moment of the force about the center of gravity [N m]
+    pub torque: Vector<f32>,
+}
+
+/// Forces on the aircraft for the debug overlay, in the body frame
+pub(crate) struct AeroDebug {
+    pub wings: Vec<WingDebug>,
+    /// Total aerodynamic force of the wings [N]
+    pub force: Vector<f32>,
+    /// Total moment of the wings about the center of gravity [N m]
+    pub torque: Vector<f32>,
+    /// The point on the line of action of the total aerodynamic force closest to the center
+    /// of gravity, relative to it, or `None` without force [m]
+    pub center_of_pressure: Option<Vector<f32>>,
+    pub thrust: Vector<f32>,
+    /// Weight of the aircraft with the fuel and payload [N]
+    pub gravity: Vector<f32>,
+    /// Component of the total aerodynamic force along the airflow [N]
+    pub drag: Vector<f32>,
+    /// Center of gravity in the body frame [m]
+    pub center_of_mass: Vector<f32>,
+}
+
 pub(crate) struct ControlMesh {
     pub surface: Gm<Mesh, ColorMaterial>,
     pub arrow: Gm<Mesh, ColorMaterial>,
     pub transform: Mat4,
-    pub pos: Vec3,
+    /// Position of the wing's center, the translation of `transform`
+    position: Mat4,
+    /// Orientation of the undeflected surface, the rotation of `transform`
+    orientation: Mat4,
+}
+
+impl ControlMesh {
+    /// Returns the transformation of the surface deflected by the rotation about its center.
+    pub fn deflected(&self, deflection: &Mat4) -> Mat4 {
+        self.position * deflection * self.orientation
+    }
 }
 
 impl Vehicle {
@@ -303,9 +664,9 @@ impl Vehicle {
             .map(|wing| {
                 let surface_mesh = TriMesh::square();
                 let mut surface = Gm::new(
-                    Mesh::new(&context, &surface_mesh),
+                    Mesh::new(context, &surface_mesh),
                     ColorMaterial::new(
-                        &context,
+                        context,
                         &CpuMaterial {
                             albedo: Srgba {
                                 r: 0,
@@ -318,10 +679,10 @@ impl Vehicle {
                     ),
                 );
                 let arrow_mesh = TriMesh::arrow(0.8, 0.5, 8);
-                let mut arrow = Gm::new(
-                    Mesh::new(&context, &arrow_mesh),
+                let arrow = Gm::new(
+                    Mesh::new(context, &arrow_mesh),
                     ColorMaterial::new(
-                        &context,
+                        context,
                         &CpuMaterial {
                             roughness: 0.6,
                             metallic: 0.6,
@@ -346,18 +707,39 @@ impl Vehicle {
                     Control::Rudder => Mat4::from_angle_y(Deg(90.)),
                     _ => Mat4::from_scale(1.),
                 };
-                let transform = Mat4::from_translation(pos2) * rot;
+                let position = Mat4::from_translation(pos2);
+                let transform = position * rot;
                 surface.set_transformation(transform);
                 ControlMesh {
                     surface,
                     arrow,
                     transform,
-                    pos: pos2,
+                    position,
+                    orientation: rot,
                 }
             })
             .collect()
     }
 
+    /// Rotation of each wing by the deflection of its control surface in the body frame, the
+    /// same as in the aerodynamics.
+    pub fn wing_deflections(&self) -> impl Iterator<Item = Mat4> + '_ {
+        self.wings.iter().map(|wing| {
+            let angle = wing.sensitivity * wing.deflection(&self.controls, &self.trim);
+            let v = wing.axis * angle;
+            let v = Vec3::new(v.x, v.y, v.z);
+            if v.is_zero() {
+                Mat4::from_scale(1.)
+            } else {
+                Mat4::from_axis_angle(v.normalize(), Rad(v.magnitude()))
+            }
+        })
+    }
+
+    pub fn wing_names(&self) -> impl Iterator<Item = &str> {
+        self.wings.iter().map(|wing| wing.name.as_str())
+    }
+
     pub fn wing_forces<'a>(&'a self) -> impl Iterator<Item = Vec3> + 'a {
         self.wings.iter().map(|wing| {
             let v = wing.force;
@@ -369,34 +751,138 @@ impl Vehicle {
 
 /// Control surface definition.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum Control {
+pub(crate) enum Control {
     None,
     Aileron,
     Elevator,
     Rudder,
 }
 
+impl std::str::FromStr for Control {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "none" => Self::None,
+            "aileron" => Self::Aileron,
+            "elevator" => Self::Elevator,
+            "rudder" => Self::Rudder,
+            _ => {
+                return Err(format!(
+                    "unknown control \"{s}\", expected one of none, aileron, elevator or rudder"
+                ))
+            }
+        })
+    }
+}
+
 /// An internal structure that representing a wing and its parameters.
 struct Wing {
-    /// Position of the wing's center, relative to center of mass
+    /// Position of the wing's center in the body frame
     pos: Vector3<f32>,
     /// The aerodynamic tensor, defines how force is applied to the wing.
     aero: Matrix<f32>,
-    #[allow(dead_code)]
-    /// Name of the wing, just for debugging
+    /// Name of the wing, for the flight data and the debug overlay
     name: String,
     control: Control,
     /// The aerodynamic tensor is rotated around this axis if this control surface is manipulated.
     axis: Vector<f32>,
     /// Sensitivity of this control surface when this surface is manipulated.
     sensitivity: f32,
+    airfoil: Option<Airfoil>,
+    /// Whether the flow over the airfoil is separated
+    stalled: bool,
+    /// Structural integrity, which scales the force, 1 for intact and 0 for torn off
+    health: f32,
     /// Cached force from previous frame for visualization
     force: Vector<f32>,
 }
 
-const MAIN_WING_TENSOR: Matrix<f32> = Matrix::new(-0.1, 0., 0., 0., -6.5, 0., 0., -0.6, -0.025);
-const TAIL_WING_TENSOR: Matrix<f32> = Matrix::new(-0.1, 0., 0., 0., -1.9, 0., 0., -0.0, -0.015);
-const RUDDER_TENSOR: Matrix<f32> = Matrix::new(-1.5, 0., 0., 0., -0.05, 0., 0., 0., -0.015);
+/// Returns the load factor of the acceleration along the up axis, in units of the gravity.
+fn load_factor(acceleration: &Vector<f32>, gravity: &Vector<f32>, up: &Vector<f32>) -> f32 {
+    (acceleration - gravity).dot(up) / gravity.norm()
+}
+
+fn flight_state(body: &RigidBody, wind: &WindField) -> FlightState {
+    let rotation = body.rotation();
+    let forward = rotation * -Vector::z();
+    let right = rotation * Vector::x();
+    let up = rotation * Vector::y();
+    let airvel = body.linvel() - wind.velocity_at(&Point::from(*body.translation()));
+    let local_airvel = rotation.inverse_transform_vector(&airvel);
+    let sideslip = if 0. < airvel.norm() {
+        (local_airvel.x / airvel.norm()).clamp(-1., 1.).asin()
+    } else {
+        0.
+    };
+    let velocity = body.linvel();
+    let local_velocity = rotation.inverse_transform_vector(velocity);
+    FlightState {
+        position: *body.translation(),
+        ground_speed: velocity.x.hypot(velocity.z),
+        altitude: body.translation().y,
+        vertical_speed: body.linvel().y,
+        airspeed: airvel.norm(),
+        heading: forward.x.atan2(-forward.z).to_degrees().rem_euclid(360.),
+        pitch: forward.y.clamp(-1., 1.).asin().to_degrees(),
+        roll: (-right.y).atan2(up.y).to_degrees(),
+        turn_rate: -body.angvel().y.to_degrees(),
+        sideslip: sideslip.to_degrees(),
+        angle_of_attack: (-local_airvel.y).atan2(-local_airvel.z).to_degrees(),
+        flight_path: [
+            local_velocity.x.atan2(-local_velocity.z).to_degrees(),
+            local_velocity.y.atan2(-local_velocity.z).to_degrees(),
+        ],
+    }
+}
+
+impl Wing {
+    /// Normal of the surface in the wing frame, the direction of the lift at zero angle of
+    /// attack: that of the airfoil, or the plane that the control surface is drawn in.
+    fn normal(&self) -> Vector<f32> {
+        match (&self.airfoil, self.control) {
+            (Some(airfoil), _) => airfoil.normal,
+            (None, Control::Rudder) => Vector::x(),
+            (None, _) => Vector::y(),
+        }
+    }
+
+    /// Returns the deflection of this wing's control surface in the range [-1, 1], offset
+    /// by the trim tab.
+    fn deflection(&self, controls: &ControlInput, trim: &Trim) -> f32 {
+        match self.control {
+            Control::Aileron => controls.aileron + trim.aileron,
+            Control::Elevator => controls.elevator + trim.elevator,
+            Control::Rudder => controls.rudder + trim.rudder,
+            Control::None => 0.,
+        }
+        .clamp(-1., 1.)
+    }
+
+    /// Returns the aerodynamic force in world coordinates, given the rotation of the body,
+    /// the velocity of the wing relative to the air in world coordinates and the deflection
+    /// of the control surface. `stalled` is the state of the flow separation over the airfoil.
+    fn force(
+        &self,
+        rotation: &UnitQuaternion<f32>,
+        airvel: &Vector<f32>,
+        deflection: f32,
+        atmosphere: &Atmosphere,
+        stalled: &mut bool,
+    ) -> Vector<f32> {
+        let wing_rot =
+            rotation * UnitQuaternion::from_scaled_axis(self.axis * self.sensitivity * deflection);
+        let linvel = wing_rot.inverse_transform_vector(airvel);
+        let density_ratio = atmosphere.density_ratio();
+        let force = if let Some(airfoil) = &self.airfoil {
+            airfoil.force(&linvel, atmosphere.density, stalled)
+                + self.aero * airfoil.spanwise(&linvel) * density_ratio
+        } else {
+            self.aero * linvel * density_ratio
+        };
+        wing_rot.transform_vector(&force) * self.health
+    }
+}
 
 fn _quatrotquat(this: &Quat, v: &Vec3) -> Quat {
     let q = Quat::from_sv(0., *v);
@@ -404,3 +890,123 @@ fn _quatrotquat(this: &Quat, v: &Vec3) -> Quat {
     qr += *this;
     qr.normalize()
 }
+
+#[test]
+fn test_vehicle_controls() {
+    use crate::{aircraft::DEFAULT_AIRCRAFT, physics::PhysicsSet};
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let mut physics = PhysicsSet::new(100.);
+    let mut vehicle = Vehicle::new(physics.new_body(VEHICLE_POSITION, &aircraft), &aircraft);
+    let command = ControlInput {
+        thrust: 1.,
+        aileron: -1.,
+        elevator: 0.5,
+        rudder: 0.,
+        ..ControlInput::default()
+    };
+    vehicle.update(0.25, &mut physics, &command, &WindField::default());
+    assert_eq!(
+        vehicle.controls,
+        ControlInput {
+            thrust: 0.25,
+            aileron: -0.25,
+            elevator: 0.25,
+            rudder: 0.,
+            ..ControlInput::default()
+        }
+    );
+    // The engine pushes the airframe forward, towards -z.
+    assert!(physics.rigid_body_set[vehicle.body_handle].linvel().z < 0.);
+
+    // Burnt fuel lightens the airframe.
+    physics.step();
+    assert!(vehicle.engine.fuel < aircraft.engine.fuel);
+    let mass = physics.rigid_body_set[vehicle.body_handle].mass();
+    assert!((mass - aircraft.mass - vehicle.engine.fuel).abs() < 1e-3);
+
+    // The reset refuels and restarts the engine.
+    vehicle.engine.stop();
+    vehicle.reset(&mut physics.rigid_body_set);
+    assert_eq!(vehicle.engine.fuel, aircraft.engine.fuel);
+    assert_eq!(vehicle.loading.fuel(), aircraft.engine.fuel);
+    assert_eq!(vehicle.engine.state, crate::engine::EngineState::Running);
+
+    // The telemetry shows the values that the engaged autopilot modes hold.
+    vehicle
+        .autopilot
+        .engage_lateral(LateralMode::Heading(270.), &vehicle.controls);
+    vehicle.autopilot.autothrottle = Some(150.);
+    let telemetry = vehicle.telemetry();
+    let value = |name: &str| telemetry.iter().find(|(n, _)| *n == name).unwrap().1;
+    assert_eq!(value("autopilot_heading"), Some(270.));
+    assert_eq!(value("autopilot_altitude"), None);
+    assert_eq!(value("autopilot_airspeed"), Some(150.));
+}
+
+#[test]
+fn test_roll_damping() {
+    use crate::{aircraft::DEFAULT_AIRCRAFT, physics::PhysicsSet};
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let mut physics = PhysicsSet::new(100.);
+    physics.gravity = Vector::zeros();
+    let mut vehicle = Vehicle::new(physics.new_body(VEHICLE_POSITION, &aircraft), &aircraft);
+    let roll_rate = 1.;
+    let body = &mut physics.rigid_body_set[vehicle.body_handle];
+    body.set_linvel(vector![0., 0., -100.], true);
+    body.set_angvel(vector![0., 0., roll_rate], true);
+
+    let delta_time = physics.integration_parameters.dt as f64;
+    let mut roll_rates = vec![roll_rate];
+    for _ in 0..2 {
+        for _ in 0..(0.5 / delta_time).round() as usize {
+            vehicle.update(
+                delta_time,
+                &mut physics,
+                &ControlInput::default(),
+                &WindField::default(),
+            );
+            physics.step();
+        }
+        roll_rates.push(physics.rigid_body_set[vehicle.body_handle].angvel().z);
+    }
+
+    // The roll rate decays exponentially without changing its sign.
+    let decay0 = roll_rates[1] / roll_rates[0];
+    let decay1 = roll_rates[2] / roll_rates[1];
+    assert!(0. < decay0 && decay0 < 0.9, "{roll_rates:?}");
+    assert!((decay0 - decay1).abs() < 0.02, "{roll_rates:?}");
+}
+
+#[test]
+fn test_aero_debug() {
+    use crate::{aircraft::DEFAULT_AIRCRAFT, physics::PhysicsSet};
+    let aircraft = AircraftDef::load(DEFAULT_AIRCRAFT).unwrap();
+    let mut physics = PhysicsSet::new(100.);
+    let mut vehicle = Vehicle::new(physics.new_body(VEHICLE_POSITION, &aircraft), &aircraft);
+    physics.rigid_body_set[vehicle.body_handle].set_linvel(vector![0., 0., -150.], true);
+    let wind = WindField::default();
+    vehicle.update(0.01, &mut physics, &ControlInput::default(), &wind);
+
+    let debug = vehicle.aero_debug(&physics.rigid_body_set, &wind);
+    assert_eq!(debug.wings.len(), aircraft.wings.len());
+    // Every wing sees the airflow against the velocity of the airframe, but for the small
+    // pitch rate of the step.
+    let velocity = *physics.rigid_body_set[vehicle.body_handle].linvel();
+    for wing in &debug.wings {
+        assert!((wing.airflow + velocity).norm() < 0.1);
+    }
+    let torque: Vector<f32> = debug.wings.iter().map(|wing| wing.torque).sum();
+    assert!((torque - debug.torque).norm() <= 1e-3 * debug.torque.norm());
+
+    // The force at the center of pressure has the moment of the wings, but for the part
+    // along the force, which no point of application can produce.
+    let cp = debug.center_of_pressure.unwrap();
+    let direction = debug.force.normalize();
+    let moment = debug.torque - direction * debug.torque.dot(&direction);
+    assert!((cp.cross(&debug.force) - moment).norm() <= 1e-3 * debug.torque.norm());
+
+    // The drag points aft along the airflow, and the airframe is level.
+    assert!(0. < debug.drag.z && debug.drag.x.abs() < 1e-3 && debug.drag.y.abs() < 1e-3);
+    let weight = vehicle.mass_properties().mass() * GRAVITY.y;
+    assert!((debug.gravity - vector![0., weight, 0.]).norm() < 1e-3 * weight.abs());
+}
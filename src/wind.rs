@@ -0,0 +1,350 @@
+//! Wind field with a steady wind, altitude wind shear, discrete gusts and turbulence.
+//!
+//! The turbulence is a frozen field of random Fourier modes whose amplitudes follow the
+//! Dryden or von Kármán spectrum, advected by the steady wind. Since the field is a
+//! function of the position, each wing samples a different airflow.
+
+use std::{error::Error, path::Path};
+
+use rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::xor128::Xor128;
+
+/// Number of Fourier modes of the turbulence field
+const TURBULENCE_MODES: usize = 64;
+
+/// Range of the sampled spatial frequencies, relative to the inverse scale length
+const MIN_FREQUENCY: f32 = 0.01;
+const MAX_FREQUENCY: f32 = 100.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TurbulenceModel {
+    Dryden,
+    VonKarman,
+}
+
+impl TurbulenceModel {
+    /// One-sided spectral density of a velocity component at the spatial frequency `omega`
+    /// [rad/m] with the scale length `scale`, normalized to unit variance.
+    fn spectrum(&self, omega: f32, scale: f32) -> f32 {
+        let l_omega = scale * omega;
+        match self {
+            Self::Dryden => 2. * scale / std::f32::consts::PI / (1. + l_omega.powi(2)),
+            Self::VonKarman => {
+                2. * scale / std::f32::consts::PI / (1. + (1.339 * l_omega).powi(2)).powf(5. / 6.)
+            }
+        }
+    }
+}
+
+/// A single Fourier mode of the turbulence field.
+struct TurbulenceMode {
+    wavevector: Vector<f32>,
+    /// Velocity amplitude, perpendicular to the wavevector
+    amplitude: Vector<f32>,
+    phase: f32,
+}
+
+/// Discrete "1-cosine" gust.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Gust {
+    /// Start time [s]
+    pub start: f64,
+    /// Duration [s]
+    pub duration: f64,
+    /// Peak wind velocity [m/s]
+    pub amplitude: [f32; 3],
+}
+
+impl Gust {
+    fn velocity(&self, time: f64) -> Vector<f32> {
+        let t = (time - self.start) / self.duration;
+        if !(0. ..=1.).contains(&t) {
+            return Vector::zeros();
+        }
+        let factor = 0.5 * (1. - (2. * std::f64::consts::PI * t).cos());
+        Vector::from(self.amplitude) * factor as f32
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TurbulenceDef {
+    model: TurbulenceModel,
+    /// RMS velocity of each component [m/s]
+    intensity: f32,
+    /// Scale length [m]
+    scale_length: f32,
+    #[serde(default)]
+    seed: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WindDef {
+    #[serde(default)]
+    steady: [f32; 3],
+    #[serde(default = "default_reference_altitude")]
+    reference_altitude: f32,
+    #[serde(default)]
+    shear_exponent: f32,
+    #[serde(default)]
+    gusts: Vec<Gust>,
+    turbulence: Option<TurbulenceDef>,
+}
+
+fn default_reference_altitude() -> f32 {
+    10.
+}
+
+pub(crate) struct WindField {
+    /// Steady wind velocity at the reference altitude [m/s]
+    steady: Vector<f32>,
+    /// Altitude at which the wind shear profile has the steady wind velocity [m]
+    reference_altitude: f32,
+    /// Power law exponent of the wind shear profile, 0 for a uniform wind
+    shear_exponent: f32,
+    gusts: Vec<Gust>,
+    turbulence: Vec<TurbulenceMode>,
+    /// Elapsed simulation time [s]
+    time: f64,
+}
+
+impl Default for WindField {
+    /// Calm air
+    fn default() -> Self {
+        Self {
+            steady: Vector::zeros(),
+            reference_altitude: default_reference_altitude(),
+            shear_exponent: 0.,
+            gusts: vec![],
+            turbulence: vec![],
+            time: 0.,
+        }
+    }
+}
+
+impl WindField {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read weather file {}: {e}", path.display()))?;
+        Self::parse(&src).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let def: WindDef = toml::from_str(src)?;
+        if !def.steady.iter().all(|v| v.is_finite()) {
+            return Err("steady wind must be finite".into());
+        }
+        if !(def.reference_altitude.is_finite() && 0. < def.reference_altitude) {
+            return Err("reference_altitude must be positive".into());
+        }
+        if !(def.shear_exponent.is_finite() && 0. <= def.shear_exponent) {
+            return Err("shear_exponent must be non-negative".into());
+        }
+        if def
+            .gusts
+            .iter()
+            .any(|gust| !(gust.duration.is_finite() && 0. < gust.duration))
+        {
+            return Err("gust duration must be positive".into());
+        }
+        if def
+            .gusts
+            .iter()
+            .any(|gust| !(gust.start.is_finite() && gust.amplitude.iter().all(|v| v.is_finite())))
+        {
+            return Err("gust start and amplitude must be finite".into());
+        }
+        let mut ret = Self {
+            steady: def.steady.into(),
+            reference_altitude: def.reference_altitude,
+            shear_exponent: def.shear_exponent,
+            gusts: def.gusts,
+            ..Self::default()
+        };
+        if let Some(turbulence) = def.turbulence {
+            let valid = turbulence.scale_length.is_finite()
+                && 0. < turbulence.scale_length
+                && turbulence.intensity.is_finite()
+                && 0. <= turbulence.intensity;
+            if !valid {
+                return Err(
+                    "turbulence scale_length must be positive and intensity non-negative".into(),
+                );
+            }
+            ret.set_turbulence(
+                turbulence.model,
+                turbulence.intensity,
+                turbulence.scale_length,
+                turbulence.seed,
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Generates the turbulence field from the random number generator seeded by `seed`,
+    /// so that the same seed reproduces the same turbulence.
+    pub fn set_turbulence(
+        &mut self,
+        model: TurbulenceModel,
+        intensity: f32,
+        scale_length: f32,
+        seed: u32,
+    ) {
+        use std::f32::consts::PI;
+        let mut rng = Xor128::new(seed);
+        let mut rand = || rng.next() as f32;
+
+        // Logarithmically spaced spatial frequencies
+        let min = (MIN_FREQUENCY / scale_length).ln();
+        let max = (MAX_FREQUENCY / scale_length).ln();
+        let step = (max - min) / TURBULENCE_MODES as f32;
+
+        let mut modes: Vec<_> = (0..TURBULENCE_MODES)
+            .map(|i| {
+                let omega = (min + (i as f32 + 0.5) * step).exp();
+                let band = omega * step;
+                let magnitude = (2. * model.spectrum(omega, scale_length) * band).sqrt();
+
+                // Uniformly distributed direction of the wavevector
+                let z = 2. * rand() - 1.;
+                let phi = 2. * PI * rand();
+                let r = (1. - z * z).sqrt();
+                let direction = vector![r * phi.cos(), r * phi.sin(), z];
+
+                // Velocity perpendicular to the wavevector keeps the field divergence free
+                let tangent = direction
+                    .cross(&if z.abs() < 0.9 {
+                        Vector::z()
+                    } else {
+                        Vector::x()
+                    })
+                    .normalize();
+                let bitangent = direction.cross(&tangent);
+                let theta = 2. * PI * rand();
+                let amplitude = (tangent * theta.cos() + bitangent * theta.sin()) * magnitude;
+
+                TurbulenceMode {
+                    wavevector: direction * omega,
+                    amplitude,
+                    phase: 2. * PI * rand(),
+                }
+            })
+            .collect();
+
+        // Each mode contributes half of its squared amplitude to the variance of the
+        // velocity, which should add up to the intensity squared per component.
+        let variance: f32 = modes
+            .iter()
+            .map(|mode| mode.amplitude.norm_squared() * 0.5)
+            .sum();
+        if 0. < variance {
+            let scale = intensity * (3. / variance).sqrt();
+            for mode in &mut modes {
+                mode.amplitude *= scale;
+            }
+        }
+        self.turbulence = modes;
+    }
+
+    /// Advances the time of the gusts and the advection of the turbulence.
+    pub fn advance(&mut self, delta_time: f64) {
+        self.time += delta_time;
+    }
+
+    /// Returns the wind velocity at the given world position.
+    pub fn velocity_at(&self, pos: &Point<f32>) -> Vector<f32> {
+        let altitude = pos.y.max(0.);
+        let shear = if self.shear_exponent == 0. {
+            1.
+        } else {
+            (altitude / self.reference_altitude).powf(self.shear_exponent)
+        };
+        let mut ret = self.steady * shear;
+
+        for gust in &self.gusts {
+            ret += gust.velocity(self.time);
+        }
+
+        // Taylor's frozen turbulence hypothesis: the field moves with the steady wind.
+        let advected = pos.coords - self.steady * self.time as f32;
+        for mode in &self.turbulence {
+            ret += mode.amplitude * (mode.wavevector.dot(&advected) + mode.phase).cos();
+        }
+        ret
+    }
+}
+
+#[test]
+fn test_wind_shear_and_gust() {
+    let mut wind = WindField::parse(
+        r#"
+steady = [10.0, 0.0, 0.0]
+reference_altitude = 100.0
+shear_exponent = 0.5
+
+[[gusts]]
+start = 1.0
+duration = 2.0
+amplitude = [0.0, 4.0, 0.0]
+"#,
+    )
+    .unwrap();
+    let v = wind.velocity_at(&point![0., 400., 0.]);
+    assert!((v - vector![20., 0., 0.]).norm() < 1e-4);
+    assert_eq!(wind.velocity_at(&point![0., 0., 0.]), Vector::zeros());
+    wind.advance(2.);
+    let v = wind.velocity_at(&point![0., 100., 0.]);
+    assert!((v - vector![10., 4., 0.]).norm() < 1e-4);
+
+    // A negative or non-finite shear exponent would blow up the wind near the ground.
+    for exponent in ["-0.5", "nan", "inf"] {
+        let Err(err) = WindField::parse(&format!("shear_exponent = {exponent}")) else {
+            panic!("shear_exponent = {exponent} should be rejected");
+        };
+        assert_eq!(err.to_string(), "shear_exponent must be non-negative");
+    }
+    for (src, expected) in [
+        ("steady = [nan, 0.0, 0.0]", "steady wind must be finite"),
+        (
+            "[[gusts]]\nstart = inf\nduration = 1.0\namplitude = [0.0, 1.0, 0.0]",
+            "gust start and amplitude must be finite",
+        ),
+        (
+            "[[gusts]]\nstart = 0.0\nduration = 1.0\namplitude = [0.0, nan, 0.0]",
+            "gust start and amplitude must be finite",
+        ),
+    ] {
+        let Err(err) = WindField::parse(src) else {
+            panic!("{src} should be rejected");
+        };
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_turbulence() {
+    let turbulence = |seed| {
+        let mut wind = WindField::default();
+        wind.set_turbulence(TurbulenceModel::Dryden, 2., 200., seed);
+        wind
+    };
+    let wind = turbulence(1);
+    let samples: Vec<_> = (0..10000)
+        .map(|i| wind.velocity_at(&point![i as f32 * 10., 500., 0.]))
+        .collect();
+    let mean_square = samples.iter().map(|v| v.norm_squared()).sum::<f32>() / samples.len() as f32;
+    // RMS of each component should be near the intensity
+    let rms = (mean_square / 3.).sqrt();
+    assert!((1.5..2.5).contains(&rms), "rms = {rms}");
+
+    // The same seed reproduces the same field
+    let p = point![12., 34., 56.];
+    assert_eq!(turbulence(1).velocity_at(&p), wind.velocity_at(&p));
+    assert_ne!(turbulence(2).velocity_at(&p), wind.velocity_at(&p));
+}